@@ -0,0 +1,138 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt, io};
+
+/// How threads are spread over the cores, and the caches those share
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThreadPlacement {
+    /// Fill every SMT sibling of a physical core before moving on to the next, so that pairs of
+    /// threads share their L1 and L2 caches
+    Pack,
+
+    /// One thread per physical core before any core gets a second, so threads only share the
+    /// last level cache
+    Spread,
+
+    /// Round robin across sockets, one physical core at a time, so threads share as little as
+    /// possible
+    Sockets,
+}
+
+impl fmt::Display for ThreadPlacement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+/// Orders `cores` for threads to be pinned to one after the other, following `placement`
+#[cfg(target_os = "linux")]
+pub fn order_cores(
+    cores: &[usize],
+    placement: ThreadPlacement,
+) -> Result<Vec<usize>, Box<dyn Error>> {
+    let mut topology = Vec::new();
+    for &core in cores {
+        let read = |name: &str| -> Result<usize, Box<dyn Error>> {
+            let path = format!("/sys/devices/system/cpu/cpu{core}/topology/{name}");
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read {path} ({e})"))?;
+            Ok(contents.trim().parse()?)
+        };
+        topology.push((read("physical_package_id")?, read("core_id")?, core));
+    }
+    topology.sort_unstable();
+    // Rank each core among the SMT siblings sharing its physical core, and each physical core
+    // among those of its socket
+    let (mut physical, mut sibling) = (0, 0);
+    let mut ordered = Vec::new();
+    for (index, &(package, core_id, core)) in topology.iter().enumerate() {
+        if index > 0 {
+            let (previous_package, previous_core_id, _) = topology[index - 1];
+            if previous_package != package {
+                (physical, sibling) = (0, 0);
+            } else if previous_core_id != core_id {
+                (physical, sibling) = (physical + 1, 0);
+            } else {
+                sibling += 1;
+            }
+        }
+        let key = match placement {
+            ThreadPlacement::Pack => (package, physical, sibling),
+            ThreadPlacement::Spread => (sibling, package, physical),
+            ThreadPlacement::Sockets => (sibling, physical, package),
+        };
+        ordered.push((key, core));
+    }
+    ordered.sort_unstable();
+    Ok(ordered.into_iter().map(|(_, core)| core).collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn order_cores(
+    _cores: &[usize],
+    _placement: ThreadPlacement,
+) -> Result<Vec<usize>, Box<dyn Error>> {
+    Err("placing threads by the cpu's topology is only supported on linux".into())
+}
+
+/// Cores the current thread is allowed to run on
+#[cfg(target_os = "linux")]
+pub fn available_cores() -> io::Result<Vec<usize>> {
+    // SAFETY: cpu_set_t is a plain bitmask, valid when zeroed, and is only written by the kernel
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((0..libc::CPU_SETSIZE as usize)
+            .filter(|&core| libc::CPU_ISSET(core, &set))
+            .collect())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn available_cores() -> io::Result<Vec<usize>> {
+    Ok((0..std::thread::available_parallelism()?.get()).collect())
+}
+
+/// Core the current thread is running on, as far as it can tell
+#[cfg(target_os = "linux")]
+pub fn current_core() -> Option<usize> {
+    // SAFETY: sched_getcpu has no preconditions
+    let core = unsafe { libc::sched_getcpu() };
+    usize::try_from(core).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn current_core() -> Option<usize> {
+    None
+}
+
+/// Restricts the current thread to run only on the given core
+#[cfg(target_os = "linux")]
+pub fn pin_to_core(core: usize) -> io::Result<()> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("core {core} is out of range"),
+        ));
+    }
+    // SAFETY: see `available_cores`
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_to_core(_core: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "pinning threads to cores is only supported on linux",
+    ))
+}
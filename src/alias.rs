@@ -0,0 +1,87 @@
+use crate::{
+    chase::{follow, link_random_cycle, CACHE_LINE},
+    record::{unix_millis, Mode, Output, Record},
+};
+use clap::Parser;
+use rand::thread_rng;
+use std::{error::Error, mem::size_of, path::PathBuf};
+
+#[derive(Parser)]
+pub struct AliasArgs {
+    /// Maximum number of conflicting lines to chase through, counting up from 1
+    #[clap(short, long, default_value_t = 64)]
+    max_lines: usize,
+
+    /// Distances in bytes between the conflicting lines; each must be a power of two of at least
+    /// a cache line. Lines this far apart map to the same cache set, so the latency jumps once
+    /// there are more of them than the cache has ways
+    #[clap(short, long, value_delimiter = ',', default_value = "4096,65536")]
+    stride: Vec<usize>,
+
+    /// Number of dependent loads to perform per line count and stride
+    #[clap(short, long, default_value_t = 100_000_000)]
+    iterations: usize,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_alias(args: AliasArgs) -> Result<(), Box<dyn Error>> {
+    if args.max_lines == 0 {
+        return Err("--max-lines must be at least 1".into());
+    }
+    if let Some(stride) = args
+        .stride
+        .iter()
+        .find(|&&stride| !stride.is_power_of_two() || stride < CACHE_LINE)
+    {
+        return Err(format!(
+            "stride {stride} is not a power of two of at least {CACHE_LINE} bytes"
+        )
+        .into());
+    }
+
+    let max_stride = args.stride.iter().copied().max().unwrap_or(CACHE_LINE);
+    let mut out = Output::create(args.out, args.append)?;
+
+    println!("Allocating {} bytes", args.max_lines * max_stride);
+    let mut chain: Vec<usize> = vec![0; args.max_lines * max_stride / size_of::<usize>()];
+
+    let mut rng = thread_rng();
+    for &stride in &args.stride {
+        let stride_elements = stride / size_of::<usize>();
+        for lines in 1..=args.max_lines {
+            let mut nodes: Vec<usize> = (0..lines).map(|line| line * stride_elements).collect();
+            let start = link_random_cycle(&mut chain, &mut nodes, &mut rng);
+
+            println!("Chasing {lines} lines {stride} bytes apart");
+            let start_time = unix_millis();
+            let (index, total_duration) = follow(&chain, start, args.iterations);
+            let total_duration_float = total_duration.as_secs_f32();
+            let steps_per_second = (args.iterations as f32) / total_duration_float;
+            let latency_ns = total_duration.as_nanos() as f64 / args.iterations as f64;
+            println!(
+                "\rCompleted testing: took {total_duration_float:.3} secs, with an average latency of {latency_ns:.2} ns/access. end: {index}"
+            );
+            out.write(&Record {
+                start_time,
+                step_size: lines as u64,
+                total_duration_millis: total_duration.as_millis(),
+                steps_per_second,
+                avg_latency_ns: Some(latency_ns),
+                mode: Mode::Alias,
+                stride: Some(stride as u64),
+                ..Record::default()
+            })?;
+        }
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
@@ -0,0 +1,312 @@
+use crate::{
+    plot::{series_labels, swept_size},
+    record::{read_records, Record},
+};
+use clap::Parser;
+use std::{collections::BTreeMap, error::Error, path::PathBuf};
+
+#[derive(Parser)]
+pub struct AnalyzeArgs {
+    /// File containing test data to analyze
+    data_file: PathBuf,
+
+    /// Smallest increase in how fast latency grows per doubling of the size, as a fraction, that
+    /// counts as a knee; latency must also keep growing at least this fast after it. Lower values
+    /// also report gentler bends
+    #[clap(long, default_value_t = 0.1)]
+    knee_threshold: f64,
+
+    /// Also fit a model of this many levels of cache in front of memory to each series, and print
+    /// each level's estimated latency and capacity. The model assumes accesses spread uniformly
+    /// over the working set, so it suits a total size sweep of uniformly random accesses, such as
+    /// `test --sweep total-size` with an initial step size at least the total size, or `chase`
+    #[clap(long)]
+    levels: Option<usize>,
+}
+
+pub fn run_analyze(args: AnalyzeArgs) -> Result<(), Box<dyn Error>> {
+    if args.knee_threshold <= 0.0 {
+        return Err("--knee-threshold must be greater than 0".into());
+    }
+    if args.levels == Some(0) {
+        return Err("--levels must be at least 1".into());
+    }
+    let data = read_records(&args.data_file)?;
+    if data.is_empty() {
+        return Err("No data".into());
+    }
+    let (size_name, _) = swept_size(&data);
+
+    for (label, points) in latency_curves(&data) {
+        let knees = knees(&points, args.knee_threshold);
+        println!("{label}:");
+        if knees.is_empty() {
+            println!("  No knees");
+        }
+        for knee in knees {
+            println!("  Knee at {}", knee.describe(&points, size_name));
+        }
+        if let Some(levels) = args.levels {
+            match fit_model(&points, levels) {
+                Some(model) => print_model(&model),
+                None => println!(
+                    "  Too few distinct sizes to fit {levels} cache levels; at least {} are needed",
+                    levels + 2
+                ),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Each series' latency in nanoseconds against the size swept, sorted by size, keeping the first
+/// record of each size
+pub fn latency_curves(data: &[Record]) -> BTreeMap<String, Vec<(u64, f64)>> {
+    let (_, size) = swept_size(data);
+    let mut series: BTreeMap<String, Vec<(u64, f64)>> = BTreeMap::new();
+    for (record, label) in data.iter().zip(series_labels(data)) {
+        series
+            .entry(label)
+            .or_default()
+            .push((size(record), latency_ns(record)));
+    }
+    for points in series.values_mut() {
+        points.sort_by_key(|&(size, _)| size);
+        points.dedup_by_key(|&mut (size, _)| size);
+    }
+    series
+}
+
+/// Average latency of a record's accesses in nanoseconds
+pub fn latency_ns(record: &Record) -> f64 {
+    record
+        .avg_latency_ns
+        .unwrap_or(1e9 / record.steps_per_second as f64)
+}
+
+/// Percentage a latency grows by per doubling, given the slope of its natural log against the
+/// size's base 2 log
+fn growth_percent(slope: f64) -> f64 {
+    (slope.exp() - 1.0) * 100.0
+}
+
+/// A point where the latency curve bends upward
+pub struct Knee {
+    /// Index of the knee's point in the curve
+    pub index: usize,
+    /// Slopes of log latency per doubling of the size on either side of the knee
+    pub slope_before: f64,
+    pub slope_after: f64,
+}
+
+impl Knee {
+    /// Where the knee is in the curve `points` it was found in, and how it bends there
+    pub fn describe(&self, points: &[(u64, f64)], size_name: &str) -> String {
+        let (size, latency) = points[self.index];
+        format!(
+            "{size_name} {size}: {latency:.2} ns/access, with latency growing {:.1}% per doubling after it against {:.1}% before",
+            growth_percent(self.slope_after),
+            growth_percent(self.slope_before)
+        )
+    }
+}
+
+/// Finds the knees in a latency curve, sorted by size: the points where log latency, against log
+/// size, turns upward by more than `threshold` per doubling and more sharply than at either
+/// neighbouring point, and keeps growing by more than `threshold` per doubling after it, so that
+/// recovering from a noisy dip doesn't count. Working in logs makes the threshold relative, so it
+/// applies alike to the cheap cache levels and to memory
+pub fn knees(points: &[(u64, f64)], threshold: f64) -> Vec<Knee> {
+    let slopes: Vec<f64> = points
+        .windows(2)
+        .map(|pair| {
+            let [(size_a, latency_a), (size_b, latency_b)] = [pair[0], pair[1]];
+            (latency_b.ln() - latency_a.ln()) / ((size_b as f64).log2() - (size_a as f64).log2())
+        })
+        .collect();
+    // Bends at each interior point, where bends[i] is at points[i + 1]
+    let bends: Vec<f64> = slopes.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let min_bend = threshold.ln_1p();
+    (0..bends.len())
+        .filter(|&i| {
+            bends[i] > min_bend
+                && slopes[i + 1] > min_bend
+                && (i == 0 || bends[i] >= bends[i - 1])
+                && bends.get(i + 1).is_none_or(|&next| bends[i] > next)
+        })
+        .map(|i| Knee {
+            index: i + 1,
+            slope_before: slopes[i],
+            slope_after: slopes[i + 1],
+        })
+        .collect()
+}
+
+/// Latency of uniformly random accesses over a working set of `w` bytes, modelled as a hierarchy
+/// of fully associative caches: an access misses a cache holding `c` bytes with probability
+/// `1 - c / w` once the working set outgrows it, and each miss adds that level's penalty
+struct Model {
+    /// Latency of an access that hits the first level
+    base_ns: f64,
+    /// Capacity in bytes of each level, and the latency added by missing it
+    levels: Vec<(f64, f64)>,
+    /// Root mean square difference between the model and the measurements
+    rms_ns: f64,
+}
+
+impl Model {
+    /// Fraction of accesses to a working set of `size` bytes that miss a cache of `capacity` bytes
+    fn miss_rate(capacity: f64, size: f64) -> f64 {
+        (1.0 - capacity / size).max(0.0)
+    }
+}
+
+/// Fits a model of `levels` caches in front of memory to a latency curve, by trying every
+/// combination of capacities on a grid of quarter powers of two spanning the measured sizes, and
+/// solving for the latencies by least squares. Combinations that would need a negative penalty
+/// are rejected, and the one leaving the smallest error is kept
+fn fit_model(points: &[(u64, f64)], levels: usize) -> Option<Model> {
+    if points.len() < levels + 2 {
+        return None;
+    }
+    let min = (points[0].0 as f64).log2();
+    let max = (points[points.len() - 1].0 as f64).log2();
+    let capacities: Vec<f64> = (0..)
+        .map(|i| min + i as f64 / 4.0)
+        .take_while(|&exponent| exponent <= max)
+        .map(f64::exp2)
+        .collect();
+
+    let mut best: Option<Model> = None;
+    let mut chosen = Vec::with_capacity(levels);
+    search_capacities(&capacities, 0, levels, &mut chosen, &mut |chosen| {
+        let Some(model) = solve_penalties(points, chosen) else {
+            return;
+        };
+        if best.as_ref().is_none_or(|best| model.rms_ns < best.rms_ns) {
+            best = Some(model);
+        }
+    });
+    best
+}
+
+/// Calls `visit` with every increasing choice of `remaining` more capacities from
+/// `capacities[start..]`, appended to `chosen`
+fn search_capacities(
+    capacities: &[f64],
+    start: usize,
+    remaining: usize,
+    chosen: &mut Vec<f64>,
+    visit: &mut impl FnMut(&[f64]),
+) {
+    if remaining == 0 {
+        visit(chosen);
+        return;
+    }
+    for i in start..capacities.len() {
+        chosen.push(capacities[i]);
+        search_capacities(capacities, i + 1, remaining - 1, chosen, visit);
+        chosen.pop();
+    }
+}
+
+/// Solves for the base latency and the penalty of each level with the given capacities, by
+/// least squares through the normal equations. Fails if the system is singular, or if any
+/// penalty comes out negative
+fn solve_penalties(points: &[(u64, f64)], capacities: &[f64]) -> Option<Model> {
+    let n = capacities.len() + 1;
+    let features = |size: u64| {
+        std::iter::once(1.0)
+            .chain(
+                capacities
+                    .iter()
+                    .map(move |&capacity| Model::miss_rate(capacity, size as f64)),
+            )
+            .collect::<Vec<f64>>()
+    };
+    // Augmented normal equations, [X^T X | X^T y]
+    let mut system = vec![vec![0.0; n + 1]; n];
+    for &(size, latency) in points {
+        let row = features(size);
+        for (equation, &feature) in system.iter_mut().zip(&row) {
+            for (coefficient, &other) in equation.iter_mut().zip(&row) {
+                *coefficient += feature * other;
+            }
+            equation[n] += feature * latency;
+        }
+    }
+    // Gaussian elimination with partial pivoting
+    for column in 0..n {
+        let pivot = (column..n)
+            .max_by(|&a, &b| system[a][column].abs().total_cmp(&system[b][column].abs()))?;
+        if system[pivot][column].abs() < 1e-9 {
+            return None;
+        }
+        system.swap(column, pivot);
+        let pivot = system[column].clone();
+        for (row, equation) in system.iter_mut().enumerate() {
+            if row != column {
+                let factor = equation[column] / pivot[column];
+                for (value, &pivot_value) in equation[column..].iter_mut().zip(&pivot[column..]) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+    let solution: Vec<f64> = (0..n).map(|i| system[i][n] / system[i][i]).collect();
+    if solution[1..].iter().any(|&penalty| penalty < 0.0) {
+        return None;
+    }
+    let squared_error: f64 = points
+        .iter()
+        .map(|&(size, latency)| {
+            let modelled: f64 = features(size)
+                .iter()
+                .zip(&solution)
+                .map(|(feature, coefficient)| feature * coefficient)
+                .sum();
+            (modelled - latency).powi(2)
+        })
+        .sum();
+    Some(Model {
+        base_ns: solution[0],
+        levels: capacities
+            .iter()
+            .copied()
+            .zip(solution[1..].iter().copied())
+            .collect(),
+        rms_ns: (squared_error / points.len() as f64).sqrt(),
+    })
+}
+
+/// Prints each level of a fitted model with its latency and capacity
+fn print_model(model: &Model) {
+    println!(
+        "  Fitted {} cache levels, with an error of {:.2} ns/access:",
+        model.levels.len(),
+        model.rms_ns
+    );
+    let mut latency = model.base_ns;
+    for (level, &(capacity, penalty)) in model.levels.iter().enumerate() {
+        println!(
+            "    Level {}: {latency:.2} ns/access, holding about {}",
+            level + 1,
+            format_bytes(capacity)
+        );
+        latency += penalty;
+    }
+    println!("    Memory: {latency:.2} ns/access");
+}
+
+/// Formats a size in bytes with a binary unit
+pub fn format_bytes(bytes: f64) -> String {
+    let units = ["bytes", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", units[unit])
+}
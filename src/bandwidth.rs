@@ -0,0 +1,150 @@
+use crate::record::{unix_millis, Mode, Output, Record};
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt, hint::black_box, mem::size_of, path::PathBuf, time::Instant};
+
+/// Multiplier used by the scale and triad kernels
+const SCALAR: f64 = 3.0;
+
+/// One of the STREAM benchmark's kernels, operating on arrays `a`, `b` and `c`
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kernel {
+    /// c = a
+    Copy,
+
+    /// b = scalar * c
+    Scale,
+
+    /// c = a + b
+    Add,
+
+    /// a = b + scalar * c
+    Triad,
+}
+
+impl Kernel {
+    /// Number of arrays read or written per element, for counting the bytes moved
+    fn arrays(self) -> usize {
+        match self {
+            Kernel::Copy | Kernel::Scale => 2,
+            Kernel::Add | Kernel::Triad => 3,
+        }
+    }
+}
+
+impl fmt::Display for Kernel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+#[derive(Parser)]
+pub struct BandwidthArgs {
+    /// Initial size of each of the three arrays in bytes
+    #[clap(short = 'd', long, default_value_t = 4096)]
+    min_size: usize,
+
+    /// Maximum size of each of the three arrays in bytes
+    #[clap(short, long, default_value_t = 256 * 1024 * 1024)]
+    max_size: usize,
+
+    /// Minimum number of elements to process per kernel and size. Small arrays are processed
+    /// repeatedly until at least this many elements have been handled
+    #[clap(short, long, default_value_t = 1_000_000_000)]
+    iterations: usize,
+
+    /// Kernels to run at each size
+    #[clap(
+        short,
+        long,
+        value_delimiter = ',',
+        default_value = "copy,scale,add,triad"
+    )]
+    kernel: Vec<Kernel>,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_bandwidth(args: BandwidthArgs) -> Result<(), Box<dyn Error>> {
+    if args.min_size < size_of::<f64>() || args.max_size < args.min_size {
+        return Err("--min-size must be at least 8 bytes and no larger than --max-size".into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    println!("Allocating arrays");
+    let len = args.max_size / size_of::<f64>();
+    let mut a: Vec<f64> = vec![1.0; len];
+    let mut b: Vec<f64> = vec![2.0; len];
+    let mut c: Vec<f64> = vec![0.0; len];
+
+    let mut size = args.min_size;
+    while size <= args.max_size {
+        let elements = size / size_of::<f64>();
+        let passes = args.iterations.div_ceil(elements);
+        for &kernel in &args.kernel {
+            println!("Testing {kernel} kernel over {size} byte arrays");
+            let start_time = unix_millis();
+            let start_instant = Instant::now();
+            for _ in 0..passes {
+                // Hide the arrays from the optimizer so repeated passes aren't folded into one
+                let (a, b, c) =
+                    black_box((&mut a[..elements], &mut b[..elements], &mut c[..elements]));
+                run_kernel(kernel, a, b, c);
+            }
+            let total_duration = Instant::now().duration_since(start_instant);
+            let total_duration_float = total_duration.as_secs_f32();
+            let processed = passes * elements;
+            let steps_per_second = (processed as f32) / total_duration_float;
+            let gigabytes_per_second = (processed * kernel.arrays() * size_of::<f64>()) as f32
+                / total_duration_float
+                / 1e9;
+            println!(
+                "Completed testing: took {total_duration_float:.3} secs, with an average bandwidth of {gigabytes_per_second:.2} GB/s"
+            );
+            out.write(&Record {
+                start_time,
+                step_size: size as u64,
+                total_duration_millis: total_duration.as_millis(),
+                steps_per_second,
+                mode: Mode::Bandwidth,
+                kernel: Some(kernel),
+                gigabytes_per_second: Some(gigabytes_per_second),
+                ..Record::default()
+            })?;
+        }
+        size <<= 1;
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
+
+fn run_kernel(kernel: Kernel, a: &mut [f64], b: &mut [f64], c: &mut [f64]) {
+    match kernel {
+        Kernel::Copy => c.copy_from_slice(a),
+        Kernel::Scale => {
+            for (b, c) in b.iter_mut().zip(c.iter()) {
+                *b = SCALAR * c;
+            }
+        }
+        Kernel::Add => {
+            for ((c, a), b) in c.iter_mut().zip(a.iter()).zip(b.iter()) {
+                *c = a + b;
+            }
+        }
+        Kernel::Triad => {
+            for ((a, b), c) in a.iter_mut().zip(b.iter()).zip(c.iter()) {
+                *a = b + SCALAR * c;
+            }
+        }
+    }
+}
@@ -0,0 +1,495 @@
+use crate::numa;
+use clap::ValueEnum;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt,
+    fs::OpenOptions,
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Size of the pages backing a buffer
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum PageSize {
+    /// The system's default page size, usually 4KiB
+    #[default]
+    #[value(name = "base")]
+    Base,
+
+    /// Explicit 2MiB huge pages, which must have been reserved beforehand
+    #[value(name = "2m")]
+    Huge2m,
+
+    /// Explicit 1GiB huge pages, which must have been reserved beforehand
+    #[value(name = "1g")]
+    Huge1g,
+}
+
+impl PageSize {
+    /// Size of a page in bytes
+    pub fn bytes(self) -> usize {
+        match self {
+            PageSize::Base => base_page_size(),
+            PageSize::Huge2m => 2 << 20,
+            PageSize::Huge1g => 1 << 30,
+        }
+    }
+}
+
+impl fmt::Display for PageSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+/// Flag asking mmap to fault in the whole mapping up front, when prefaulting. Mappings placed on
+/// NUMA nodes aren't populated, since that would allocate their pages before they can be placed;
+/// touching every page after filling the buffer faults them in all the same
+#[cfg(target_os = "linux")]
+fn populate_flag(options: &BufferOptions) -> libc::c_int {
+    if options.prefault && options.numa_policy().is_none() {
+        libc::MAP_POPULATE
+    } else {
+        0
+    }
+}
+
+#[cfg(unix)]
+fn base_page_size() -> usize {
+    // SAFETY: sysconf has no preconditions
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+#[cfg(not(unix))]
+fn base_page_size() -> usize {
+    4096
+}
+
+/// Where the memory of a buffer comes from
+#[derive(Clone, Default, PartialEq, Eq)]
+pub enum Backing {
+    /// Anonymous memory
+    #[default]
+    Anonymous,
+
+    /// A shared mapping of the file at the given path
+    File(PathBuf),
+
+    /// A mapping of a device-DAX character device, such as persistent or CXL attached memory
+    DevDax(PathBuf),
+
+    /// A synchronous mapping of a file on a filesystem mounted with DAX, which maps persistent
+    /// memory directly rather than through the page cache
+    FsDax(PathBuf),
+}
+
+impl FromStr for Backing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.split_once(':') {
+            _ if s == "anonymous" => Ok(Backing::Anonymous),
+            Some(("mmap", path)) if !path.is_empty() => Ok(Backing::File(path.into())),
+            Some(("devdax", path)) if !path.is_empty() => Ok(Backing::DevDax(path.into())),
+            Some(("fsdax", path)) if !path.is_empty() => Ok(Backing::FsDax(path.into())),
+            _ => Err(format!(
+                "{s} is not one of anonymous, mmap:<path>, devdax:<path> or fsdax:<path>"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Backing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backing::Anonymous => f.write_str("anonymous"),
+            Backing::File(path) => write!(f, "mmap:{}", path.to_string_lossy()),
+            Backing::DevDax(path) => write!(f, "devdax:{}", path.to_string_lossy()),
+            Backing::FsDax(path) => write!(f, "fsdax:{}", path.to_string_lossy()),
+        }
+    }
+}
+
+/// How the pages of a buffer are placed across NUMA nodes
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NumaPolicy {
+    /// Every page on the node given by --memory-node, or wherever the kernel puts it without one
+    #[default]
+    Bind,
+
+    /// Pages spread round robin across every node with memory, as big-memory applications are
+    /// often run
+    Interleave,
+}
+
+impl fmt::Display for NumaPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+/// What a buffer is filled with before it's used
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BufferInit {
+    /// Random bytes from the standard generator, ChaCha12, seeded afresh for each 64KiB chunk
+    #[default]
+    Random,
+
+    /// Random bytes from splitmix64, which is much cheaper to generate. Too weak for anything but
+    /// keeping the data from being trivially predictable or compressible
+    FastRandom,
+
+    /// The bytes 0 to 255 repeating, the same for every seed
+    Pattern,
+
+    /// Zeros, written out so that every page is backed by memory of its own rather than left
+    /// mapped to the kernel's shared zero page
+    Zeros,
+}
+
+impl BufferInit {
+    /// Fills a chunk of a buffer, with data generated from `seed` if it's random
+    fn fill(self, bytes: &mut [u8], seed: u64) {
+        match self {
+            BufferInit::Random => StdRng::seed_from_u64(seed).fill(bytes),
+            BufferInit::FastRandom => {
+                let mut state = seed;
+                for word in bytes.chunks_mut(8) {
+                    let value = splitmix64(&mut state).to_ne_bytes();
+                    word.copy_from_slice(&value[..word.len()]);
+                }
+            }
+            BufferInit::Pattern => bytes
+                .iter_mut()
+                .enumerate()
+                .for_each(|(index, byte)| *byte = index as u8),
+            BufferInit::Zeros => bytes.fill(0),
+        }
+    }
+
+    /// What a buffer filled this way holds, for messages
+    pub fn describe(self) -> &'static str {
+        match self {
+            BufferInit::Random => "random data",
+            BufferInit::FastRandom => "fast random data",
+            BufferInit::Pattern => "patterned data",
+            BufferInit::Zeros => "zeros",
+        }
+    }
+}
+
+impl fmt::Display for BufferInit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+/// Advances a splitmix64 generator, returning its next value
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// How the memory of a buffer is provided
+#[derive(Clone, Default)]
+pub struct BufferOptions {
+    pub backing: Backing,
+    pub page_size: PageSize,
+    /// NUMA node to allocate the buffer on, rather than leaving placement to the kernel
+    pub node: Option<usize>,
+    /// Spread the buffer's pages across every NUMA node with memory instead
+    pub interleave: bool,
+    /// Lock the buffer into memory once it's filled, so it can't be paged out
+    pub mlock: bool,
+    /// Populate mappings as they're made, and touch every page once the buffer is filled, so that
+    /// none of it is still to be faulted in when timing starts
+    pub prefault: bool,
+    /// What the buffer is filled with
+    pub init: BufferInit,
+}
+
+impl BufferOptions {
+    /// How the buffer is placed across NUMA nodes, if it's placed at all
+    pub fn numa_policy(&self) -> Option<NumaPolicy> {
+        if self.interleave {
+            Some(NumaPolicy::Interleave)
+        } else {
+            self.node.map(|_| NumaPolicy::Bind)
+        }
+    }
+
+    /// Sets the NUMA policy of a freshly made mapping for the buffer, before any of its pages are
+    /// touched
+    #[cfg(target_os = "linux")]
+    fn place(&self, ptr: *mut u8, len: usize) -> Result<(), Box<dyn Error>> {
+        if let Some(node) = self.node {
+            numa::bind_memory(ptr, len, node)?;
+        }
+        if self.interleave {
+            numa::interleave_memory(ptr, len)?;
+        }
+        Ok(())
+    }
+}
+
+/// A buffer of bytes to run a benchmark over
+pub enum Buffer {
+    Heap(Vec<u8>),
+    #[cfg(target_os = "linux")]
+    Mapped {
+        ptr: *mut u8,
+        len: usize,
+        map_len: usize,
+    },
+}
+
+// SAFETY: a mapped buffer is uniquely owned, just like a Vec
+unsafe impl Send for Buffer {}
+unsafe impl Sync for Buffer {}
+
+impl Buffer {
+    /// Allocates `len` bytes of random data as described by `options`
+    pub fn random(len: usize, options: &BufferOptions) -> Result<Self, Box<dyn Error>> {
+        Buffer::seeded(len, options, rand::random())
+    }
+
+    /// Allocates `len` bytes as described by `options`, filling them with data generated from
+    /// `seed` when it's random, so that it's the same data every time for the same seed
+    pub fn seeded(len: usize, options: &BufferOptions, seed: u64) -> Result<Self, Box<dyn Error>> {
+        let mut buffer = match &options.backing {
+            Backing::File(path) | Backing::DevDax(path) | Backing::FsDax(path) => {
+                Buffer::map_file(len, path, options)?
+            }
+            Backing::Anonymous
+                if options.page_size == PageSize::Base && options.numa_policy().is_none() =>
+            {
+                Buffer::Heap(vec![0; len])
+            }
+            Backing::Anonymous => Buffer::map(len, options)?,
+        };
+        // Each chunk gets its own generator, so the data doesn't depend on how rayon splits the work
+        buffer
+            .par_chunks_mut(1 << 16)
+            .enumerate()
+            .for_each(|(chunk, bytes)| options.init.fill(bytes, seed.wrapping_add(chunk as u64)));
+        if options.mlock {
+            buffer.lock()?;
+        }
+        if options.prefault {
+            buffer.touch_pages();
+        }
+        Ok(buffer)
+    }
+
+    /// Writes a byte back to itself on every page, faulting in any page that filling the buffer
+    /// left unmapped or that has since been reclaimed, and breaking any sharing of its pages
+    fn touch_pages(&mut self) {
+        let page_size = base_page_size();
+        for offset in (0..self.len()).step_by(page_size) {
+            let byte = &mut self[offset] as *mut u8;
+            // SAFETY: the byte is in bounds, and volatile accesses keep the write from being
+            // elided for storing the value that's already there
+            unsafe { byte.write_volatile(byte.read_volatile()) };
+        }
+    }
+
+    /// Locks every page of the buffer into memory
+    #[cfg(unix)]
+    fn lock(&self) -> Result<(), Box<dyn Error>> {
+        // SAFETY: the buffer's memory is valid for its whole length
+        if unsafe { libc::mlock(self.as_ptr().cast(), self.len()) } == 0 {
+            return Ok(());
+        }
+        let error = std::io::Error::last_os_error();
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: getrlimit only writes to the struct it's given
+        let limit = if unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) } == 0 {
+            format!("{} bytes", limit.rlim_cur)
+        } else {
+            "unknown".to_string()
+        };
+        Err(format!(
+            "failed to lock {} bytes into memory ({error}); RLIMIT_MEMLOCK is {limit}, so raise it with `ulimit -l` or in /etc/security/limits.conf, or run as root",
+            self.len()
+        )
+        .into())
+    }
+
+    #[cfg(not(unix))]
+    fn lock(&self) -> Result<(), Box<dyn Error>> {
+        Err("--mlock is only supported on unix".into())
+    }
+
+    /// Maps fresh pages for the buffer, which can be placed before they're first touched
+    #[cfg(target_os = "linux")]
+    fn map(len: usize, options: &BufferOptions) -> Result<Self, Box<dyn Error>> {
+        let page_size = options.page_size;
+        let huge_flags = match page_size {
+            PageSize::Base => 0,
+            PageSize::Huge2m => libc::MAP_HUGETLB | libc::MAP_HUGE_2MB,
+            PageSize::Huge1g => libc::MAP_HUGETLB | libc::MAP_HUGE_1GB,
+        };
+        // Mappings must be a whole number of pages long
+        let map_len = len.next_multiple_of(page_size.bytes());
+        // SAFETY: an anonymous mapping doesn't alias any existing memory
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                map_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | huge_flags | populate_flag(options),
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            let error = std::io::Error::last_os_error();
+            return Err(if page_size == PageSize::Base {
+                format!("failed to map {len} bytes ({error})")
+            } else {
+                format!("failed to map {len} bytes of {page_size} huge pages ({error}); make sure enough are reserved in /sys/kernel/mm/hugepages")
+            }
+            .into());
+        }
+        let buffer = Buffer::Mapped {
+            ptr: ptr.cast(),
+            len,
+            map_len,
+        };
+        options.place(ptr.cast(), map_len)?;
+        Ok(buffer)
+    }
+
+    /// Maps the file or device at `path` for the buffer. Files are first created or resized to
+    /// `len` bytes
+    #[cfg(target_os = "linux")]
+    fn map_file(len: usize, path: &Path, options: &BufferOptions) -> Result<Self, Box<dyn Error>> {
+        use std::os::fd::AsRawFd;
+
+        /// Fails the mapping unless it maps persistent memory directly. Has the same value on
+        /// every architecture, but isn't defined by libc for all of them
+        const MAP_SYNC: libc::c_int = 0x80000;
+        /// Alignment that device-DAX mappings must have, unless the device was configured with a
+        /// larger one
+        const DAX_ALIGN: usize = 2 << 20;
+
+        if options.page_size != PageSize::Base {
+            return Err("file backed buffers can only use base pages".into());
+        }
+        let device = matches!(options.backing, Backing::DevDax(_));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(!device)
+            .truncate(false)
+            .open(path)
+            .map_err(|e| format!("failed to open {} ({e})", path.to_string_lossy()))?;
+        let (map_len, flags) = match options.backing {
+            Backing::DevDax(_) => (len.next_multiple_of(DAX_ALIGN), libc::MAP_SHARED),
+            Backing::FsDax(_) => (len, libc::MAP_SHARED_VALIDATE | MAP_SYNC),
+            _ => (len, libc::MAP_SHARED),
+        };
+        if !device {
+            file.set_len(len as u64)?;
+        }
+        // SAFETY: a new mapping doesn't alias any existing memory. The file is assumed not to be
+        // truncated by anything else while it's mapped
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                map_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                flags | populate_flag(options),
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            let error = std::io::Error::last_os_error();
+            let hint = match options.backing {
+                Backing::FsDax(_) if error.raw_os_error() == Some(libc::EOPNOTSUPP) => {
+                    "; make sure the filesystem is mounted with -o dax"
+                }
+                Backing::DevDax(_) => "; make sure the device's alignment is at most 2MiB",
+                _ => "",
+            };
+            return Err(format!("failed to map {} ({error}){hint}", path.to_string_lossy()).into());
+        }
+        let buffer = Buffer::Mapped {
+            ptr: ptr.cast(),
+            len,
+            map_len,
+        };
+        options.place(ptr.cast(), map_len)?;
+        Ok(buffer)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn map(_len: usize, _options: &BufferOptions) -> Result<Self, Box<dyn Error>> {
+        Err("huge pages and NUMA placement are only supported on linux".into())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn map_file(
+        _len: usize,
+        _path: &Path,
+        _options: &BufferOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        Err("file backed buffers are only supported on linux".into())
+    }
+}
+
+impl Deref for Buffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Buffer::Heap(vec) => vec,
+            // SAFETY: the mapping is at least `len` bytes long and lives as long as `self`
+            #[cfg(target_os = "linux")]
+            Buffer::Mapped { ptr, len, .. } => unsafe { std::slice::from_raw_parts(*ptr, *len) },
+        }
+    }
+}
+
+impl DerefMut for Buffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            Buffer::Heap(vec) => vec,
+            // SAFETY: see `deref`
+            #[cfg(target_os = "linux")]
+            Buffer::Mapped { ptr, len, .. } => unsafe {
+                std::slice::from_raw_parts_mut(*ptr, *len)
+            },
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        // Heap memory can stay mapped after it's freed, so unlock it in case it was locked.
+        // Unlocking memory that isn't locked does nothing
+        #[cfg(unix)]
+        if let Buffer::Heap(vec) = self {
+            // SAFETY: the vec's memory is valid for its whole length
+            unsafe { libc::munlock(vec.as_ptr().cast(), vec.len()) };
+        }
+        #[cfg(target_os = "linux")]
+        if let Buffer::Mapped { ptr, map_len, .. } = *self {
+            // SAFETY: the mapping is owned by this buffer and no longer borrowed
+            unsafe { libc::munmap(ptr.cast(), map_len) };
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{
+    measure::measure,
+    record::{unix_millis, Mode, Output, Record},
+};
+use clap::Parser;
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use std::{error::Error, mem::size_of, path::PathBuf, time::Duration};
+
+/// Size of a cache line in bytes; each node of the chain occupies its own line
+pub const CACHE_LINE: usize = 64;
+
+#[derive(Parser)]
+pub struct ChaseArgs {
+    /// Initial working set size in bytes. Rounded down to a whole number of cache lines
+    #[clap(short = 'd', long, default_value_t = 4096)]
+    min_size: usize,
+
+    /// Maximum working set size in bytes
+    #[clap(short, long, default_value_t = 1024 * 1024 * 1024)]
+    max_size: usize,
+
+    /// Number of dependent loads to perform per working set size
+    #[clap(short, long, default_value_t = 100_000_000)]
+    iterations: usize,
+
+    /// Maximum number of independent chains to walk interleaved with one another, doubling from
+    /// 1 at each size. Comparing throughput with one chain and many measures memory level
+    /// parallelism
+    #[clap(short, long, default_value_t = 1)]
+    chains: usize,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_chase(args: ChaseArgs) -> Result<(), Box<dyn Error>> {
+    if args.min_size < CACHE_LINE || args.max_size < args.min_size {
+        return Err(format!(
+            "--min-size must be at least {CACHE_LINE} bytes and no larger than --max-size"
+        )
+        .into());
+    }
+    if args.chains == 0 || args.chains > args.min_size / CACHE_LINE {
+        return Err("--chains must be at least 1 and no more than the lines in --min-size".into());
+    }
+    if args.iterations < args.chains {
+        return Err("--iterations must be at least --chains, to load from every chain".into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    println!("Allocating chain buffer");
+    let mut chain: Vec<usize> = vec![0; args.max_size / size_of::<usize>()];
+
+    let stride = CACHE_LINE / size_of::<usize>();
+    let mut rng = thread_rng();
+    let mut size = args.min_size;
+    while size <= args.max_size {
+        let mut chains = 1;
+        while chains <= args.chains {
+            println!("Building {chains} chains over {size} bytes");
+            let mut nodes: Vec<usize> = (0..size / CACHE_LINE).map(|line| line * stride).collect();
+            nodes.shuffle(&mut rng);
+            let per_chain = nodes.len() / chains;
+            let mut starts: Vec<usize> = nodes
+                .chunks_exact_mut(per_chain)
+                .take(chains)
+                .map(|nodes| link_random_cycle(&mut chain, nodes, &mut rng))
+                .collect();
+
+            println!("Chasing pointers over {size} bytes");
+            let start_time = unix_millis();
+            let (loads, total_duration) = follow_interleaved(&chain, &mut starts, args.iterations);
+            let total_duration_float = total_duration.as_secs_f32();
+            let steps_per_second = (loads as f32) / total_duration_float;
+            let latency_ns = total_duration.as_nanos() as f64 / loads as f64;
+            println!(
+                "\rCompleted chase: took {total_duration_float:.3} secs, with an average latency of {latency_ns:.2} ns/access. end: {}",
+                starts[0]
+            );
+            out.write(&Record {
+                start_time,
+                step_size: size as u64,
+                total_duration_millis: total_duration.as_millis(),
+                steps_per_second,
+                avg_latency_ns: Some(latency_ns),
+                mode: Mode::Chase,
+                chains: Some(chains as u64),
+                ..Record::default()
+            })?;
+            chains <<= 1;
+        }
+        size <<= 1;
+    }
+    println!("Finished chasing pointers");
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Links the elements of `chain` at the indices in `nodes` into a single cycle that visits them in
+/// a random order, by shuffling `nodes` and pointing each one at the next and the last back at
+/// the first. Returns the index to start following the cycle from.
+pub fn link_random_cycle(chain: &mut [usize], nodes: &mut [usize], rng: &mut impl Rng) -> usize {
+    nodes.shuffle(rng);
+    for pair in nodes.windows(2) {
+        chain[pair[0]] = pair[1];
+    }
+    chain[*nodes.last().unwrap()] = nodes[0];
+    nodes[0]
+}
+
+/// Follows `chain` for `iterations` dependent loads, starting at `start`. Returns the index it ended
+/// on along with the time taken.
+pub fn follow(chain: &[usize], start: usize, iterations: usize) -> (usize, Duration) {
+    let mut index = start;
+    let total_duration = measure(iterations, || index = chain[index]);
+    (index, total_duration)
+}
+
+/// Follows several chains at once, advancing each of `indices` by one load in turn, for
+/// `iterations` loads in total, rounded down to a whole number of loads of every chain. The loads
+/// of different chains are independent, so the cpu can have one in flight per chain. Leaves each
+/// index where its chain ended, and returns the number of loads made along with the time taken.
+fn follow_interleaved(
+    chain: &[usize],
+    indices: &mut [usize],
+    iterations: usize,
+) -> (usize, Duration) {
+    let rounds = iterations / indices.len();
+    let total_duration = measure(rounds, || {
+        for index in indices.iter_mut() {
+            *index = chain[*index];
+        }
+    });
+    (rounds * indices.len(), total_duration)
+}
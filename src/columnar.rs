@@ -0,0 +1,263 @@
+use crate::record::Record;
+use arrow_array::{
+    ArrayRef, BooleanArray, Float32Array, Float64Array, Int64Array, RecordBatch, StringArray,
+    UInt64Array,
+};
+use arrow_ipc::reader::FileReader;
+use arrow_json::LineDelimitedWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde::{
+    de::{self, value::StrDeserializer, IntoDeserializer, MapAccess, Visitor},
+    forward_to_deserialize_any, Deserialize, Deserializer,
+};
+use serde_json::{Map, Value};
+use std::{error::Error, io::Cursor, sync::Arc};
+
+/// What every Arrow IPC file starts with
+pub const ARROW_MAGIC: &[u8] = b"ARROW1";
+/// What every Parquet file starts and ends with
+pub const PARQUET_MAGIC: &[u8] = b"PAR1";
+
+/// The columns of every record, typed as `Record`'s fields are, so that files of different runs
+/// share one schema whichever measurements each took. Every column is nullable, as most fields
+/// are optional
+pub fn record_schema() -> Schema {
+    let mut fields = Vec::new();
+    Record::deserialize(StructTracer(&mut fields))
+        .unwrap_or_else(|err| unreachable!("records deserialize from any fields ({err})"));
+    Schema::new(
+        fields
+            .into_iter()
+            .map(|(name, data_type)| Field::new(name, data_type, true))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Lays out records, as the fields they serialize to, as columns of a single batch in the schema
+/// of `record_schema`
+pub fn record_batch(rows: &[Map<String, Value>]) -> Result<RecordBatch, Box<dyn Error>> {
+    let schema = Arc::new(record_schema());
+    let mut columns: Vec<ArrayRef> = Vec::new();
+    for field in schema.fields() {
+        let values = rows
+            .iter()
+            .map(|row| row.get(field.name()).filter(|value| !value.is_null()));
+        let column: ArrayRef = match field.data_type() {
+            DataType::Boolean => Arc::new(BooleanArray::from_iter(
+                values.map(|value| value.and_then(Value::as_bool)),
+            )),
+            DataType::UInt64 => Arc::new(UInt64Array::from_iter(
+                values.map(|value| value.and_then(Value::as_u64)),
+            )),
+            DataType::Int64 => Arc::new(Int64Array::from_iter(
+                values.map(|value| value.and_then(Value::as_i64)),
+            )),
+            DataType::Float32 => {
+                Arc::new(Float32Array::from_iter(values.map(|value| {
+                    value.and_then(Value::as_f64).map(|value| value as f32)
+                })))
+            }
+            DataType::Float64 => Arc::new(Float64Array::from_iter(
+                values.map(|value| value.and_then(Value::as_f64)),
+            )),
+            _ => Arc::new(StringArray::from_iter(values.map(|value| {
+                value.map(|value| match value {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                })
+            }))),
+        };
+        columns.push(column);
+    }
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+/// Deserializes a struct without any data, noting the name and Arrow type of each of its fields
+/// as it asks for them
+struct StructTracer<'a>(&'a mut Vec<(&'static str, DataType)>);
+
+impl<'de> Deserializer<'de> for StructTracer<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only structs can be traced"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(FieldsTracer {
+            fields: fields.iter(),
+            name: "",
+            traced: self.0,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+/// Hands a struct each of its fields in turn, with a value that notes the field's type
+struct FieldsTracer<'a> {
+    fields: std::slice::Iter<'static, &'static str>,
+    /// The field whose value is asked for next
+    name: &'static str,
+    traced: &'a mut Vec<(&'static str, DataType)>,
+}
+
+impl<'de> MapAccess<'de> for FieldsTracer<'_> {
+    type Error = de::value::Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some(&name) = self.fields.next() else {
+            return Ok(None);
+        };
+        self.name = name;
+        let key: StrDeserializer<Self::Error> = name.into_deserializer();
+        seed.deserialize(key).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let mut data_type = DataType::Null;
+        let value = seed.deserialize(FieldTracer(&mut data_type))?;
+        self.traced.push((self.name, data_type));
+        Ok(value)
+    }
+}
+
+/// Deserializes a placeholder for a field, noting the Arrow type that holds its values. Unsigned
+/// integers are all widened to 64 bits, and enums, which records hold as their names, are text
+struct FieldTracer<'a>(&'a mut DataType);
+
+impl<'de> Deserializer<'de> for FieldTracer<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom(
+            "fields must have a concrete type to be traced",
+        ))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        *self.0 = DataType::Boolean;
+        visitor.visit_bool(false)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        *self.0 = DataType::UInt64;
+        visitor.visit_u64(0)
+    }
+
+    /// Times in milliseconds, which fit in 64 bits for the next half a billion years
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        *self.0 = DataType::UInt64;
+        visitor.visit_u128(0)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        *self.0 = DataType::Int64;
+        visitor.visit_i64(0)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        *self.0 = DataType::Float32;
+        visitor.visit_f32(0.0)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        *self.0 = DataType::Float64;
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        *self.0 = DataType::Utf8;
+        visitor.visit_string(String::new())
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = DataType::Utf8;
+        let variant: StrDeserializer<Self::Error> = variants[0].into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    forward_to_deserialize_any! {
+        i128 char bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct map
+        struct identifier ignored_any
+    }
+}
+
+/// Reads the rows of every batch of an Arrow IPC file back into the fields of records, leaving
+/// out the fields that are null
+pub fn read_ipc(bytes: Vec<u8>) -> Result<Vec<Map<String, Value>>, Box<dyn Error>> {
+    rows(FileReader::try_new(Cursor::new(bytes), None)?)
+}
+
+/// Reads the rows of a Parquet file back into the fields of records, as `read_ipc` does
+pub fn read_parquet(bytes: Vec<u8>) -> Result<Vec<Map<String, Value>>, Box<dyn Error>> {
+    rows(ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(bytes))?.build()?)
+}
+
+/// The rows of batches, as the fields of records
+fn rows(
+    batches: impl Iterator<Item = Result<RecordBatch, ArrowError>>,
+) -> Result<Vec<Map<String, Value>>, Box<dyn Error>> {
+    let mut lines = LineDelimitedWriter::new(Vec::new());
+    for batch in batches {
+        lines.write(&batch?)?;
+    }
+    lines.finish()?;
+    let lines = lines.into_inner();
+    Ok(serde_json::Deserializer::from_slice(&lines)
+        .into_iter()
+        .try_collect()?)
+}
@@ -0,0 +1,124 @@
+use crate::{
+    record::{unix_millis, Mode, Output, Record},
+    store::Store,
+};
+use clap::Parser;
+use std::{error::Error, hint::black_box, mem::size_of, path::PathBuf, time::Instant};
+
+#[derive(Parser)]
+pub struct CombiningArgs {
+    /// Maximum number of concurrent store streams, counting up from 1
+    #[clap(short, long, default_value_t = 32)]
+    max_streams: usize,
+
+    /// Size in bytes of the buffer the streams are spread across
+    #[clap(short, long, default_value_t = 64 * 1024 * 1024)]
+    size: usize,
+
+    /// Minimum number of 8 byte stores to perform per store kind and stream count. The buffer is
+    /// filled repeatedly until at least this many stores have been made
+    #[clap(short, long, default_value_t = 1_000_000_000)]
+    iterations: usize,
+
+    /// Kinds of stores to test at each stream count
+    #[clap(
+        short = 't',
+        long,
+        value_delimiter = ',',
+        default_value = "regular,non-temporal"
+    )]
+    store: Vec<Store>,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_combining(args: CombiningArgs) -> Result<(), Box<dyn Error>> {
+    let elements = args.size / size_of::<u64>();
+    if args.max_streams == 0 || args.max_streams > elements {
+        return Err("--max-streams must be at least 1 and no more than the words in --size".into());
+    }
+    if cfg!(not(target_arch = "x86_64")) && args.store.contains(&Store::NonTemporal) {
+        return Err("non-temporal stores are only supported on x86_64".into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    println!("Allocating buffer");
+    let mut buffer: Vec<u64> = vec![0; elements];
+
+    for streams in 1..=args.max_streams {
+        let stream_len = elements / streams;
+        let stores = stream_len * streams;
+        let passes = args.iterations.div_ceil(stores);
+        for &store in &args.store {
+            println!("Testing {streams} streams of {store} stores");
+            let start_time = unix_millis();
+            let start_instant = Instant::now();
+            for pass in 0..passes {
+                // Hide the buffer from the optimizer so earlier passes aren't discarded as dead
+                // stores
+                let buffer = black_box(&mut buffer[..stores]);
+                fill_streams(buffer, streams, pass as u64, store);
+            }
+            let total_duration = Instant::now().duration_since(start_instant);
+            let total_duration_float = total_duration.as_secs_f32();
+            let steps_per_second = ((passes * stores) as f32) / total_duration_float;
+            let gigabytes_per_second =
+                (passes * stores * size_of::<u64>()) as f32 / total_duration_float / 1e9;
+            println!(
+                "Completed testing: took {total_duration_float:.3} secs, with an average store rate of {steps_per_second:.2} steps/sec ({gigabytes_per_second:.2} GB/s)"
+            );
+            out.write(&Record {
+                start_time,
+                step_size: streams as u64,
+                total_duration_millis: total_duration.as_millis(),
+                steps_per_second,
+                mode: Mode::Combining,
+                store: Some(store),
+                gigabytes_per_second: Some(gigabytes_per_second),
+                ..Record::default()
+            })?;
+        }
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Splits `buffer` into `streams` equal regions and overwrites them all with `value`, storing to
+/// the next element of every region in turn, so each store goes to a different cache line than
+/// the last
+fn fill_streams(buffer: &mut [u64], streams: usize, value: u64, store: Store) {
+    let stream_len = buffer.len() / streams;
+    match store {
+        Store::Regular => {
+            for offset in 0..stream_len {
+                for stream in 0..streams {
+                    buffer[stream * stream_len + offset] = value;
+                }
+            }
+        }
+        #[cfg(target_arch = "x86_64")]
+        Store::NonTemporal => {
+            use std::arch::x86_64::{_mm_sfence, _mm_stream_si64};
+            for offset in 0..stream_len {
+                for stream in 0..streams {
+                    let element = &mut buffer[stream * stream_len + offset];
+                    // SAFETY: the pointer comes from a valid, aligned mutable reference
+                    unsafe { _mm_stream_si64(element as *mut u64 as *mut i64, value as i64) };
+                }
+            }
+            // SAFETY: sse is always available on x86_64
+            unsafe { _mm_sfence() };
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        Store::NonTemporal => unreachable!("non-temporal stores are rejected up front"),
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{
+    plot::series_labels,
+    record::{read_records, Record},
+    stats::{welch_p_value, Aggregate, Summary},
+};
+use clap::Parser;
+use std::{collections::HashMap, error::Error, path::PathBuf};
+
+#[derive(Parser)]
+pub struct CompareArgs {
+    /// Results to compare against
+    baseline: PathBuf,
+
+    /// Results to compare with the baseline, from the same test run after the change being
+    /// evaluated
+    candidate: PathBuf,
+
+    /// Significance level: differences whose p-value is below this are reported as real, and the
+    /// rest as noise
+    #[clap(long, default_value_t = 0.05)]
+    alpha: f64,
+}
+
+pub fn run_compare(args: CompareArgs) -> Result<(), Box<dyn Error>> {
+    if !(0.0..1.0).contains(&args.alpha) || args.alpha == 0.0 {
+        return Err("--alpha must be between 0 and 1".into());
+    }
+    let baseline = read_records(&args.baseline)?;
+    let candidate = read_records(&args.candidate)?;
+
+    let mut candidates: HashMap<String, &Record> = candidate
+        .iter()
+        .map(|record| (step_key(record), record))
+        .collect();
+    let (mut faster, mut slower, mut unchanged, mut untested) = (0, 0, 0, 0);
+    for (before, label) in baseline.iter().zip(series_labels(&baseline)) {
+        let Some(after) = candidates.remove(&step_key(before)) else {
+            continue;
+        };
+        let (a, b) = (summary(before), summary(after));
+        let change = (b.mean / a.mean - 1.0) * 100.0;
+        let mut line = format!("{label} at step size {}", before.step_size);
+        if let Some(total_size) = before.total_size {
+            line += &format!(" of {total_size} bytes");
+        }
+        line += &format!(": {:.2} -> {:.2} steps/sec ({change:+.2}%)", a.mean, b.mean);
+        match welch_p_value(a, b) {
+            Some(p) if p < args.alpha => {
+                if b.mean > a.mean {
+                    faster += 1;
+                    println!("{line}, p = {p:.4}: significantly faster");
+                } else {
+                    slower += 1;
+                    println!("{line}, p = {p:.4}: significantly slower");
+                }
+            }
+            Some(p) => {
+                unchanged += 1;
+                println!("{line}, p = {p:.4}: no significant change");
+            }
+            None => {
+                untested += 1;
+                println!("{line}: too few repeats to test");
+            }
+        }
+    }
+    let matched = faster + slower + unchanged + untested;
+    println!(
+        "Of {matched} steps in both files, {faster} were significantly faster, {slower} significantly slower and {unchanged} unchanged at a significance level of {}",
+        args.alpha
+    );
+    if untested > 0 {
+        println!(
+            "{untested} steps had fewer than two repeats in one of the files, so couldn't be tested; record them with --repeats"
+        );
+    }
+    let unmatched = baseline.len() - matched + candidates.len();
+    if unmatched > 0 {
+        println!("{unmatched} steps only appear in one of the files, and weren't compared");
+    }
+
+    Ok(())
+}
+
+/// Identifies a step across result files: its series, step size, total size and thread count
+pub fn step_key(record: &Record) -> String {
+    let parameters: Vec<String> = record
+        .series_parameters()
+        .into_iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect();
+    format!(
+        "{} step {} total {:?} threads {:?}",
+        parameters.join(","),
+        record.step_size,
+        record.total_size,
+        record.threads
+    )
+}
+
+/// The distribution of a step's repeats, as far as its record describes it. Records without
+/// repeats count as a single sample
+pub fn summary(record: &Record) -> Summary {
+    let mean = record
+        .steps_per_second_mean
+        .or((record.aggregate != Some(Aggregate::Median)).then_some(record.steps_per_second));
+    Summary {
+        mean: mean.unwrap_or(record.steps_per_second) as f64,
+        stddev: record.steps_per_second_stddev.unwrap_or_default() as f64,
+        count: record
+            .repeats_kept
+            .or(record
+                .repeats
+                .filter(|_| record.trim.unwrap_or_default() == 0.0))
+            .unwrap_or(1) as usize,
+    }
+}
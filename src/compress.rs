@@ -0,0 +1,99 @@
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+/// What gzip streams start with
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+/// What zstd frames start with
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression of a results file, on top of its format
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// The compression suggested by a file's extension, such as `.gz` in `results.jsonl.gz`
+    pub fn of_path(path: &Path) -> Compression {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst" | "zstd") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+/// A results file, compressing what's written to it on the way if it's compressed. Flushing
+/// ends the compressed block, so everything written before a flush can be read back even if the
+/// file is never finished
+pub enum Sink {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+    Zstd(zstd::Encoder<'static, File>),
+}
+
+impl Sink {
+    pub fn new(file: File, compression: Compression) -> io::Result<Sink> {
+        Ok(match compression {
+            Compression::None => Sink::Plain(file),
+            Compression::Gzip => Sink::Gzip(GzEncoder::new(file, flate2::Compression::default())),
+            Compression::Zstd => Sink::Zstd(zstd::Encoder::new(file, 0)?),
+        })
+    }
+
+    /// Ends the compressed stream
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Sink::Plain(mut file) => file.flush(),
+            Sink::Gzip(encoder) => encoder.finish().map(drop),
+            Sink::Zstd(encoder) => encoder.finish().map(drop),
+        }
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Plain(file) => file.write(buf),
+            Sink::Gzip(encoder) => encoder.write(buf),
+            Sink::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(file) => file.flush(),
+            Sink::Gzip(encoder) => encoder.flush(),
+            Sink::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Decompresses the contents of a file if they're gzip or zstd, whatever its name, and returns
+/// them as they are otherwise. Files appended to hold one stream after another, which are read
+/// in turn, and a file whose last stream was cut short by an interrupted run is read as far as
+/// it goes
+pub fn decompress(bytes: Vec<u8>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut decoder: Box<dyn Read> = if bytes.starts_with(GZIP_MAGIC) {
+        Box::new(MultiGzDecoder::new(bytes.as_slice()))
+    } else if bytes.starts_with(ZSTD_MAGIC) {
+        Box::new(zstd::Decoder::new(bytes.as_slice())?)
+    } else {
+        return Ok(bytes);
+    };
+    let mut contents = Vec::new();
+    if let Err(err) = decoder.read_to_end(&mut contents) {
+        if contents.is_empty() {
+            return Err(err.into());
+        }
+        println!("Warning: the results file ends partway through ({err}), so only what came before is read");
+    }
+    Ok(contents)
+}
@@ -0,0 +1,174 @@
+use crate::record::{unix_millis, Mode, Output, Record};
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt,
+    mem::size_of,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Barrier,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Which atomics the threads increment
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Atomics {
+    /// Every thread increments the same atomic
+    Single,
+
+    /// Each thread increments its own atomic, padded out to a cache line of its own
+    Padded,
+
+    /// Each increment goes to a pseudorandom atomic in a region of --region-size bytes shared by
+    /// all threads, so contention falls as the region grows
+    Spread,
+}
+
+impl fmt::Display for Atomics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+/// An atomic aligned to, and filling, a whole 64 byte cache line
+#[repr(align(64))]
+#[derive(Default)]
+struct Padded(AtomicU64);
+
+#[derive(Parser)]
+pub struct ContentionArgs {
+    /// Maximum number of threads, doubling from 1 [default number of available cpus]
+    #[clap(short, long)]
+    max_threads: Option<usize>,
+
+    /// Number of increments each thread performs per test
+    #[clap(short, long, default_value_t = 10_000_000)]
+    iterations: usize,
+
+    /// Atomics to increment at each thread count
+    #[clap(
+        short,
+        long,
+        value_delimiter = ',',
+        default_value = "single,padded,spread"
+    )]
+    atomics: Vec<Atomics>,
+
+    /// Size in bytes of the region of atomics used by the spread mode
+    #[clap(short, long, default_value_t = 4096)]
+    region_size: usize,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_contention(args: ContentionArgs) -> Result<(), Box<dyn Error>> {
+    let max_threads = match args.max_threads {
+        Some(max_threads) => max_threads,
+        None => thread::available_parallelism()?.get(),
+    };
+    let region_atomics = args.region_size / size_of::<AtomicU64>();
+    if args.atomics.contains(&Atomics::Spread) && region_atomics == 0 {
+        return Err("--region-size must be at least 8 bytes".into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    let mut threads = 1;
+    while threads <= max_threads {
+        for &atomics in &args.atomics {
+            println!("Testing {threads} threads with {atomics} atomics");
+            let start_time = unix_millis();
+            let (sum, total_duration) = match atomics {
+                Atomics::Single => {
+                    let counter = AtomicU64::default();
+                    contend(threads, args.iterations, |_, _| &counter)
+                }
+                Atomics::Padded => {
+                    let counters: Vec<Padded> = (0..threads).map(|_| Padded::default()).collect();
+                    contend(threads, args.iterations, |thread, _| &counters[thread].0)
+                }
+                Atomics::Spread => {
+                    let region: Vec<AtomicU64> =
+                        (0..region_atomics).map(|_| AtomicU64::default()).collect();
+                    contend(threads, args.iterations, |_, random| {
+                        &region[random as usize % region.len()]
+                    })
+                }
+            };
+            let total_duration_float = total_duration.as_secs_f32();
+            let steps_per_second = ((threads * args.iterations) as f32) / total_duration_float;
+            println!(
+                "Completed testing: took {total_duration_float:.3} secs, with an average increment rate of {steps_per_second:.2} steps/sec. sum: {sum}"
+            );
+            out.write(&Record {
+                start_time,
+                step_size: threads as u64,
+                total_duration_millis: total_duration.as_millis(),
+                steps_per_second,
+                mode: Mode::Contention,
+                atomics: Some(atomics),
+                region_size: (atomics == Atomics::Spread).then_some(args.region_size as u64),
+                ..Record::default()
+            })?;
+        }
+        threads <<= 1;
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Spawns `threads` threads that each perform `iterations` atomic increments, on the atomic
+/// returned by `target` for the thread's index and a fresh pseudorandom number. Returns the sum of
+/// everything the increments returned along with the time taken from when all threads started.
+fn contend<'a>(
+    threads: usize,
+    iterations: usize,
+    target: impl Fn(usize, u64) -> &'a AtomicU64 + Sync,
+) -> (u64, Duration) {
+    let barrier = Barrier::new(threads + 1);
+    let (start_instant, sum) = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|thread| {
+                let (barrier, target) = (&barrier, &target);
+                scope.spawn(move || {
+                    // xorshift is cheap enough not to hide the cost of the increments
+                    let mut random = thread as u64 + 1;
+                    let mut sum: u64 = 0;
+                    barrier.wait();
+                    for _ in 0..iterations {
+                        random ^= random << 13;
+                        random ^= random >> 7;
+                        random ^= random << 17;
+                        let previous = target(thread, random).fetch_add(1, Ordering::Relaxed);
+                        sum = sum.wrapping_add(previous);
+                    }
+                    sum
+                })
+            })
+            .collect();
+        // Start the clock before releasing the threads; once released they may well finish
+        // before this thread is scheduled again
+        let start_instant = Instant::now();
+        barrier.wait();
+        let sum = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .fold(0, u64::wrapping_add);
+        (start_instant, sum)
+    });
+    let total_duration = Instant::now().duration_since(start_instant);
+    (sum, total_duration)
+}
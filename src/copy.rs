@@ -0,0 +1,76 @@
+use crate::{
+    buffer::{Buffer, BufferOptions},
+    record::{unix_millis, Mode, Output, Record},
+};
+use clap::Parser;
+use std::{error::Error, hint::black_box, path::PathBuf, time::Instant};
+
+#[derive(Parser)]
+pub struct CopyArgs {
+    /// Initial copy size in bytes
+    #[clap(short = 'd', long, default_value_t = 8)]
+    min_size: usize,
+
+    /// Maximum copy size in bytes
+    #[clap(short, long, default_value_t = 256 * 1024 * 1024)]
+    max_size: usize,
+
+    /// Minimum number of bytes to copy per size. Small copies are repeated until at least this
+    /// many bytes have been copied
+    #[clap(short, long, default_value_t = 10_000_000_000)]
+    iterations: usize,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_copy(args: CopyArgs) -> Result<(), Box<dyn Error>> {
+    if args.min_size == 0 || args.max_size < args.min_size {
+        return Err("--min-size must be at least 1 byte and no larger than --max-size".into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    println!("Allocating buffers");
+    let source = Buffer::random(args.max_size, &BufferOptions::default())?;
+    let mut destination: Vec<u8> = vec![0; args.max_size];
+
+    let mut size = args.min_size;
+    while size <= args.max_size {
+        let copies = args.iterations.div_ceil(size);
+        println!("Testing {size} byte copies");
+        let start_time = unix_millis();
+        let start_instant = Instant::now();
+        for _ in 0..copies {
+            // Hide the buffers from the optimizer so repeated copies aren't folded into one
+            let (destination, source) = black_box((&mut destination[..size], &source[..size]));
+            destination.copy_from_slice(source);
+        }
+        let total_duration = Instant::now().duration_since(start_instant);
+        let total_duration_float = total_duration.as_secs_f32();
+        let steps_per_second = (copies as f32) / total_duration_float;
+        let gigabytes_per_second = (copies * size) as f32 / total_duration_float / 1e9;
+        println!(
+            "Completed testing: took {total_duration_float:.3} secs, with an average copy rate of {steps_per_second:.2} copies/sec or {gigabytes_per_second:.2} GB/s"
+        );
+        out.write(&Record {
+            start_time,
+            step_size: size as u64,
+            total_duration_millis: total_duration.as_millis(),
+            steps_per_second,
+            mode: Mode::Copy,
+            gigabytes_per_second: Some(gigabytes_per_second),
+            ..Record::default()
+        })?;
+        size <<= 1;
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use crate::{
+    compare::summary,
+    plot::{series_labels, swept_size},
+    record::{read_records, Record},
+};
+use clap::Parser;
+use serde_json::{json, Value};
+use std::{collections::HashSet, error::Error, fs, path::PathBuf};
+
+/// Two-sided 95% quantile of the normal distribution, for confidence intervals
+const Z_95: f64 = 1.959964;
+
+#[derive(Parser)]
+pub struct CriterionArgs {
+    /// File containing test data to export
+    data_file: PathBuf,
+
+    /// Criterion's output directory to write the benchmarks into
+    #[clap(short, long, default_value = "target/criterion")]
+    out_dir: PathBuf,
+
+    /// Name of the baseline to save the results as, such as `new`, which Criterion writes its
+    /// latest run to, or a name to pass to `critcmp`
+    #[clap(short, long, default_value = "new")]
+    baseline: String,
+}
+
+pub fn run_criterion(args: CriterionArgs) -> Result<(), Box<dyn Error>> {
+    if args.baseline.is_empty() || args.baseline.contains(['/', '\\']) {
+        return Err("--baseline must be a plain directory name".into());
+    }
+    let data = read_records(&args.data_file)?;
+    if data.is_empty() {
+        return Err("No data".into());
+    }
+    let (_, size) = swept_size(&data);
+
+    let mut exported = HashSet::new();
+    let mut replaced = 0;
+    for (record, label) in data.iter().zip(series_labels(&data)) {
+        // Each series is a function of the group of its mode, and each size a value of it, as
+        // benchmarks parameterised with `BenchmarkId` are
+        let group_id = record.mode.to_string();
+        let function_id = (label != group_id).then_some(label);
+        let value_str = size(record).to_string();
+        let ids: Vec<&str> = [Some(&group_id), function_id.as_ref(), Some(&value_str)]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        let full_id = ids.join("/");
+        let directory_name = ids
+            .iter()
+            .map(|id| filename_safe(id))
+            .collect::<Vec<_>>()
+            .join("/");
+        if !exported.insert(directory_name.clone()) {
+            replaced += 1;
+        }
+
+        let bytes_per_access = record
+            .gigabytes_per_second
+            .map(|gigabytes| (gigabytes as f64 * 1e9 / record.steps_per_second as f64).round());
+        let throughput = match bytes_per_access {
+            Some(bytes) if bytes >= 1.0 => json!({ "Bytes": bytes as u64 }),
+            _ => json!({ "Elements": 1 }),
+        };
+        let benchmark = json!({
+            "group_id": group_id,
+            "function_id": function_id,
+            "value_str": value_str,
+            "throughput": throughput,
+            "full_id": full_id,
+            "directory_name": directory_name,
+            "title": full_id,
+        });
+
+        let dir = args.out_dir.join(&directory_name).join(&args.baseline);
+        fs::create_dir_all(&dir)?;
+        let write = |name: &str, value: &Value| -> Result<(), Box<dyn Error>> {
+            Ok(fs::write(dir.join(name), serde_json::to_vec(value)?)?)
+        };
+        write("benchmark.json", &benchmark)?;
+        write("estimates.json", &estimates(record))?;
+        write("sample.json", &sample(record))?;
+        write("tukey.json", &tukey(record))?;
+    }
+    if replaced > 0 {
+        println!(
+            "Warning: {replaced} records measured a step already exported, and took its place; the file likely holds several runs"
+        );
+    }
+    println!(
+        "Exported {} benchmarks to {} as baseline {}",
+        exported.len(),
+        args.out_dir.to_string_lossy(),
+        args.baseline
+    );
+
+    Ok(())
+}
+
+/// Criterion's statistics of a record's time per access in nanoseconds. Records only keep the
+/// mean, median and standard deviation of their repeats' rates, so these are converted to times
+/// to first order and the confidence intervals taken as normal, rather than bootstrapped as
+/// Criterion does
+fn estimates(record: &Record) -> Value {
+    let rates = summary(record);
+    let mean = 1e9 / rates.mean;
+    let std_dev = 1e9 * rates.stddev / (rates.mean * rates.mean);
+    let median = record
+        .steps_per_second_median
+        .map_or(mean, |median| 1e9 / median as f64);
+    let standard_error = std_dev / (rates.count as f64).sqrt();
+    let stats = |point_estimate: f64, standard_error: f64| {
+        json!({
+            "confidence_interval": {
+                "confidence_level": 0.95,
+                "lower_bound": point_estimate - Z_95 * standard_error,
+                "upper_bound": point_estimate + Z_95 * standard_error,
+            },
+            "point_estimate": point_estimate,
+            "standard_error": standard_error,
+        })
+    };
+    // The median absolute deviation of a normal distribution, and the standard error of its
+    // median and of its standard deviation
+    let median_abs_dev = 0.6745 * std_dev;
+    let std_dev_error = std_dev / (2.0 * rates.count as f64).sqrt();
+    json!({
+        "mean": stats(mean, standard_error),
+        "median": stats(median, 1.2533 * standard_error),
+        "median_abs_dev": stats(median_abs_dev, 0.6745 * std_dev_error),
+        "slope": null,
+        "std_dev": stats(std_dev, std_dev_error),
+    })
+}
+
+/// The step as a single sample of all its accesses
+fn sample(record: &Record) -> Value {
+    let iterations = record.iterations.unwrap_or(1) as f64;
+    let mean_ns = 1e9 / summary(record).mean;
+    json!({
+        "sampling_mode": "Flat",
+        "iters": [iterations],
+        "times": [iterations * mean_ns],
+    })
+}
+
+/// Criterion's fences for outliers, at 1.5 and 3 interquartile ranges beyond the quartiles, as
+/// they'd be for normally distributed times
+fn tukey(record: &Record) -> Value {
+    let rates = summary(record);
+    let mean = 1e9 / rates.mean;
+    let iqr = 1.349 * 1e9 * rates.stddev / (rates.mean * rates.mean);
+    let (q1, q3) = (mean - iqr / 2.0, mean + iqr / 2.0);
+    json!([
+        q1 - 3.0 * iqr,
+        q1 - 1.5 * iqr,
+        q3 + 1.5 * iqr,
+        q3 + 3.0 * iqr
+    ])
+}
+
+/// An id made safe to name a directory with, the way Criterion does
+fn filename_safe(id: &str) -> String {
+    id.chars()
+        .map(|c| match c {
+            '?' | '"' | '/' | '\\' | '*' | '<' | '>' | ':' | '|' | '^' => '_',
+            c => c,
+        })
+        .collect()
+}
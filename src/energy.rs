@@ -0,0 +1,142 @@
+use std::{error::Error, fs, path::PathBuf};
+
+/// Where linux exposes the RAPL energy counters, on both intel and amd cpus
+const POWERCAP: &str = "/sys/class/powercap";
+
+/// A RAPL energy counter, which wraps around after `max_range_uj` microjoules
+struct Zone {
+    path: PathBuf,
+    max_range_uj: u64,
+}
+
+impl Zone {
+    fn open(path: PathBuf) -> Result<Self, Box<dyn Error>> {
+        let max_range_uj = read_uj(&path.join("max_energy_range_uj"))?;
+        let zone = Zone { path, max_range_uj };
+        // Reading the counter is usually restricted to root, so fail up front rather than midway
+        zone.read()?;
+        Ok(zone)
+    }
+
+    fn read(&self) -> Result<u64, Box<dyn Error>> {
+        read_uj(&self.path.join("energy_uj"))
+    }
+}
+
+fn read_uj(path: &PathBuf) -> Result<u64, Box<dyn Error>> {
+    let contents = fs::read_to_string(path).map_err(|err| {
+        format!(
+            "failed to read {} ({err}); reading RAPL energy counters usually requires root",
+            path.display()
+        )
+    })?;
+    Ok(contents.trim().parse()?)
+}
+
+/// Energy consumed over some span of time
+#[derive(Clone, Copy)]
+pub struct Energy {
+    /// Summed over every package, including all of their cores and not just the one running the
+    /// test
+    pub package_joules: f64,
+    /// Summed over every package's memory controllers, when the cpu reports them separately
+    pub dram_joules: Option<f64>,
+}
+
+impl Energy {
+    /// Adds the energy consumed over another span of time
+    pub fn merge(&mut self, other: Energy) {
+        self.package_joules += other.package_joules;
+        self.dram_joules = match (self.dram_joules, other.dram_joules) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+/// The RAPL package and DRAM energy counters of every package
+pub struct Rapl {
+    package: Vec<Zone>,
+    dram: Vec<Zone>,
+}
+
+/// Values of every counter of a [`Rapl`], in microjoules
+pub struct Reading(Vec<u64>);
+
+impl Rapl {
+    /// Finds every package's energy counters, failing if there are none or they can't be read
+    pub fn open() -> Result<Self, Box<dyn Error>> {
+        let zone_paths = |dir: &PathBuf, depth: usize| -> Vec<PathBuf> {
+            let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .and_then(|name| name.strip_prefix("intel-rapl:"))
+                        .is_some_and(|ids| ids.split(':').count() == depth)
+                })
+                .collect();
+            paths.sort();
+            paths
+        };
+        let name = |path: &PathBuf| fs::read_to_string(path.join("name")).unwrap_or_default();
+
+        let (mut package, mut dram) = (Vec::new(), Vec::new());
+        for package_path in zone_paths(&PathBuf::from(POWERCAP), 1) {
+            if !name(&package_path).starts_with("package") {
+                continue;
+            }
+            for subzone in zone_paths(&package_path, 2) {
+                if name(&subzone).trim() == "dram" {
+                    dram.push(Zone::open(subzone)?);
+                }
+            }
+            package.push(Zone::open(package_path)?);
+        }
+        if package.is_empty() {
+            return Err(format!(
+                "no RAPL energy counters were found in {POWERCAP}; they need an intel or amd cpu and the intel_rapl driver, and usually aren't exposed in virtual machines"
+            )
+            .into());
+        }
+        Ok(Rapl { package, dram })
+    }
+
+    /// Reads every counter
+    pub fn read(&self) -> Result<Reading, Box<dyn Error>> {
+        Ok(Reading(
+            self.package
+                .iter()
+                .chain(&self.dram)
+                .map(Zone::read)
+                .try_collect()?,
+        ))
+    }
+
+    /// Energy consumed since `start` was read, assuming each counter wrapped around at most once
+    pub fn since(&self, start: &Reading) -> Result<Energy, Box<dyn Error>> {
+        let end = self.read()?;
+        let mut consumed = self
+            .package
+            .iter()
+            .chain(&self.dram)
+            .zip(start.0.iter().zip(&end.0))
+            .map(|(zone, (&start, &end))| {
+                let uj = if end >= start {
+                    end - start
+                } else {
+                    end + zone.max_range_uj - start
+                };
+                uj as f64 / 1e6
+            });
+        let package_joules = consumed.by_ref().take(self.package.len()).sum();
+        let dram_joules = (!self.dram.is_empty()).then(|| consumed.sum());
+        Ok(Energy {
+            package_joules,
+            dram_joules,
+        })
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{
+    measure::measure,
+    record::{unix_millis, Mode, Output, Record},
+};
+use clap::Parser;
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use std::{error::Error, hint::black_box, path::PathBuf};
+
+#[derive(Parser)]
+pub struct FragmentArgs {
+    /// Total size in bytes of the working set
+    #[clap(short, long, default_value_t = 256 * 1024 * 1024)]
+    total_size: usize,
+
+    /// Sizes in bytes of the separately allocated chunks the working set is split into, each a
+    /// power of two; the whole sweep is run once for each, after a contiguous baseline
+    #[clap(short, long, value_delimiter = ',', default_value = "64,4096,262144")]
+    chunk_size: Vec<usize>,
+
+    /// Initial step size
+    #[clap(short = 'd', long, default_value_t = 1)]
+    initial_step_size: usize,
+
+    /// Maximum step size [default same as --total-size]
+    #[clap(short, long)]
+    max_step_size: Option<usize>,
+
+    /// Number of iterations to run per step
+    #[clap(short, long, default_value_t = 1_000_000_000)]
+    iterations: usize,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_fragment(args: FragmentArgs) -> Result<(), Box<dyn Error>> {
+    if !args.total_size.is_power_of_two() {
+        return Err("--total-size must be a power of two".into());
+    }
+    if args
+        .chunk_size
+        .iter()
+        .any(|&size| !size.is_power_of_two() || size > args.total_size)
+    {
+        return Err("--chunk-size must be powers of two no larger than --total-size".into());
+    }
+    if args.initial_step_size == 0 {
+        return Err("--initial-step-size must be at least 1".into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    let max_step_size = args
+        .max_step_size
+        .unwrap_or(args.total_size)
+        .clamp(args.initial_step_size, args.total_size);
+    let mut rng = thread_rng();
+    // A single chunk spanning the whole working set is the contiguous baseline, addressed in
+    // exactly the same way as the fragmented ones
+    for chunk_size in [args.total_size].into_iter().chain(args.chunk_size) {
+        println!(
+            "Allocating {} chunks of {chunk_size} bytes of random data",
+            args.total_size / chunk_size
+        );
+        let chunks = allocate_chunks(args.total_size, chunk_size, &mut rng);
+        let shift = chunk_size.trailing_zeros();
+        let mask = chunk_size - 1;
+
+        let mut step_size = args.initial_step_size;
+        while step_size <= max_step_size {
+            println!("Testing step size {step_size} across {chunk_size} byte chunks");
+            let start_time = unix_millis();
+            let mut position: usize = 0;
+            let mut sum: u8 = 0;
+            let total_duration = measure(args.iterations, || {
+                let step: usize = rng.gen();
+                let step = step % step_size;
+                // All ones to move down, or all zeroes to move up, as in the test's random walk
+                let down = (rng.gen::<bool>() as usize).wrapping_neg();
+                position =
+                    position.wrapping_add((step ^ down).wrapping_sub(down)) & (args.total_size - 1);
+                sum = sum.wrapping_add(black_box(&chunks)[position >> shift][position & mask]);
+            });
+            let total_duration_float = total_duration.as_secs_f32();
+            let steps_per_second = (args.iterations as f32) / total_duration_float;
+            let latency_ns = total_duration.as_nanos() as f64 / args.iterations as f64;
+            println!(
+                "\rCompleted testing: took {total_duration_float:.3} secs, with an average step rate of {steps_per_second:.2} steps/sec ({latency_ns:.2} ns/access). sum: {sum}"
+            );
+            out.write(&Record {
+                start_time,
+                step_size: step_size as u64,
+                total_duration_millis: total_duration.as_millis(),
+                steps_per_second,
+                avg_latency_ns: Some(latency_ns),
+                mode: Mode::Fragment,
+                total_size: Some(args.total_size as u64),
+                chunk_size: Some(chunk_size as u64),
+                ..Record::default()
+            })?;
+            step_size <<= 1;
+        }
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Allocates `total_size` bytes of random data as separate heap allocations of `chunk_size` bytes
+/// each, in a shuffled order so that chunks which are adjacent in the working set are scattered
+/// across the heap, as they would be after a long running program's allocations interleave
+fn allocate_chunks(total_size: usize, chunk_size: usize, rng: &mut impl Rng) -> Vec<Box<[u8]>> {
+    let mut chunks: Vec<Box<[u8]>> = (0..total_size / chunk_size)
+        .map(|_| (0..chunk_size).map(|_| rng.gen()).collect())
+        .collect();
+    chunks.shuffle(rng);
+    chunks
+}
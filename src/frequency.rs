@@ -0,0 +1,75 @@
+use crate::sampler::Sampler;
+use std::{error::Error, fs};
+
+/// Samples the frequency of whichever core the thread that started it is running on, from a
+/// background thread, until it's finished
+pub struct FrequencySampler {
+    sampler: Sampler,
+}
+
+impl FrequencySampler {
+    /// Starts sampling the current thread's core, failing if its frequency can't be read
+    #[cfg(target_os = "linux")]
+    pub fn start() -> Result<Self, Box<dyn Error>> {
+        // SAFETY: gettid has no preconditions
+        let tid = unsafe { libc::gettid() };
+        if current_frequency_mhz(tid).is_none() {
+            return Err(
+                "the cpu frequency isn't exposed through /sys/devices/system/cpu/*/cpufreq or /proc/cpuinfo"
+                    .into(),
+            );
+        }
+        Ok(FrequencySampler {
+            sampler: Sampler::start(move || current_frequency_mhz(tid)),
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn start() -> Result<Self, Box<dyn Error>> {
+        Err("sampling the cpu frequency is only supported on linux".into())
+    }
+
+    /// Stops sampling, returning every frequency sampled in MHz
+    pub fn finish(self) -> Vec<f64> {
+        self.sampler.finish()
+    }
+}
+
+/// Current frequency in MHz of the core the thread `tid` of this process is running on
+#[cfg(target_os = "linux")]
+fn current_frequency_mhz(tid: libc::pid_t) -> Option<f64> {
+    let core = current_core(tid)?;
+    let scaling = format!("/sys/devices/system/cpu/cpu{core}/cpufreq/scaling_cur_freq");
+    match fs::read_to_string(scaling) {
+        Ok(khz) => Some(khz.trim().parse::<f64>().ok()? / 1000.0),
+        Err(_) => cpuinfo_mhz(core),
+    }
+}
+
+/// Core the thread `tid` of this process last ran on
+#[cfg(target_os = "linux")]
+fn current_core(tid: libc::pid_t) -> Option<usize> {
+    let stat = fs::read_to_string(format!("/proc/self/task/{tid}/stat")).ok()?;
+    // The command name in parentheses may contain spaces, so count fields from after it, where
+    // the processor is the 39th field overall
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(36)?.parse().ok()
+}
+
+/// Frequency of the given core in MHz as reported by /proc/cpuinfo, for systems without cpufreq
+/// such as virtual machines
+#[cfg(target_os = "linux")]
+fn cpuinfo_mhz(core: usize) -> Option<f64> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    let block = cpuinfo.split("\n\n").find(|block| {
+        block.lines().any(|line| {
+            line.split_once(':').is_some_and(|(key, value)| {
+                key.trim() == "processor" && value.trim().parse() == Ok(core)
+            })
+        })
+    })?;
+    block.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key.trim() == "cpu MHz").then(|| value.trim().parse().ok())?
+    })
+}
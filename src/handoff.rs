@@ -0,0 +1,204 @@
+use crate::{
+    affinity::{available_cores, pin_to_core},
+    record::{unix_millis, Mode, Output, Record},
+};
+use clap::Parser;
+use std::{
+    error::Error,
+    hint::{black_box, spin_loop},
+    io,
+    path::PathBuf,
+    slice,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Barrier,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The flag the region is handed over with, alone on its own cache line
+#[repr(align(64))]
+#[derive(Default)]
+struct Line(AtomicU64);
+
+/// The region the two threads take turns with. Only the thread whose turn it is touches it, and
+/// the flag orders each turn after the last
+#[derive(Clone, Copy)]
+struct Region(*mut u64);
+
+// SAFETY: see `Region`
+unsafe impl Send for Region {}
+unsafe impl Sync for Region {}
+
+impl Region {
+    // Taking the whole region, rather than closures capturing only its pointer, keeps them Send
+    fn ptr(self) -> *mut u64 {
+        self.0
+    }
+}
+
+#[derive(Parser)]
+pub struct HandoffArgs {
+    /// Core the producer writes the region on [default the first available core]
+    #[clap(short, long)]
+    producer_core: Option<usize>,
+
+    /// Core the consumer reads the region on [default the second available core]
+    #[clap(short, long)]
+    consumer_core: Option<usize>,
+
+    /// Initial region size in bytes, a multiple of 8
+    #[clap(short = 'd', long, default_value_t = 64)]
+    min_size: usize,
+
+    /// Maximum region size in bytes
+    #[clap(short, long, default_value_t = 64 * 1024 * 1024)]
+    max_size: usize,
+
+    /// Minimum number of bytes to hand over per size. Small regions are handed over until at
+    /// least this many bytes have been
+    #[clap(short, long, default_value_t = 1_000_000_000)]
+    iterations: usize,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_handoff(args: HandoffArgs) -> Result<(), Box<dyn Error>> {
+    if args.min_size == 0 || !args.min_size.is_multiple_of(8) || args.max_size < args.min_size {
+        return Err(
+            "--min-size must be a positive multiple of 8 bytes no larger than --max-size".into(),
+        );
+    }
+    let available = available_cores()?;
+    let mut defaults = available.iter().copied();
+    let producer_core = args.producer_core.or_else(|| defaults.next());
+    let consumer_core = args
+        .consumer_core
+        .or_else(|| defaults.find(|&core| Some(core) != producer_core));
+    let (Some(producer_core), Some(consumer_core)) = (producer_core, consumer_core) else {
+        return Err("at least two cores are needed to hand a region between them".into());
+    };
+    if producer_core == consumer_core {
+        return Err("--producer-core and --consumer-core must be different cores".into());
+    }
+    if let Some(core) = [producer_core, consumer_core]
+        .into_iter()
+        .find(|core| !available.contains(core))
+    {
+        return Err(format!("core {core} isn't available to this process").into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    println!("Allocating a {} byte region", args.max_size);
+    let mut region = vec![0u64; args.max_size / 8];
+
+    let mut size = args.min_size;
+    while size <= args.max_size {
+        let rounds = args.iterations.div_ceil(size);
+        println!("Testing {size} byte regions from core {producer_core} to core {consumer_core}");
+        let start_time = unix_millis();
+        let total_duration = handoff(
+            producer_core,
+            consumer_core,
+            &mut region[..size / 8],
+            rounds,
+        )?;
+        let total_duration_float = total_duration.as_secs_f32();
+        let steps_per_second = (rounds as f32) / total_duration_float;
+        let gigabytes_per_second = (rounds * size) as f32 / total_duration_float / 1e9;
+        let latency_ns = total_duration.as_nanos() as f64 / rounds as f64;
+        println!(
+            "Completed testing: took {total_duration_float:.3} secs, handing over {steps_per_second:.2} regions/sec ({latency_ns:.2} ns each) or {gigabytes_per_second:.2} GB/s"
+        );
+        out.write(&Record {
+            start_time,
+            step_size: size as u64,
+            total_duration_millis: total_duration.as_millis(),
+            steps_per_second,
+            avg_latency_ns: Some(latency_ns),
+            mode: Mode::Handoff,
+            first_core: Some(producer_core),
+            second_core: Some(consumer_core),
+            gigabytes_per_second: Some(gigabytes_per_second),
+            iterations: Some(rounds as u64),
+            ..Record::default()
+        })?;
+        size <<= 1;
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Hands `region` from a producer thread pinned to one core to a consumer pinned to another
+/// `rounds` times. Each round the producer fills the region and raises the flag to an odd value,
+/// then the consumer sums it and answers with the next even value, so every round pulls the
+/// region's lines from the producer's cache into the consumer's. Returns the time taken as seen
+/// by the producer.
+fn handoff(
+    producer_core: usize,
+    consumer_core: usize,
+    region: &mut [u64],
+    rounds: usize,
+) -> io::Result<Duration> {
+    let line = Line::default();
+    let flag = &line.0;
+    let words = region.len();
+    let region = Region(region.as_mut_ptr());
+    // Both threads must be pinned before either starts, or one would spin forever waiting for a
+    // partner that gave up
+    let pinned = Barrier::new(2);
+    let failed = AtomicBool::new(false);
+    let pin = |core| {
+        let result = pin_to_core(core);
+        if result.is_err() {
+            failed.store(true, Ordering::Relaxed);
+        }
+        pinned.wait();
+        result.map(|()| !failed.load(Ordering::Relaxed))
+    };
+    thread::scope(|scope| {
+        let consumer = scope.spawn(|| {
+            if pin(consumer_core)? {
+                for round in 0..rounds as u64 {
+                    while flag.load(Ordering::Acquire) != 2 * round + 1 {
+                        spin_loop();
+                    }
+                    // SAFETY: the producer is waiting for the flag, so nothing else touches the
+                    // region until it's raised again
+                    let words = unsafe { slice::from_raw_parts(region.ptr(), words) };
+                    black_box(words.iter().fold(0u64, |sum, &word| sum.wrapping_add(word)));
+                    flag.store(2 * round + 2, Ordering::Release);
+                }
+            }
+            Ok(())
+        });
+        let producer = scope.spawn(|| {
+            if !pin(producer_core)? {
+                return Ok(Duration::ZERO);
+            }
+            let start_instant = Instant::now();
+            for round in 0..rounds as u64 {
+                // SAFETY: the consumer finished with the region before lowering the flag to this
+                // round, and waits until it's raised
+                unsafe { slice::from_raw_parts_mut(region.ptr(), words) }.fill(black_box(round));
+                flag.store(2 * round + 1, Ordering::Release);
+                while flag.load(Ordering::Acquire) != 2 * round + 2 {
+                    spin_loop();
+                }
+            }
+            Ok(Instant::now().duration_since(start_instant))
+        });
+        let total_duration = producer.join().unwrap();
+        consumer.join().unwrap().and(total_duration)
+    })
+}
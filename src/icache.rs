@@ -0,0 +1,178 @@
+use crate::{
+    chase::CACHE_LINE,
+    measure::measure,
+    record::{unix_millis, Mode, Output, Record},
+};
+use clap::Parser;
+use std::{error::Error, path::PathBuf};
+
+#[derive(Parser)]
+pub struct IcacheArgs {
+    /// Initial size of the generated code in bytes. Rounded down to a whole number of cache lines
+    #[clap(short = 'd', long, default_value_t = 4096)]
+    min_size: usize,
+
+    /// Maximum size of the generated code in bytes
+    #[clap(short, long, default_value_t = 64 * 1024 * 1024)]
+    max_size: usize,
+
+    /// Minimum number of cache line sized blocks of code to execute per size. Small code regions
+    /// are called repeatedly until at least this many blocks have run
+    #[clap(short, long, default_value_t = 1_000_000_000)]
+    iterations: usize,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_icache(args: IcacheArgs) -> Result<(), Box<dyn Error>> {
+    if args.min_size < CACHE_LINE || args.max_size < args.min_size {
+        return Err(format!(
+            "--min-size must be at least {CACHE_LINE} bytes and no larger than --max-size"
+        )
+        .into());
+    }
+    if cfg!(not(all(target_os = "linux", target_arch = "x86_64"))) {
+        return Err("the icache benchmark is only supported on x86_64 linux".into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    let mut size = args.min_size;
+    while size <= args.max_size {
+        let blocks = size / CACHE_LINE;
+        println!("Generating {blocks} blocks of code");
+        let code = Code::generate(blocks)?;
+
+        let calls = args.iterations.div_ceil(blocks);
+        let executed = calls * blocks;
+        println!("Executing {size} bytes of code");
+        let start_time = unix_millis();
+        let total_duration = measure(calls, || code.call());
+        let total_duration_float = total_duration.as_secs_f32();
+        let steps_per_second = (executed as f32) / total_duration_float;
+        println!(
+            "\rCompleted testing: took {total_duration_float:.3} secs, with an average rate of {steps_per_second:.2} blocks/sec"
+        );
+        out.write(&Record {
+            start_time,
+            step_size: size as u64,
+            total_duration_millis: total_duration.as_millis(),
+            steps_per_second,
+            mode: Mode::Icache,
+            total_size: Some(size as u64),
+            ..Record::default()
+        })?;
+        size <<= 1;
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
+
+/// A region of generated machine code, made of cache line sized blocks that each run a line of
+/// nops and then jump to the next block. The blocks are chained in a random order so the next
+/// line prefetcher can't hide the cost of fetching them, and the last one returns.
+struct Code {
+    ptr: *mut u8,
+    len: usize,
+    entry: usize,
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+impl Code {
+    const NOP: u8 = 0x90;
+    const JMP: u8 = 0xe9;
+    const RET: u8 = 0xc3;
+    /// Length of a `jmp rel32` instruction
+    const JMP_LEN: usize = 5;
+
+    fn generate(blocks: usize) -> Result<Self, Box<dyn Error>> {
+        use rand::seq::SliceRandom;
+
+        let len = blocks * CACHE_LINE;
+        // SAFETY: an anonymous mapping doesn't alias any existing memory
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(format!(
+                "failed to map {len} bytes for code ({})",
+                std::io::Error::last_os_error()
+            )
+            .into());
+        }
+        let mut code = Code {
+            ptr: ptr.cast(),
+            len,
+            entry: 0,
+        };
+        // SAFETY: the mapping is `len` bytes long and writable
+        let bytes = unsafe { std::slice::from_raw_parts_mut(code.ptr, len) };
+        bytes.fill(Self::NOP);
+
+        let mut order: Vec<usize> = (0..blocks).collect();
+        order.shuffle(&mut rand::thread_rng());
+        for pair in order.windows(2) {
+            let jump = (pair[0] + 1) * CACHE_LINE - Self::JMP_LEN;
+            // Jumps are relative to the end of the instruction, which is the end of the block
+            let offset = (pair[1] as isize - pair[0] as isize - 1) * CACHE_LINE as isize;
+            bytes[jump] = Self::JMP;
+            bytes[jump + 1..jump + Self::JMP_LEN]
+                .copy_from_slice(&i32::try_from(offset)?.to_le_bytes());
+        }
+        let last = order.last().unwrap();
+        bytes[(last + 1) * CACHE_LINE - 1] = Self::RET;
+
+        // SAFETY: the mapping is owned by `code` and no longer borrowed
+        if unsafe { libc::mprotect(ptr, len, libc::PROT_READ | libc::PROT_EXEC) } != 0 {
+            return Err(format!(
+                "failed to make generated code executable ({})",
+                std::io::Error::last_os_error()
+            )
+            .into());
+        }
+        code.entry = order[0] * CACHE_LINE;
+        Ok(code)
+    }
+
+    /// Runs every block of the code once
+    fn call(&self) {
+        // SAFETY: the entry block starts a chain of jumps through every block that ends in a
+        // return, and touches nothing but the instruction pointer
+        let function: extern "C" fn() = unsafe { std::mem::transmute(self.ptr.add(self.entry)) };
+        function();
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+impl Code {
+    fn generate(_blocks: usize) -> Result<Self, Box<dyn Error>> {
+        unreachable!("unsupported platforms are rejected up front")
+    }
+
+    fn call(&self) {}
+}
+
+impl Drop for Code {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        // SAFETY: the mapping is owned by this code region and no longer in use
+        unsafe {
+            libc::munmap(self.ptr.cast(), self.len)
+        };
+    }
+}
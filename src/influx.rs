@@ -0,0 +1,184 @@
+use crate::record::{fields, Record};
+use serde_json::{Map, Number, Value};
+use std::{error::Error, fmt::Write as _};
+
+/// The measurement every record is a point of
+pub const MEASUREMENT: &str = "cache_locality";
+
+/// Columns written as tags, identifying the step a point measured, rather than as fields
+const TAGS: &[&str] = &[
+    "hostname",
+    "mode",
+    "total_size",
+    "step_size",
+    "thread",
+    "sample",
+];
+
+/// A record as a point of InfluxDB line protocol, without its newline. The machine, mode and
+/// step are tags, along with the series the step belongs to, and every other column a field of
+/// its own type, timestamped in nanoseconds when the step started, or for the samples within a
+/// step, when the sample's interval ended. Integers too large to be signed are written as
+/// unsigned, which InfluxDB 2 and later accept
+pub fn line(record: &Record) -> Result<String, Box<dyn Error>> {
+    let fields = fields(record)?;
+    let mut line = MEASUREMENT.to_string();
+    for &tag in TAGS {
+        let value = match fields.get(tag) {
+            Some(Value::String(text)) => text.clone(),
+            Some(Value::Number(number)) => number.to_string(),
+            _ => continue,
+        };
+        let key = if tag == "hostname" { "host" } else { tag };
+        write!(line, ",{key}={}", escape(&value, ", ="))?;
+    }
+    let series: Vec<String> = record
+        .series_parameters()
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect();
+    write!(line, ",series={}", escape(&series.join(", "), ", ="))?;
+
+    let mut separator = ' ';
+    for (name, value) in &fields {
+        if name == "start_time" || TAGS.contains(&name.as_str()) {
+            continue;
+        }
+        let value = match value {
+            Value::Bool(value) => value.to_string(),
+            Value::Number(number) if number.is_i64() => format!("{number}i"),
+            Value::Number(number) if number.is_u64() => format!("{number}u"),
+            Value::Number(number) => number.to_string(),
+            Value::String(text) => format!("\"{}\"", escape(text, "\"")),
+            Value::Null | Value::Array(_) | Value::Object(_) => continue,
+        };
+        write!(line, "{separator}{}={value}", escape(name, ", ="))?;
+        separator = ',';
+    }
+
+    let millis = record.start_time as f64 + record.sample_time_ms.unwrap_or_default();
+    write!(line, " {}", (millis * 1e6).round() as u128)?;
+    Ok(line)
+}
+
+/// Reads the points of line protocol written by `line` back into the fields of records
+pub fn read_lines(contents: &str) -> Result<Vec<Map<String, Value>>, Box<dyn Error>> {
+    let mut records = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let malformed = || format!("line {} isn't a point of line protocol", number + 1);
+        let parts = split(line, ' ');
+        let [series, point_fields, timestamp] = parts.as_slice() else {
+            return Err(malformed().into());
+        };
+        let mut record = Map::new();
+        for tag in split(series, ',').iter().skip(1) {
+            let (key, value) = split_once(tag, '=').ok_or_else(malformed)?;
+            let value = unescape(&value);
+            match key.as_str() {
+                "series" => {}
+                "host" => {
+                    record.insert("hostname".to_string(), Value::String(value));
+                }
+                _ => {
+                    let value = match value.parse::<u64>() {
+                        Ok(number) => Value::from(number),
+                        Err(_) => Value::String(value),
+                    };
+                    record.insert(key, value);
+                }
+            }
+        }
+        for field in split(point_fields, ',') {
+            let (key, value) = split_once(&field, '=').ok_or_else(malformed)?;
+            record.insert(key, field_value(&value).ok_or_else(malformed)?);
+        }
+        let nanos: f64 = timestamp.parse().map_err(|_| malformed())?;
+        let sample_time_ms = record.get("sample_time_ms").and_then(Value::as_f64);
+        let millis = (nanos / 1e6 - sample_time_ms.unwrap_or_default()).round() as u64;
+        record.insert("start_time".to_string(), millis.into());
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// A field's value, typed the way line protocol marks it
+fn field_value(value: &str) -> Option<Value> {
+    if let Some(text) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        return Some(Value::String(unescape(text)));
+    }
+    if let Some(integer) = value.strip_suffix('i') {
+        return integer.parse::<i64>().ok().map(Value::from);
+    }
+    if let Some(integer) = value.strip_suffix('u') {
+        return integer.parse::<u64>().ok().map(Value::from);
+    }
+    match value {
+        "t" | "T" | "true" | "True" | "TRUE" => Some(Value::Bool(true)),
+        "f" | "F" | "false" | "False" | "FALSE" => Some(Value::Bool(false)),
+        _ => Number::from_f64(value.parse().ok()?).map(Value::Number),
+    }
+}
+
+/// Text with each of `special` escaped by a backslash, along with backslashes themselves
+fn escape(text: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\\' || special.contains(c) {
+            escaped.push('\\');
+        }
+        // Newlines would end the point, and can't be escaped
+        escaped.push(if c == '\n' { ' ' } else { c });
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Splits at each `separator` that isn't escaped or quoted, keeping the escapes
+fn split(text: &str, separator: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let (mut escaped, mut quoted) = (false, false);
+    for c in text.chars() {
+        let part = parts.last_mut().unwrap();
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(String::new());
+            continue;
+        }
+        part.push(c);
+    }
+    parts
+}
+
+/// A tag or field's unescaped key and its value, still escaped
+fn split_once(text: &str, separator: char) -> Option<(String, String)> {
+    let mut parts = split(text, separator).into_iter();
+    let key = parts.next()?;
+    let value: Vec<String> = parts.collect();
+    if value.is_empty() {
+        return None;
+    }
+    Some((unescape(&key), value.join(&separator.to_string())))
+}
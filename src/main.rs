@@ -1,26 +1,91 @@
 #![feature(iterator_try_collect)]
+use alias::{run_alias, AliasArgs};
+use analyze::{run_analyze, AnalyzeArgs};
+use bandwidth::{run_bandwidth, BandwidthArgs};
+use chase::{run_chase, ChaseArgs};
 use clap::{Parser, Subcommand};
-use csv::Reader;
-use plotters::prelude::*;
-use progress_observer::prelude::*;
-use rand::{thread_rng, Rng};
-use rayon::prelude::*;
-use serde::{Deserialize, Serialize};
-use std::{
-    error::Error,
-    io::{stdout, Write},
-    path::PathBuf,
-    time::{Duration, Instant, SystemTime},
-};
-
-#[derive(Serialize, Deserialize)]
-struct Record {
-    start_time: u128,
-    step_size: u64,
-    total_duration_millis: u128,
-    steps_per_second: f32,
+use combining::{run_combining, CombiningArgs};
+use compare::{run_compare, CompareArgs};
+use contention::{run_contention, ContentionArgs};
+use copy::{run_copy, CopyArgs};
+use criterion::{run_criterion, CriterionArgs};
+use fragment::{run_fragment, FragmentArgs};
+use handoff::{run_handoff, HandoffArgs};
+use icache::{run_icache, IcacheArgs};
+use matrix::{run_matrix, MatrixArgs};
+use pingpong::{run_ping_pong, PingPongArgs};
+use placement::{run_placement, PlacementArgs};
+use plot::{plot_data, PlotArgs};
+use prefetch::{run_prefetch, PrefetchArgs};
+use record::is_broken_pipe;
+use report::{run_report, ReportArgs};
+use sharing::{run_sharing, SharingArgs};
+use std::error::Error;
+use store::{run_store, StoreArgs};
+use tlb::{run_tlb, TlbArgs};
+use walk::{run_test, TestArgs};
+use writeback::{run_writeback, WritebackArgs};
+
+// Progress is printed to stdout, which may be piped into a reader that stops early, such as
+// `head`. Printing after it's gone carries on without the progress rather than panicking, so that
+// the run still finishes and saves its results; these shadow std's macros in every module
+macro_rules! print {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        let _ = write!(std::io::stdout(), $($arg)*);
+    }};
 }
 
+macro_rules! println {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        let _ = writeln!(std::io::stdout(), $($arg)*);
+    }};
+}
+
+mod affinity;
+mod alias;
+mod analyze;
+mod bandwidth;
+mod buffer;
+mod chase;
+mod columnar;
+mod combining;
+mod compare;
+mod compress;
+mod contention;
+mod copy;
+mod criterion;
+mod energy;
+mod fragment;
+mod frequency;
+mod handoff;
+mod icache;
+mod influx;
+mod matrix;
+mod measure;
+mod metrics;
+mod numa;
+mod paging;
+mod perf;
+mod pingpong;
+mod placement;
+mod plot;
+mod prefetch;
+mod record;
+mod regression;
+mod report;
+mod sampler;
+mod sharing;
+mod stats;
+mod store;
+mod system;
+mod thermal;
+mod tlb;
+mod verify;
+mod walk;
+mod writeback;
+
 #[derive(Parser)]
 struct Args {
     #[command(subcommand)]
@@ -30,173 +95,122 @@ struct Args {
 #[derive(Subcommand)]
 enum Command {
     /// Run the cache locality test
-    Test(TestArgs),
+    Test(Box<TestArgs>),
 
-    /// Plot results from a generated csv file
-    Plot(PlotArgs),
-}
+    /// Measure load latency by chasing pointers through a random cycle, sweeping the working set
+    /// size
+    Chase(ChaseArgs),
 
-#[derive(Parser)]
-struct TestArgs {
-    /// Amount of memory to allocate for test
-    #[clap(short, long, default_value_t = 1024 * 1024 * 1024)]
-    total_size: usize,
-
-    /// Initial step size
-    #[clap(short = 'd', long, default_value_t = 1)]
-    initial_step_size: usize,
-
-    /// Maximum step size. Must be >= --initial-step-size & <= --total-size [default same as --total-size]
-    #[clap(short, long)]
-    max_step_size: Option<usize>,
-
-    /// Number of iterations to run per step
-    #[clap(short, long, default_value_t = 1_000_000_000)]
-    iterations: usize,
-
-    /// Output file to record results to
-    #[clap(short, long)]
-    out: Option<PathBuf>,
-}
+    /// Compare row-major, column-major and Morton order summation of a square matrix, sweeping its
+    /// dimension
+    Matrix(MatrixArgs),
 
-#[derive(Parser)]
-struct PlotArgs {
-    /// File containing test data to plot
-    data_file: PathBuf,
+    /// Measure the false sharing penalty of threads incrementing counters packed into the same
+    /// cache line versus padded onto separate lines, sweeping the thread count
+    FalseSharing(SharingArgs),
 
-    /// Output image to save plot to [default same as input file with .png extension]
-    #[clap(short, long)]
-    out_img: Option<PathBuf>,
-}
+    /// Measure the round trip latency of bouncing a cache line between every pair of cores
+    PingPong(PingPongArgs),
 
-fn run_test(args: TestArgs) -> Result<(), Box<dyn Error>> {
-    println!("Allocating random data");
-    let mem: Vec<u8> = (0..args.total_size)
-        .into_par_iter()
-        .map(|_| rand::random())
-        .collect();
-
-    let mut out = args
-        .out
-        .as_ref()
-        .map(|out| csv::Writer::from_path(out))
-        .transpose()?;
-
-    let max_step_size = args
-        .max_step_size
-        .unwrap_or(args.total_size)
-        .clamp(args.initial_step_size, args.total_size);
-    let mut step_size = args.initial_step_size;
-    let mut rng = thread_rng();
-    while step_size <= max_step_size {
-        println!("Testing step size {step_size}");
-        let mut sum: u8 = 0;
-        let mut position: usize = 0;
-        let start_instant = Instant::now();
-        let start_time = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
-        for (steps, should_print) in Observer::new_starting_at(Duration::from_millis(100), 100_000)
-            .take(args.iterations)
-            .enumerate()
-        {
-            let step: usize = rng.gen();
-            let step = step % step_size;
-            if rng.gen() {
-                position = position.wrapping_add(step);
-            } else {
-                position = position.wrapping_sub(step);
-            }
-            position %= args.total_size;
-            sum = sum.wrapping_add(mem[position]);
-            if should_print {
-                let now = Instant::now();
-                let duration = now.duration_since(start_instant).as_secs_f32();
-                let steps_per_second = (steps as f32) / duration;
-                print!("\r{steps_per_second:.2} steps/sec");
-                stdout().flush().unwrap();
-            }
-        }
-        let total_duration = Instant::now().duration_since(start_instant);
-        let total_duration_float = total_duration.as_secs_f32();
-        let steps_per_second = (args.iterations as f32) / total_duration_float;
-        println!(
-            "\rCompleted testing: took {total_duration_float:.3} secs, with an average access rate of {steps_per_second:.2} steps/sec. sum: {sum}"
-        );
-        if let Some(out) = &mut out {
-            let step_size = step_size as u64;
-            let total_duration_millis = total_duration.as_millis();
-            out.serialize(Record {
-                start_time,
-                step_size,
-                total_duration_millis,
-                steps_per_second,
-            })?;
-            out.flush()?;
-        }
-        step_size <<= 1;
-    }
-    println!("Finished running tests");
-    if let Some(out) = &args.out {
-        println!("Saved results to {}", out.to_string_lossy());
-    }
+    /// Measure cache to cache transfer throughput by handing a region written on one core to a
+    /// reader on another, sweeping the region size
+    Handoff(HandoffArgs),
 
-    Ok(())
-}
+    /// Stress the TLB by touching one byte per page, sweeping the number of distinct pages
+    Tlb(TlbArgs),
+
+    /// Compare regular and non-temporal stores filling buffers of increasing size
+    Store(StoreArgs),
+
+    /// Measure sustained memory bandwidth with STREAM's copy, scale, add and triad kernels,
+    /// sweeping the array size
+    Bandwidth(BandwidthArgs),
+
+    /// Issue software prefetches a number of accesses ahead of random demand loads, sweeping the
+    /// prefetch distance
+    Prefetch(PrefetchArgs),
+
+    /// Measure instruction fetch throughput by running through generated code of increasing size
+    Icache(IcacheArgs),
+
+    /// Measure contention between threads performing atomic increments on a single shared
+    /// atomic, their own padded atomics or a region of atomics, sweeping the thread count
+    Contention(ContentionArgs),
+
+    /// Measure memcpy throughput between two buffers, sweeping the copy size
+    Copy(CopyArgs),
+
+    /// Expose cache set conflicts by chasing through lines separated by a power of two stride,
+    /// counting up the number of lines
+    Alias(AliasArgs),
+
+    /// Measure the cost of writebacks by streaming through memory after leaving a working set
+    /// of increasing size clean or dirty
+    Writeback(WritebackArgs),
+
+    /// Measure write combining and store buffer capacity by storing to an increasing number of
+    /// concurrent streams, each in its own region of a buffer
+    Combining(CombiningArgs),
+
+    /// Measure the cost of heap fragmentation by randomly walking a working set split into many
+    /// separately allocated chunks, against a contiguous baseline
+    Fragment(FragmentArgs),
+
+    /// Compare random reads from a working set on the stack, on the heap and in a static buffer,
+    /// sweeping its size
+    Placement(PlacementArgs),
+
+    /// Plot results from a generated csv file
+    Plot(PlotArgs),
+
+    /// Find the knees in each series of a result file's latency curve, where it turns upward as
+    /// the working set outgrows a level of the cache
+    Analyze(AnalyzeArgs),
+
+    /// Compare two result files step by step, testing whether each difference is significant or
+    /// just noise
+    Compare(CompareArgs),
+
+    /// Export a result file as Criterion benchmarks, one per step of each series, for critcmp and
+    /// other tools that read Criterion's output
+    Criterion(CriterionArgs),
 
-fn plot_data(args: PlotArgs) -> Result<(), Box<dyn Error>> {
-    let out_img = args
-        .out_img
-        .unwrap_or_else(|| args.data_file.with_extension("png"));
-
-    let data: Vec<Record> = Reader::from_path(args.data_file)?
-        .deserialize()
-        .try_collect()?;
-
-    let min_x = data
-        .iter()
-        .map(|record| record.step_size)
-        .min()
-        .ok_or("No data")?;
-    let max_x = data
-        .iter()
-        .map(|record| record.step_size)
-        .max()
-        .ok_or("No data")?;
-    let max_y = data
-        .iter()
-        .map(|record| record.steps_per_second)
-        .max_by(|a, b| a.total_cmp(b))
-        .ok_or("No data")?;
-
-    let root = BitMapBackend::new(&out_img, (1024, 1024)).into_drawing_area();
-    root.fill(&WHITE)?;
-    let mut plot = ChartBuilder::on(&root)
-        .margin(5)
-        .x_label_area_size(30)
-        .y_label_area_size(100)
-        .build_cartesian_2d((min_x..max_x).log_scale(), 0.0..max_y)?;
-
-    plot.configure_mesh().draw()?;
-
-    plot.draw_series(LineSeries::new(
-        data.iter()
-            .map(|record| (record.step_size, record.steps_per_second)),
-        RED,
-    ))?;
-
-    root.present()?;
-
-    println!("Saved plot to {}", out_img.to_string_lossy());
-
-    Ok(())
+    /// Summarise result files as a Markdown report of the machine, the parameters, the knees and
+    /// the latency of each series, with a plot of each file embedded
+    Report(ReportArgs),
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    match args.subcommand {
-        Command::Test(args) => run_test(args),
+    let result = match args.subcommand {
+        Command::Test(args) => run_test(*args),
+        Command::Chase(args) => run_chase(args),
+        Command::Matrix(args) => run_matrix(args),
+        Command::FalseSharing(args) => run_sharing(args),
+        Command::PingPong(args) => run_ping_pong(args),
+        Command::Handoff(args) => run_handoff(args),
+        Command::Tlb(args) => run_tlb(args),
+        Command::Store(args) => run_store(args),
+        Command::Bandwidth(args) => run_bandwidth(args),
+        Command::Prefetch(args) => run_prefetch(args),
+        Command::Icache(args) => run_icache(args),
+        Command::Contention(args) => run_contention(args),
+        Command::Copy(args) => run_copy(args),
+        Command::Alias(args) => run_alias(args),
+        Command::Writeback(args) => run_writeback(args),
+        Command::Combining(args) => run_combining(args),
+        Command::Fragment(args) => run_fragment(args),
+        Command::Placement(args) => run_placement(args),
         Command::Plot(args) => plot_data(args),
+        Command::Analyze(args) => run_analyze(args),
+        Command::Compare(args) => run_compare(args),
+        Command::Criterion(args) => run_criterion(args),
+        Command::Report(args) => run_report(args),
+    };
+    // Whatever reads the results piping them out has all it wants, so there's nothing left to
+    // do and nothing went wrong
+    match result {
+        Err(err) if is_broken_pipe(err.as_ref()) => Ok(()),
+        result => result,
     }
 }
@@ -0,0 +1,180 @@
+use crate::record::{unix_millis, Mode, Output, Record};
+use clap::{Parser, ValueEnum};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt, hint::black_box, path::PathBuf, time::Instant};
+
+/// Order in which the elements of the matrix are summed
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Traversal {
+    /// Sum each row in turn, visiting elements in the order they are laid out in memory
+    RowMajor,
+
+    /// Sum each column in turn, jumping a whole row ahead in memory on every access
+    ColumnMajor,
+
+    /// Visit elements along a Morton (Z-order) curve, recursively covering each quadrant of the
+    /// matrix before moving on to the next
+    Morton,
+}
+
+impl fmt::Display for Traversal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+#[derive(Parser)]
+pub struct MatrixArgs {
+    /// Initial matrix dimension
+    #[clap(short = 'd', long, default_value_t = 16)]
+    min_dimension: usize,
+
+    /// Maximum matrix dimension. The matrix holds 8 byte elements, so the default allocates 512MiB
+    #[clap(short, long, default_value_t = 8192)]
+    max_dimension: usize,
+
+    /// Minimum number of elements to sum per traversal and dimension. Small matrices are
+    /// traversed repeatedly until at least this many elements have been visited
+    #[clap(short, long, default_value_t = 1_000_000_000)]
+    iterations: usize,
+
+    /// Traversal orders to test at each dimension
+    #[clap(
+        short,
+        long,
+        value_delimiter = ',',
+        default_value = "row-major,column-major,morton"
+    )]
+    traversal: Vec<Traversal>,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_matrix(args: MatrixArgs) -> Result<(), Box<dyn Error>> {
+    if args.min_dimension == 0 || args.max_dimension < args.min_dimension {
+        return Err("--min-dimension must be at least 1 and no larger than --max-dimension".into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    println!("Allocating random matrix");
+    let matrix: Vec<u64> = (0..args.max_dimension * args.max_dimension)
+        .into_par_iter()
+        .map(|_| rand::random())
+        .collect();
+
+    let mut dimension = args.min_dimension;
+    while dimension <= args.max_dimension {
+        let elements = dimension * dimension;
+        let passes = args.iterations.div_ceil(elements);
+        for &traversal in &args.traversal {
+            println!("Testing {dimension}x{dimension} matrix with {traversal} traversal");
+            let start_time = unix_millis();
+            let start_instant = Instant::now();
+            let mut sum: u64 = 0;
+            for _ in 0..passes {
+                // Hide the matrix from the optimizer so repeated passes aren't folded into one
+                let matrix = black_box(&matrix[..elements]);
+                sum = sum.wrapping_add(traverse(matrix, dimension, traversal));
+            }
+            let total_duration = Instant::now().duration_since(start_instant);
+            let total_duration_float = total_duration.as_secs_f32();
+            let steps_per_second = ((passes * elements) as f32) / total_duration_float;
+            println!(
+                "Completed testing: took {total_duration_float:.3} secs, with an average access rate of {steps_per_second:.2} steps/sec. sum: {sum}"
+            );
+            out.write(&Record {
+                start_time,
+                step_size: dimension as u64,
+                total_duration_millis: total_duration.as_millis(),
+                steps_per_second,
+                mode: Mode::Matrix,
+                traversal: Some(traversal),
+                ..Record::default()
+            })?;
+        }
+        dimension <<= 1;
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Sums every element of the square `matrix` in the given order, wrapping on overflow
+fn traverse(matrix: &[u64], dimension: usize, traversal: Traversal) -> u64 {
+    let mut sum: u64 = 0;
+    match traversal {
+        Traversal::RowMajor => {
+            for row in 0..dimension {
+                for column in 0..dimension {
+                    sum = sum.wrapping_add(matrix[row * dimension + column]);
+                }
+            }
+        }
+        Traversal::ColumnMajor => {
+            for column in 0..dimension {
+                for row in 0..dimension {
+                    sum = sum.wrapping_add(matrix[row * dimension + column]);
+                }
+            }
+        }
+        Traversal::Morton => {
+            // Tiles are visited along the curve covering the smallest power of two square of
+            // tiles containing the matrix, skipping those falling outside it
+            let tiles = dimension.div_ceil(MORTON_TILE);
+            let side = tiles.next_power_of_two();
+            for tile in 0..side * side {
+                let tile_row = compact_bits(tile >> 1) * MORTON_TILE;
+                let tile_column = compact_bits(tile) * MORTON_TILE;
+                if tile_row >= dimension || tile_column >= dimension {
+                    continue;
+                }
+                let full =
+                    tile_row + MORTON_TILE <= dimension && tile_column + MORTON_TILE <= dimension;
+                for &(row, column) in &MORTON_TILE_ORDER {
+                    let (row, column) = (tile_row + row, tile_column + column);
+                    if full || (row < dimension && column < dimension) {
+                        sum = sum.wrapping_add(matrix[row * dimension + column]);
+                    }
+                }
+            }
+        }
+    }
+    sum
+}
+
+/// Side length of the square tiles the Morton traversal visits in a precomputed order, so that
+/// decoding the curve doesn't dominate the time taken
+const MORTON_TILE: usize = 8;
+
+/// Row and column of each element within a tile, in Morton order
+const MORTON_TILE_ORDER: [(usize, usize); MORTON_TILE * MORTON_TILE] = {
+    let mut order = [(0, 0); MORTON_TILE * MORTON_TILE];
+    let mut index = 0;
+    while index < order.len() {
+        order[index] = (compact_bits(index >> 1), compact_bits(index));
+        index += 1;
+    }
+    order
+};
+
+/// Gathers the even bits of `value` into the low half of the result, undoing the interleaving of
+/// a Morton index
+const fn compact_bits(value: usize) -> usize {
+    let mut value = value as u64 & 0x5555_5555_5555_5555;
+    value = (value | (value >> 1)) & 0x3333_3333_3333_3333;
+    value = (value | (value >> 2)) & 0x0f0f_0f0f_0f0f_0f0f;
+    value = (value | (value >> 4)) & 0x00ff_00ff_00ff_00ff;
+    value = (value | (value >> 8)) & 0x0000_ffff_0000_ffff;
+    value = (value | (value >> 16)) & 0x0000_0000_ffff_ffff;
+    value as usize
+}
@@ -0,0 +1,292 @@
+use clap::ValueEnum;
+use hdrhistogram::Histogram;
+use progress_observer::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::{Cell, RefCell},
+    error::Error,
+    fmt,
+    io::{stdout, Write},
+    time::{Duration, Instant},
+};
+
+/// Clock used to time batches of steps, alongside `Instant` for the total time
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Timer {
+    /// The operating system's monotonic clock
+    #[default]
+    Instant,
+
+    /// The cpu's cycle counter, read with rdtscp on x86_64 or cntvct_el0 on aarch64, serialized
+    /// so that accesses can't be reordered across the reads. Much cheaper to read than the
+    /// monotonic clock; counts at a constant reference rate on modern cpus, whatever the core's
+    /// actual frequency, so cycles are reference cycles
+    Tsc,
+}
+
+impl Timer {
+    /// Fails if the cycle counter isn't supported on this architecture
+    pub fn check(self) -> Result<(), Box<dyn Error>> {
+        if self == Timer::Tsc && cfg!(not(any(target_arch = "x86_64", target_arch = "aarch64"))) {
+            return Err("the tsc timer is only supported on x86_64 and aarch64".into());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+/// Reads the cycle counter, after every earlier instruction has completed and before any later one
+/// starts
+#[cfg(target_arch = "x86_64")]
+fn cycles() -> u64 {
+    use std::arch::x86_64::{__rdtscp, _mm_lfence};
+
+    let mut aux = 0;
+    // SAFETY: rdtscp and lfence have no preconditions, and are supported by every x86_64 cpu
+    // recent enough to run this
+    unsafe {
+        let cycles = __rdtscp(&mut aux);
+        _mm_lfence();
+        cycles
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn cycles() -> u64 {
+    use std::arch::asm;
+
+    let cycles: u64;
+    // SAFETY: reading the virtual counter has no side effects, and the isbs keep it from being
+    // reordered with the surrounding instructions
+    unsafe { asm!("isb", "mrs {}, cntvct_el0", "isb", out(reg) cycles, options(nostack)) };
+    cycles
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn cycles() -> u64 {
+    unreachable!("the tsc timer is rejected up front on other architectures")
+}
+
+/// Throughput over one of the intervals `measure` prints the rate of steps after
+#[derive(Clone, Copy)]
+pub struct Sample {
+    /// Time measured since the recording started when the interval ended
+    pub time: Duration,
+    pub steps: usize,
+    pub duration: Duration,
+}
+
+impl Sample {
+    pub fn steps_per_second(&self) -> f64 {
+        self.steps as f64 / self.duration.as_secs_f64()
+    }
+}
+
+/// State `measure` records into while a recording is running on the current thread
+struct Recording {
+    timer: Timer,
+    /// Batch size and histogram of the batches' latency, in picoseconds per step with the
+    /// monotonic clock or thousandths of a cycle per step with the cycle counter
+    histogram: Option<(usize, Histogram<u64>)>,
+    /// Total cycles and time measured, when timing with the cycle counter
+    cycles: u64,
+    duration: Duration,
+    /// Throughput of each interval, when recording them
+    samples: Option<Vec<Sample>>,
+    /// Time measured so far, which samples are timed from
+    elapsed: Duration,
+}
+
+thread_local! {
+    static RECORDING: RefCell<Option<Recording>> = const { RefCell::new(None) };
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Stops `measure` printing the rate of steps on this thread, for threads measured alongside
+/// another that prints it
+pub fn quiet() {
+    QUIET.set(true);
+}
+
+/// What was recorded between `start_recording` and `finish_recording`, which can be merged with
+/// other recordings made the same way
+pub struct Recorded {
+    tsc: bool,
+    cycles: u64,
+    duration: Duration,
+    histogram: Option<Histogram<u64>>,
+    samples: Option<Vec<Sample>>,
+    elapsed: Duration,
+}
+
+impl Recorded {
+    /// Adds everything recorded in `other` to this recording, as if it was measured after it
+    pub fn merge(&mut self, other: Recorded) {
+        self.cycles += other.cycles;
+        self.duration += other.duration;
+        if let (Some(samples), Some(other_samples)) = (&mut self.samples, other.samples) {
+            samples.extend(other_samples.into_iter().map(|sample| Sample {
+                time: self.elapsed + sample.time,
+                ..sample
+            }));
+        }
+        self.elapsed += other.elapsed;
+        if let (Some(histogram), Some(other)) = (&mut self.histogram, &other.histogram) {
+            histogram
+                .add(other)
+                .expect("histograms recorded the same way have the same bounds");
+        }
+    }
+
+    /// Total cycles counted, when timing with the cycle counter
+    pub fn cycles(&self) -> Option<u64> {
+        self.tsc.then_some(self.cycles)
+    }
+
+    /// Throughput of each interval, when recording them
+    pub fn samples(&self) -> &[Sample] {
+        self.samples.as_deref().unwrap_or_default()
+    }
+
+    /// 50th, 90th, 99th and 99.9th percentiles of the batches' latency in nanoseconds per step,
+    /// when recording a histogram
+    pub fn percentile_ns(&self) -> Option<[f64; 4]> {
+        // Converts recorded values to nanoseconds
+        let scale = if self.tsc {
+            self.duration.as_nanos() as f64 / self.cycles.max(1) as f64 / 1000.0
+        } else {
+            1.0 / 1000.0
+        };
+        let histogram = self.histogram.as_ref()?;
+        Some(
+            [0.5, 0.9, 0.99, 0.999]
+                .map(|quantile| histogram.value_at_quantile(quantile) as f64 * scale),
+        )
+    }
+}
+
+/// Starts recording every call to `measure` on this thread: counting cycles with `timer`,
+/// recording the average latency of every `histogram_batch` steps into a histogram, and the
+/// throughput of every interval it prints the rate of steps after if `samples` is set
+pub fn start_recording(timer: Timer, histogram_batch: Option<usize>, samples: bool) {
+    let histogram = histogram_batch.map(|batch| {
+        // Tracks from a picosecond up to a second or more, so recording never has to resize it
+        let histogram =
+            Histogram::new_with_bounds(1, 1_000_000_000_000, 3).expect("the bounds are valid");
+        (batch, histogram)
+    });
+    let recording = Recording {
+        timer,
+        histogram,
+        cycles: 0,
+        duration: Duration::ZERO,
+        samples: samples.then(Vec::new),
+        elapsed: Duration::ZERO,
+    };
+    RECORDING.with_borrow_mut(|slot| *slot = Some(recording));
+}
+
+/// Stops recording, returning what was recorded since `start_recording`
+pub fn finish_recording() -> Recorded {
+    let Some(recording) = RECORDING.with_borrow_mut(Option::take) else {
+        return Recorded {
+            tsc: false,
+            cycles: 0,
+            duration: Duration::ZERO,
+            histogram: None,
+            samples: None,
+            elapsed: Duration::ZERO,
+        };
+    };
+    Recorded {
+        tsc: recording.timer == Timer::Tsc,
+        cycles: recording.cycles,
+        duration: recording.duration,
+        histogram: recording.histogram.map(|(_, histogram)| histogram),
+        samples: recording.samples,
+        elapsed: recording.elapsed,
+    }
+}
+
+/// Calls `step` `iterations` times, printing the rate of steps periodically, and returns the
+/// total time taken.
+pub fn measure(iterations: usize, mut step: impl FnMut()) -> Duration {
+    let mut recording = RECORDING.with_borrow_mut(Option::take);
+    let tsc = recording
+        .as_ref()
+        .is_some_and(|recording| recording.timer == Timer::Tsc);
+    let mut batch_remaining = recording
+        .as_ref()
+        .and_then(|recording| recording.histogram.as_ref())
+        .map_or(0, |&(batch, _)| batch);
+    let quiet = QUIET.get();
+    let start_cycles = if tsc { cycles() } else { 0 };
+    let start_instant = Instant::now();
+    let (mut batch_start_cycles, mut batch_start_instant) = (start_cycles, start_instant);
+    let (mut sample_start_steps, mut sample_start_instant) = (0, start_instant);
+    for (steps, should_print) in Observer::new_starting_at(Duration::from_millis(100), 100_000)
+        .take(iterations)
+        .enumerate()
+    {
+        step();
+        if let Some((batch, histogram)) = recording
+            .as_mut()
+            .and_then(|recording| recording.histogram.as_mut())
+        {
+            batch_remaining -= 1;
+            if batch_remaining == 0 {
+                let thousandths = if tsc {
+                    let now = cycles();
+                    let elapsed = now - batch_start_cycles;
+                    batch_start_cycles = now;
+                    elapsed as u128 * 1000
+                } else {
+                    let now = Instant::now();
+                    let elapsed = now.duration_since(batch_start_instant);
+                    batch_start_instant = now;
+                    elapsed.as_nanos() * 1000
+                };
+                histogram.saturating_record((thousandths / *batch as u128) as u64);
+                batch_remaining = *batch;
+            }
+        }
+        if !should_print {
+            continue;
+        }
+        let now = Instant::now();
+        if let Some(recording) = &mut recording {
+            if let Some(samples) = &mut recording.samples {
+                // `steps` counts the steps before this one
+                let done = steps + 1;
+                samples.push(Sample {
+                    time: recording.elapsed + now.duration_since(start_instant),
+                    steps: done - sample_start_steps,
+                    duration: now.duration_since(sample_start_instant),
+                });
+                (sample_start_steps, sample_start_instant) = (done, now);
+            }
+        }
+        if !quiet {
+            let duration = now.duration_since(start_instant).as_secs_f32();
+            let steps_per_second = (steps as f32) / duration;
+            print!("\r{steps_per_second:.2} steps/sec");
+            let _ = stdout().flush();
+        }
+    }
+    let total_duration = Instant::now().duration_since(start_instant);
+    if let Some(mut recording) = recording {
+        if tsc {
+            recording.cycles += cycles() - start_cycles;
+            recording.duration += total_duration;
+        }
+        recording.elapsed += total_duration;
+        RECORDING.with_borrow_mut(|slot| *slot = Some(recording));
+    }
+    total_duration
+}
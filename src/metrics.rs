@@ -0,0 +1,325 @@
+use crate::{record::Record, system::System};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::Write as _,
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// How long to wait on a scraper or Pushgateway before giving up on it
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Progress and latest results of a sweep, as Prometheus metrics served to scrapers and pushed to
+/// a Pushgateway, so that sweeps on many machines can be watched from the dashboards already
+/// watching them
+pub struct Metrics {
+    state: Arc<Mutex<State>>,
+    gateway: Option<Gateway>,
+}
+
+#[derive(Default)]
+struct State {
+    total_steps: usize,
+    completed_steps: usize,
+    /// Step size of the step running, if one is
+    step_size: Option<u64>,
+    total_size: Option<u64>,
+    /// The last step finished of each series, by its label
+    series: BTreeMap<String, Latest>,
+}
+
+/// What the last finished step of a series measured
+struct Latest {
+    step_size: u64,
+    steps_per_second: f32,
+    gigabytes_per_second: Option<f32>,
+    avg_latency_ns: Option<f64>,
+    finished_time: u128,
+}
+
+/// Where `--push-gateway` pushes metrics to
+struct Gateway {
+    /// Host and port to connect to
+    address: String,
+    /// Path of the group the metrics are pushed into, named by the job and this machine
+    path: String,
+}
+
+impl Metrics {
+    /// Starts serving metrics on `listen`, if given, and pushing them to the Pushgateway at the
+    /// http url `push_gateway`, if given
+    pub fn start(
+        listen: Option<SocketAddr>,
+        push_gateway: Option<&str>,
+        total_steps: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        let state = Arc::new(Mutex::new(State {
+            total_steps,
+            ..State::default()
+        }));
+        if let Some(listen) = listen {
+            let listener = TcpListener::bind(listen)
+                .map_err(|err| format!("failed to listen for metrics on {listen} ({err})"))?;
+            println!("Serving metrics on http://{listen}/metrics");
+            let state = state.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    if let Err(err) = serve(stream, &state) {
+                        println!("Warning: failed to serve metrics ({err})");
+                    }
+                }
+            });
+        }
+        let gateway = push_gateway.map(Gateway::parse).transpose()?;
+        Ok(Metrics { state, gateway })
+    }
+
+    /// Notes that `step_size` has started being measured over a buffer of `total_size` bytes
+    pub fn start_step(&self, step_size: u64, total_size: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.step_size = Some(step_size);
+        state.total_size = Some(total_size);
+        drop(state);
+        self.push();
+    }
+
+    /// Notes that `count` steps were skipped as already done, by --resume
+    pub fn skip_steps(&self, count: usize) {
+        self.state.lock().unwrap().completed_steps += count;
+    }
+
+    /// Notes the result of a finished step
+    pub fn finish_step(&self, record: &Record) {
+        let mut state = self.state.lock().unwrap();
+        state.completed_steps += 1;
+        state.step_size = None;
+        let label = record
+            .series_parameters()
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let latest = Latest {
+            step_size: record.step_size,
+            steps_per_second: record.steps_per_second,
+            gigabytes_per_second: record.gigabytes_per_second,
+            avg_latency_ns: record.avg_latency_ns,
+            finished_time: record.start_time + record.total_duration_millis,
+        };
+        state.series.insert(label, latest);
+        drop(state);
+        self.push();
+    }
+
+    /// Pushes the metrics to the Pushgateway, if there is one. Failing to only warns, rather than
+    /// ending a sweep over a dashboard
+    fn push(&self) {
+        let Some(gateway) = &self.gateway else {
+            return;
+        };
+        let body = self.state.lock().unwrap().exposition();
+        if let Err(err) = gateway.push(&body) {
+            println!(
+                "Warning: failed to push metrics to {} ({err})",
+                gateway.address
+            );
+        }
+    }
+}
+
+impl State {
+    /// The metrics in Prometheus' text exposition format
+    fn exposition(&self) -> String {
+        let mut text = String::new();
+        let mut metric = |name: &str, help: &str, samples: Vec<(String, f64)>| {
+            if samples.is_empty() {
+                return;
+            }
+            let name = format!("cache_locality_{name}");
+            // Writing to a string can't fail
+            let _ = writeln!(text, "# HELP {name} {help}\n# TYPE {name} gauge");
+            for (labels, value) in samples {
+                let _ = writeln!(text, "{name}{labels} {value}");
+            }
+        };
+        let unlabelled = |value: Option<f64>| {
+            value
+                .map(|value| (String::new(), value))
+                .into_iter()
+                .collect()
+        };
+        metric(
+            "steps",
+            "Steps in the sweep",
+            unlabelled(Some(self.total_steps as f64)),
+        );
+        metric(
+            "steps_completed",
+            "Steps of the sweep finished, including those skipped by --resume",
+            unlabelled(Some(self.completed_steps as f64)),
+        );
+        metric(
+            "progress_ratio",
+            "Fraction of the sweep's steps finished",
+            unlabelled(
+                (self.total_steps > 0)
+                    .then(|| self.completed_steps as f64 / self.total_steps as f64),
+            ),
+        );
+        metric(
+            "step_size_bytes",
+            "Step size of the step being measured",
+            unlabelled(self.step_size.map(|size| size as f64)),
+        );
+        metric(
+            "total_size_bytes",
+            "Size of the buffer being walked",
+            unlabelled(self.total_size.map(|size| size as f64)),
+        );
+        let per_series = |value: fn(&Latest) -> Option<f64>| {
+            self.series
+                .iter()
+                .filter_map(|(label, latest)| {
+                    Some((format!("{{series=\"{}\"}}", escape(label)), value(latest)?))
+                })
+                .collect()
+        };
+        metric(
+            "last_step_size_bytes",
+            "Step size of the last step finished of each series",
+            per_series(|latest| Some(latest.step_size as f64)),
+        );
+        metric(
+            "steps_per_second",
+            "Steps per second of the last step finished of each series",
+            per_series(|latest| Some(latest.steps_per_second as f64)),
+        );
+        metric(
+            "gigabytes_per_second",
+            "Throughput in GB/s of the last step finished of each series",
+            per_series(|latest| latest.gigabytes_per_second.map(f64::from)),
+        );
+        metric(
+            "latency_nanoseconds",
+            "Average latency per access of the last step finished of each series",
+            per_series(|latest| latest.avg_latency_ns),
+        );
+        metric(
+            "last_step_timestamp_seconds",
+            "When the last step of each series finished, in seconds since the unix epoch",
+            per_series(|latest| Some(latest.finished_time as f64 / 1000.0)),
+        );
+        text
+    }
+}
+
+/// Answers a scrape, or any other request, with the metrics
+fn serve(mut stream: TcpStream, state: &Mutex<State>) -> Result<(), Box<dyn Error>> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    // Only the request line matters, but the headers are read too so the client isn't cut off
+    // while still sending
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < 64 * 1024 {
+        let read = stream.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let response = if path == "/metrics" {
+        let body = state.lock().unwrap().exposition();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+    stream.write_all(response.as_bytes())?;
+    Ok(())
+}
+
+impl Gateway {
+    /// The Pushgateway at an http url such as `http://gateway:9091`
+    fn parse(url: &str) -> Result<Gateway, Box<dyn Error>> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or("--push-gateway must be an http:// url")?;
+        let (address, base) = rest.split_once('/').unwrap_or((rest, ""));
+        if address.is_empty() {
+            return Err("--push-gateway must name a host".into());
+        }
+        let address = if address.contains(':') {
+            address.to_string()
+        } else {
+            format!("{address}:80")
+        };
+        let instance = System::detect()
+            .hostname
+            .unwrap_or_else(|| "unknown".to_string());
+        let base = base.trim_end_matches('/');
+        let path = format!(
+            "{}/metrics/job/cache_locality/instance/{}",
+            if base.is_empty() {
+                String::new()
+            } else {
+                format!("/{base}")
+            },
+            percent_encode(&instance)
+        );
+        Ok(Gateway { address, path })
+    }
+
+    /// Replaces the metrics of this machine's group with `body`
+    fn push(&self, body: &str) -> Result<(), Box<dyn Error>> {
+        let address = self
+            .address
+            .to_socket_addrs()?
+            .next()
+            .ok_or("the host has no address")?;
+        let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        write!(
+            stream,
+            "PUT {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            self.path,
+            self.address,
+            body.len()
+        )?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let status = response.lines().next().unwrap_or_default();
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(format!("the gateway answered \"{status}\"").into()),
+        }
+    }
+}
+
+/// A label value escaped to sit in quotes
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Text escaped to sit in a url path
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
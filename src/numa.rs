@@ -0,0 +1,90 @@
+use std::{error::Error, fs};
+
+/// Root of the sysfs tree describing the NUMA topology
+const NODE_ROOT: &str = "/sys/devices/system/node";
+
+/// Cores belonging to the given NUMA node
+pub fn node_cores(node: usize) -> Result<Vec<usize>, Box<dyn Error>> {
+    let list = fs::read_to_string(format!("{NODE_ROOT}/node{node}/cpulist"))
+        .map_err(|e| format!("failed to read the cores of NUMA node {node} ({e})"))?;
+    parse_list(&list)
+}
+
+/// Parses a kernel cpu or node list such as `0-3,8,10-11`
+fn parse_list(list: &str) -> Result<Vec<usize>, Box<dyn Error>> {
+    let mut items = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let (start, end): (usize, usize) = (start.parse()?, end.parse()?);
+        items.extend(start..=end);
+    }
+    Ok(items)
+}
+
+/// Nodes that have memory of their own
+pub fn memory_nodes() -> Result<Vec<usize>, Box<dyn Error>> {
+    let list = fs::read_to_string(format!("{NODE_ROOT}/has_memory"))
+        .map_err(|e| format!("failed to read the NUMA nodes with memory ({e})"))?;
+    parse_list(&list)
+}
+
+/// Binds the pages of a not yet touched mapping to the given NUMA node, so they are allocated
+/// there when first touched
+#[cfg(target_os = "linux")]
+pub fn bind_memory(ptr: *mut u8, len: usize, node: usize) -> Result<(), Box<dyn Error>> {
+    set_policy(ptr, len, libc::MPOL_BIND, &[node])
+        .map_err(|e| format!("failed to bind memory to NUMA node {node} ({e})").into())
+}
+
+/// Spreads the pages of a not yet touched mapping round robin across every node with memory, as
+/// they're first touched
+#[cfg(target_os = "linux")]
+pub fn interleave_memory(ptr: *mut u8, len: usize) -> Result<(), Box<dyn Error>> {
+    set_policy(ptr, len, libc::MPOL_INTERLEAVE, &memory_nodes()?)
+        .map_err(|e| format!("failed to interleave memory across NUMA nodes ({e})").into())
+}
+
+/// Sets the memory policy of a range with mbind
+#[cfg(target_os = "linux")]
+fn set_policy(
+    ptr: *mut u8,
+    len: usize,
+    mode: libc::c_int,
+    nodes: &[usize],
+) -> Result<(), Box<dyn Error>> {
+    const MASK_BITS: usize = u64::BITS as usize;
+    let mut mask = [0u64; 16];
+    for &node in nodes {
+        if node >= mask.len() * MASK_BITS {
+            return Err(format!("NUMA node {node} is out of range").into());
+        }
+        mask[node / MASK_BITS] |= 1 << (node % MASK_BITS);
+    }
+    // SAFETY: mbind only changes the policy of the given range, which the caller owns, and reads
+    // `mask.len() * MASK_BITS` bits of the node mask
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            ptr,
+            len,
+            mode,
+            mask.as_ptr(),
+            mask.len() * MASK_BITS,
+            0,
+        )
+    };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn bind_memory(_ptr: *mut u8, _len: usize, _node: usize) -> Result<(), Box<dyn Error>> {
+    Err("binding memory to NUMA nodes is only supported on linux".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn interleave_memory(_ptr: *mut u8, _len: usize) -> Result<(), Box<dyn Error>> {
+    Err("interleaving memory across NUMA nodes is only supported on linux".into())
+}
@@ -0,0 +1,93 @@
+use std::fs;
+
+/// Page faults taken by this process, and the system's paging activity, since boot or over some
+/// span of time
+#[derive(Clone, Copy)]
+pub struct Paging {
+    /// Faults served without any io, such as the first touch of a freshly mapped page
+    pub minor_faults: u64,
+    /// Faults that had to read the page back in from disk, usually from swap
+    pub major_faults: u64,
+    /// Pages swapped in or out by the whole system, on linux
+    pub swapped_pages: Option<u64>,
+    /// Microseconds in which some task on the system was stalled waiting for memory, from
+    /// linux's pressure stall information
+    pub memory_stall_us: Option<u64>,
+}
+
+impl Paging {
+    /// Current totals
+    pub fn now() -> Self {
+        let (minor_faults, major_faults) = faults();
+        let vmstat = fs::read_to_string("/proc/vmstat").ok();
+        let swapped_pages = vmstat.and_then(|vmstat| {
+            let field = |name: &str| {
+                vmstat.lines().find_map(|line| {
+                    let (key, value) = line.split_once(' ')?;
+                    (key == name).then(|| value.trim().parse::<u64>().ok())?
+                })
+            };
+            Some(field("pswpin")? + field("pswpout")?)
+        });
+        let memory_stall_us =
+            fs::read_to_string("/proc/pressure/memory")
+                .ok()
+                .and_then(|pressure| {
+                    let some = pressure.lines().find(|line| line.starts_with("some "))?;
+                    some.split_whitespace()
+                        .find_map(|field| field.strip_prefix("total="))?
+                        .parse()
+                        .ok()
+                });
+        Paging {
+            minor_faults,
+            major_faults,
+            swapped_pages,
+            memory_stall_us,
+        }
+    }
+
+    /// Activity since `self` was taken
+    pub fn since(&self) -> Self {
+        let now = Paging::now();
+        let delta = |start: Option<u64>, end: Option<u64>| Some(end?.saturating_sub(start?));
+        Paging {
+            minor_faults: now.minor_faults.saturating_sub(self.minor_faults),
+            major_faults: now.major_faults.saturating_sub(self.major_faults),
+            swapped_pages: delta(self.swapped_pages, now.swapped_pages),
+            memory_stall_us: delta(self.memory_stall_us, now.memory_stall_us),
+        }
+    }
+
+    /// Adds the activity of another span of time
+    pub fn merge(&mut self, other: Paging) {
+        let sum = |a: Option<u64>, b: Option<u64>| Some(a? + b?);
+        self.minor_faults += other.minor_faults;
+        self.major_faults += other.major_faults;
+        self.swapped_pages = sum(self.swapped_pages, other.swapped_pages);
+        self.memory_stall_us = sum(self.memory_stall_us, other.memory_stall_us);
+    }
+
+    /// Whether the process had to wait on the disk for its memory, which makes a measurement
+    /// worthless as a measurement of the cache
+    pub fn swapped(&self) -> bool {
+        self.major_faults > 0
+    }
+}
+
+/// Minor and major faults taken by every thread of this process so far
+#[cfg(unix)]
+fn faults() -> (u64, u64) {
+    // SAFETY: getrusage only writes to the struct it's given
+    let usage = unsafe {
+        let mut usage = std::mem::zeroed::<libc::rusage>();
+        libc::getrusage(libc::RUSAGE_SELF, &mut usage);
+        usage
+    };
+    (usage.ru_minflt as u64, usage.ru_majflt as u64)
+}
+
+#[cfg(not(unix))]
+fn faults() -> (u64, u64) {
+    (0, 0)
+}
@@ -0,0 +1,225 @@
+use std::{error::Error, fs::File};
+
+const HARDWARE: u32 = 0;
+const HW_CACHE: u32 = 3;
+
+/// Config selecting read misses of the given cache, as `cache | op << 8 | result << 16`
+const fn read_misses(cache: u64) -> u64 {
+    cache | 1 << 16
+}
+
+/// Hardware events counted by [`Counters`], as perf_event type and config pairs. perf has no
+/// generic event for L2 misses, so only the first and last levels of the cache are counted. The
+/// generic data TLB event counts first level misses on amd, but only the misses that walk the page
+/// tables on intel
+const EVENTS: [(&str, u32, u64); 5] = [
+    ("cycles", HARDWARE, 0),
+    ("instructions", HARDWARE, 1),
+    ("L1 data cache misses", HW_CACHE, read_misses(0)),
+    ("last level cache misses", HW_CACHE, read_misses(2)),
+    ("data TLB misses", HW_CACHE, read_misses(3)),
+];
+
+/// Event counting loads that miss the second level TLB as well as the first, and so walk the page
+/// tables, as a perf_event type and config pair. perf has no generic event for it, so it's only
+/// known for some cpus: intel's generic data TLB event already counts exactly this, and on amd's
+/// zen cores it's the L2 miss half of ls_l1_d_tlb_miss
+#[cfg(target_os = "linux")]
+fn stlb_event() -> Option<(u32, u64)> {
+    const RAW: u32 = 4;
+
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    let field = |name: &str| {
+        cpuinfo.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key.trim() == name).then(|| value.trim().to_string())
+        })
+    };
+    match field("vendor_id")?.as_str() {
+        "GenuineIntel" => Some((HW_CACHE, read_misses(3))),
+        "AuthenticAMD" | "HygonGenuine" if field("cpu family")?.parse::<u32>().ok()? >= 0x17 => {
+            Some((RAW, 0xf0 << 8 | 0x45))
+        }
+        _ => None,
+    }
+}
+
+/// Totals counted between resetting a set of [`Counters`] and reading them
+#[derive(Clone, Copy)]
+pub struct Counts {
+    pub cycles: u64,
+    pub instructions: u64,
+    pub l1d_misses: u64,
+    pub llc_misses: u64,
+    pub dtlb_misses: u64,
+    /// Only counted on cpus with a known second level TLB event
+    pub stlb_misses: Option<u64>,
+}
+
+impl Counts {
+    /// Adds the totals counted over another span of time
+    pub fn merge(&mut self, other: Counts) {
+        self.cycles += other.cycles;
+        self.instructions += other.instructions;
+        self.l1d_misses += other.l1d_misses;
+        self.llc_misses += other.llc_misses;
+        self.dtlb_misses += other.dtlb_misses;
+        self.stlb_misses = self.stlb_misses.zip(other.stlb_misses).map(|(a, b)| a + b);
+    }
+}
+
+/// Hardware performance counters for the current thread, counting only while enabled
+pub struct Counters {
+    files: Vec<File>,
+    stlb: Option<File>,
+}
+
+/// The start of perf_event_attr, up to the end of its first published version
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Default)]
+struct EventAttr {
+    kind: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+impl Counters {
+    /// Opens a disabled counter for each event on the current thread, counting user space only
+    #[cfg(target_os = "linux")]
+    pub fn open() -> Result<Self, Box<dyn Error>> {
+        use std::os::fd::FromRawFd;
+
+        const FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+        const FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+        const FLAG_DISABLED: u64 = 1 << 0;
+        const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+        const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+        let open = |name: &str, kind: u32, config: u64| -> Result<File, Box<dyn Error>> {
+            let attr = EventAttr {
+                kind,
+                size: std::mem::size_of::<EventAttr>() as u32,
+                config,
+                read_format: FORMAT_TOTAL_TIME_ENABLED | FORMAT_TOTAL_TIME_RUNNING,
+                flags: FLAG_DISABLED | FLAG_EXCLUDE_KERNEL | FLAG_EXCLUDE_HV,
+                ..EventAttr::default()
+            };
+            // SAFETY: the kernel only reads `attr.size` bytes of the attributes, which are valid
+            // for the duration of the call
+            let fd = unsafe {
+                libc::syscall(
+                    libc::SYS_perf_event_open,
+                    &attr as *const EventAttr,
+                    0,
+                    -1,
+                    -1,
+                    0,
+                )
+            };
+            if fd < 0 {
+                return Err(format!(
+                    "failed to open a performance counter for {name} ({}); check that the cpu exposes hardware counters and that /proc/sys/kernel/perf_event_paranoid allows them",
+                    std::io::Error::last_os_error()
+                )
+                .into());
+            }
+            // SAFETY: the kernel just returned this descriptor, and nothing else owns it
+            Ok(unsafe { File::from_raw_fd(fd as i32) })
+        };
+        let files: Vec<File> = EVENTS
+            .into_iter()
+            .map(|(name, kind, config)| open(name, kind, config))
+            .try_collect()?;
+        let stlb = match stlb_event() {
+            Some((kind, config)) => Some(open("second level TLB misses", kind, config)?),
+            None => {
+                println!("Not counting second level TLB misses, as this cpu's event for them isn't known");
+                None
+            }
+        };
+        Ok(Counters { files, stlb })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn open() -> Result<Self, Box<dyn Error>> {
+        Err("performance counters are only supported on linux".into())
+    }
+
+    /// Zeroes every counter
+    pub fn reset(&self) -> Result<(), Box<dyn Error>> {
+        self.control(0x2403)
+    }
+
+    /// Starts counting
+    pub fn enable(&self) -> Result<(), Box<dyn Error>> {
+        self.control(0x2400)
+    }
+
+    /// Stops counting
+    pub fn disable(&self) -> Result<(), Box<dyn Error>> {
+        self.control(0x2401)
+    }
+
+    /// Reads the totals counted since the last reset. When there are more events than hardware
+    /// counters the kernel takes turns counting them, so each total is scaled up from the time
+    /// its event was actually counted
+    pub fn read(&self) -> Result<Counts, Box<dyn Error>> {
+        use std::io::Read;
+
+        let read = |file: &File| -> Result<u64, Box<dyn Error>> {
+            let mut buf = [0u8; 3 * std::mem::size_of::<u64>()];
+            (&*file).read_exact(&mut buf)?;
+            let [value, enabled, running] =
+                [0, 1, 2].map(|i| u64::from_ne_bytes(buf[i * 8..][..8].try_into().unwrap()));
+            Ok(if running == 0 {
+                0
+            } else {
+                (value as u128 * enabled as u128 / running as u128) as u64
+            })
+        };
+        let mut totals = [0u64; EVENTS.len()];
+        for (file, total) in self.files.iter().zip(&mut totals) {
+            *total = read(file)?;
+        }
+        let [cycles, instructions, l1d_misses, llc_misses, dtlb_misses] = totals;
+        Ok(Counts {
+            cycles,
+            instructions,
+            l1d_misses,
+            llc_misses,
+            dtlb_misses,
+            stlb_misses: self.stlb.as_ref().map(read).transpose()?,
+        })
+    }
+
+    /// Applies a perf_event ioctl to every counter
+    #[cfg(target_os = "linux")]
+    fn control(&self, request: libc::c_ulong) -> Result<(), Box<dyn Error>> {
+        use std::os::fd::AsRawFd;
+
+        for file in self.files.iter().chain(&self.stlb) {
+            // SAFETY: the perf_event control requests take no argument
+            if unsafe { libc::ioctl(file.as_raw_fd(), request as _, 0) } != 0 {
+                return Err(format!(
+                    "failed to control a performance counter ({})",
+                    std::io::Error::last_os_error()
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn control(&self, _request: u64) -> Result<(), Box<dyn Error>> {
+        unreachable!("counters can't be opened on other platforms")
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{
+    affinity::{available_cores, pin_to_core},
+    record::{unix_millis, Mode, Output, Record},
+};
+use clap::Parser;
+use std::{
+    error::Error,
+    hint::spin_loop,
+    io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Barrier,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The shared counter, alone on its own cache line
+#[repr(align(64))]
+#[derive(Default)]
+struct Line(AtomicU64);
+
+#[derive(Parser)]
+pub struct PingPongArgs {
+    /// Cores to test between; every pair of them is measured [default all available cores]
+    #[clap(short, long, value_delimiter = ',')]
+    cores: Option<Vec<usize>>,
+
+    /// Number of round trips to time per pair of cores
+    #[clap(short, long, default_value_t = 100_000)]
+    iterations: usize,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_ping_pong(args: PingPongArgs) -> Result<(), Box<dyn Error>> {
+    let cores = match args.cores {
+        Some(cores) => cores,
+        None => available_cores()?,
+    };
+    if cores.len() < 2 {
+        return Err("at least two cores are needed to measure ping-pong latency".into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    for (i, &first_core) in cores.iter().enumerate() {
+        for &second_core in &cores[i + 1..] {
+            println!("Testing cores {first_core} and {second_core}");
+            let start_time = unix_millis();
+            let total_duration = ping_pong(first_core, second_core, args.iterations)?;
+            let total_duration_float = total_duration.as_secs_f32();
+            let steps_per_second = (args.iterations as f32) / total_duration_float;
+            let latency_ns = total_duration.as_nanos() as f64 / args.iterations as f64;
+            println!(
+                "Completed testing: took {total_duration_float:.3} secs, with an average round trip latency of {latency_ns:.2} ns"
+            );
+            out.write(&Record {
+                start_time,
+                total_duration_millis: total_duration.as_millis(),
+                steps_per_second,
+                avg_latency_ns: Some(latency_ns),
+                mode: Mode::PingPong,
+                first_core: Some(first_core),
+                second_core: Some(second_core),
+                ..Record::default()
+            })?;
+        }
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Bounces a cache line between two threads pinned to the given cores `iterations` times. The
+/// first thread writes an odd value and waits for the second to answer with the next even value,
+/// so each round trip moves the line to the other core and back. Returns the time taken as seen by
+/// the first thread.
+fn ping_pong(first_core: usize, second_core: usize, iterations: usize) -> io::Result<Duration> {
+    let line = Line::default();
+    let counter = &line.0;
+    // Both threads must be pinned before either starts, or one would spin forever waiting for a
+    // partner that gave up
+    let pinned = Barrier::new(2);
+    let failed = AtomicBool::new(false);
+    let pin = |core| {
+        let result = pin_to_core(core);
+        if result.is_err() {
+            failed.store(true, Ordering::Relaxed);
+        }
+        pinned.wait();
+        result.map(|()| !failed.load(Ordering::Relaxed))
+    };
+    thread::scope(|scope| {
+        let pong = scope.spawn(|| {
+            if pin(second_core)? {
+                for i in 0..iterations as u64 {
+                    while counter.load(Ordering::Acquire) != 2 * i + 1 {
+                        spin_loop();
+                    }
+                    counter.store(2 * i + 2, Ordering::Release);
+                }
+            }
+            Ok(())
+        });
+        let ping = scope.spawn(|| {
+            if !pin(first_core)? {
+                return Ok(Duration::ZERO);
+            }
+            let start_instant = Instant::now();
+            for i in 0..iterations as u64 {
+                counter.store(2 * i + 1, Ordering::Release);
+                while counter.load(Ordering::Acquire) != 2 * i + 2 {
+                    spin_loop();
+                }
+            }
+            Ok(Instant::now().duration_since(start_instant))
+        });
+        let total_duration = ping.join().unwrap();
+        pong.join().unwrap().and(total_duration)
+    })
+}
@@ -0,0 +1,130 @@
+use crate::{
+    measure::measure,
+    record::{unix_millis, Mode, Output, Record},
+};
+use clap::{Parser, ValueEnum};
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt, hint::black_box, path::PathBuf, sync::Mutex};
+
+/// Largest working set that can be tested. Stack and static buffers need a size known at compile
+/// time, and this comfortably fits in the main thread's stack
+const MAX_SIZE: usize = 1024 * 1024;
+
+/// Statically allocated buffer, placed in the zero initialized data segment
+static STATIC_BUFFER: Mutex<[u8; MAX_SIZE]> = Mutex::new([0; MAX_SIZE]);
+
+/// Where the working set's memory lives
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Placement {
+    /// An array on the stack of the running thread
+    Stack,
+
+    /// A heap allocated Vec
+    Heap,
+
+    /// A static array in the program's data segment
+    Static,
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+#[derive(Parser)]
+pub struct PlacementArgs {
+    /// Initial working set size in bytes; must be a power of two
+    #[clap(short = 'd', long, default_value_t = 1024)]
+    min_size: usize,
+
+    /// Maximum working set size in bytes; must be a power of two of at most 1MiB
+    #[clap(short, long, default_value_t = MAX_SIZE)]
+    max_size: usize,
+
+    /// Number of random reads to perform per placement and size
+    #[clap(short, long, default_value_t = 1_000_000_000)]
+    iterations: usize,
+
+    /// Placements of the working set to compare at each size
+    #[clap(
+        short,
+        long,
+        value_delimiter = ',',
+        default_value = "stack,heap,static"
+    )]
+    placement: Vec<Placement>,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_placement(args: PlacementArgs) -> Result<(), Box<dyn Error>> {
+    if !args.min_size.is_power_of_two()
+        || !args.max_size.is_power_of_two()
+        || args.max_size < args.min_size
+        || args.max_size > MAX_SIZE
+    {
+        return Err(format!(
+            "--min-size and --max-size must be powers of two, with --min-size no larger than --max-size and --max-size at most {MAX_SIZE} bytes"
+        )
+        .into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    println!("Allocating buffers");
+    let mut rng = thread_rng();
+    let mut stack = [0u8; MAX_SIZE];
+    let mut heap = vec![0u8; MAX_SIZE];
+    let mut statik = STATIC_BUFFER.lock().unwrap();
+    for buffer in [&mut stack[..], &mut heap[..], &mut statik[..]] {
+        rng.fill(buffer);
+    }
+
+    let mut size = args.min_size;
+    while size <= args.max_size {
+        for &placement in &args.placement {
+            let buffer: &[u8] = match placement {
+                Placement::Stack => &stack[..size],
+                Placement::Heap => &heap[..size],
+                Placement::Static => &statik[..size],
+            };
+            println!("Testing a {size} byte {placement} buffer");
+            let start_time = unix_millis();
+            let mut sum: u8 = 0;
+            let total_duration = measure(args.iterations, || {
+                let position = rng.gen::<usize>() & (size - 1);
+                sum = sum.wrapping_add(black_box(buffer)[position]);
+            });
+            let total_duration_float = total_duration.as_secs_f32();
+            let steps_per_second = (args.iterations as f32) / total_duration_float;
+            let latency_ns = total_duration.as_nanos() as f64 / args.iterations as f64;
+            println!(
+                "\rCompleted testing: took {total_duration_float:.3} secs, with an average step rate of {steps_per_second:.2} steps/sec ({latency_ns:.2} ns/access). sum: {sum}"
+            );
+            out.write(&Record {
+                start_time,
+                step_size: size as u64,
+                total_duration_millis: total_duration.as_millis(),
+                steps_per_second,
+                avg_latency_ns: Some(latency_ns),
+                mode: Mode::Placement,
+                placement: Some(placement),
+                ..Record::default()
+            })?;
+        }
+        size <<= 1;
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
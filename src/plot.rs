@@ -0,0 +1,853 @@
+use crate::{
+    compare::summary,
+    record::{read_records, Mode, Record},
+};
+use clap::{Parser, ValueEnum};
+use plotters::{coord::Shift, prelude::*};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Parser)]
+pub struct PlotArgs {
+    /// Files containing test data to plot. Several files are overlaid in one plot, each series
+    /// labelled with the name of the file it came from, to compare runs such as before and after
+    /// a change
+    #[clap(required = true)]
+    data_files: Vec<PathBuf>,
+
+    /// Output image to save plot to [default same as the first input file with the extension of
+    /// the format]
+    #[clap(short, long)]
+    out_img: Option<PathBuf>,
+
+    /// Format to draw the plot in [default chosen by the extension of --out-img: svg, pdf, and
+    /// png for any other]
+    #[clap(short, long, value_enum)]
+    format: Option<ImageFormat>,
+
+    /// Also plot this miss rate of each series, from results recorded with --perf, against a
+    /// second axis on the right
+    #[clap(long, value_enum)]
+    miss_rate: Option<MissRate>,
+
+    /// Confidence level of the band shaded around each series, showing how far off each step's
+    /// mean could be given the spread of its repeats. Steps measured without --repeats have no
+    /// band
+    #[clap(long, default_value_t = 0.95, value_parser = parse_confidence)]
+    confidence: f64,
+
+    /// Leave out the confidence bands
+    #[clap(long)]
+    no_bands: bool,
+
+    /// Title to draw above the plot
+    #[clap(long)]
+    title: Option<String>,
+
+    /// Label of the x axis [default the size swept, such as "step size (bytes)"]
+    #[clap(long)]
+    x_label: Option<String>,
+
+    /// Label of the y axis [default the unit of throughput, such as "GB/s"]
+    #[clap(long)]
+    y_label: Option<String>,
+
+    /// Where to draw the legend. By default it's drawn on the right whenever the plot has more
+    /// than one thing to tell apart, such as several series or a confidence band; naming a
+    /// position draws it there even for a lone series
+    #[clap(long, value_enum)]
+    legend: Option<LegendPosition>,
+
+    /// Leave out the legend
+    #[clap(long, conflicts_with = "legend")]
+    no_legend: bool,
+}
+
+fn parse_confidence(s: &str) -> Result<f64, String> {
+    let confidence: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if confidence > 0.0 && confidence < 1.0 {
+        Ok(confidence)
+    } else {
+        Err(format!("{confidence} is not between 0 and 1"))
+    }
+}
+
+/// How a plot is drawn, beyond the records in it
+pub struct PlotOptions {
+    /// Miss rate of each series to plot against a second axis, as --miss-rate
+    pub miss_rate: Option<MissRate>,
+    /// Confidence level of the band around each series, if there are bands
+    pub confidence: Option<f64>,
+    pub title: Option<String>,
+    /// Label of the x axis, in place of the one chosen for the data
+    pub x_label: Option<String>,
+    /// Label of the y axis, in place of the one chosen for the data
+    pub y_label: Option<String>,
+    pub legend: Legend,
+}
+
+impl Default for PlotOptions {
+    fn default() -> Self {
+        PlotOptions {
+            miss_rate: None,
+            confidence: Some(0.95),
+            title: None,
+            x_label: None,
+            y_label: None,
+            legend: Legend::Auto,
+        }
+    }
+}
+
+/// Whether and where a plot's legend is drawn
+pub enum Legend {
+    /// On the right, when there's more than one thing in the plot to tell apart
+    Auto,
+    At(LegendPosition),
+    Hidden,
+}
+
+/// Corner or edge of the plot to draw the legend in, as --legend
+#[derive(Clone, Copy, ValueEnum)]
+pub enum LegendPosition {
+    UpperLeft,
+    UpperRight,
+    LowerLeft,
+    LowerRight,
+    Left,
+    Right,
+}
+
+impl LegendPosition {
+    fn position(self) -> SeriesLabelPosition {
+        match self {
+            LegendPosition::UpperLeft => SeriesLabelPosition::UpperLeft,
+            LegendPosition::UpperRight => SeriesLabelPosition::UpperRight,
+            LegendPosition::LowerLeft => SeriesLabelPosition::LowerLeft,
+            LegendPosition::LowerRight => SeriesLabelPosition::LowerRight,
+            LegendPosition::Left => SeriesLabelPosition::MiddleLeft,
+            LegendPosition::Right => SeriesLabelPosition::MiddleRight,
+        }
+    }
+}
+
+/// Size every plot is drawn at, in pixels for bitmaps and points for vector formats
+const SIZE: (u32, u32) = (1024, 1024);
+
+/// Formats a plot can be drawn in
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImageFormat {
+    /// A bitmap
+    Png,
+
+    /// Vector graphics, which scale cleanly in papers and slides
+    Svg,
+
+    /// A single page PDF of the same vector graphics, with its text embedded
+    Pdf,
+}
+
+impl ImageFormat {
+    /// The format suggested by a file's extension, or png for any other
+    pub fn of_path(path: &Path) -> ImageFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("svg") => ImageFormat::Svg,
+            Some("pdf") => ImageFormat::Pdf,
+            _ => ImageFormat::Png,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+            ImageFormat::Pdf => "pdf",
+        }
+    }
+}
+
+/// Misses recorded by --perf, as plotted by --miss-rate
+#[derive(Clone, Copy, ValueEnum)]
+pub enum MissRate {
+    /// L1 data cache misses
+    L1d,
+    /// Last level cache misses
+    Llc,
+    /// Data TLB misses
+    Dtlb,
+    /// Second level TLB misses, which walk the page tables
+    Stlb,
+}
+
+impl MissRate {
+    fn name(self) -> &'static str {
+        match self {
+            MissRate::L1d => "L1d",
+            MissRate::Llc => "LLC",
+            MissRate::Dtlb => "dTLB",
+            MissRate::Stlb => "STLB",
+        }
+    }
+
+    fn per_access(self, record: &Record) -> Option<f64> {
+        match self {
+            MissRate::L1d => record.l1d_misses_per_access,
+            MissRate::Llc => record.llc_misses_per_access,
+            MissRate::Dtlb => record.dtlb_misses_per_access,
+            MissRate::Stlb => record.stlb_misses_per_access,
+        }
+    }
+}
+
+pub fn plot_data(args: PlotArgs) -> Result<(), Box<dyn Error>> {
+    let format = args.format.unwrap_or_else(|| {
+        args.out_img
+            .as_deref()
+            .map_or(ImageFormat::Png, ImageFormat::of_path)
+    });
+    let out_img = args
+        .out_img
+        .unwrap_or_else(|| args.data_files[0].with_extension(format.extension()));
+
+    let (data, labels) = if let [data_file] = args.data_files.as_slice() {
+        let data = read_records(data_file)?;
+        let labels = series_labels(&data);
+        (data, labels)
+    } else {
+        overlay(&args.data_files)?
+    };
+    let options = PlotOptions {
+        miss_rate: args.miss_rate,
+        confidence: (!args.no_bands).then_some(args.confidence),
+        title: args.title,
+        x_label: args.x_label,
+        y_label: args.y_label,
+        legend: match (args.legend, args.no_legend) {
+            (_, true) => Legend::Hidden,
+            (Some(position), false) => Legend::At(position),
+            (None, false) => Legend::Auto,
+        },
+    };
+    plot_labelled(&data, &labels, &out_img, format, &options)?;
+
+    println!("Saved plot to {}", out_img.to_string_lossy());
+
+    Ok(())
+}
+
+/// Plots records in the format suggested by the image's extension
+pub fn plot_records(
+    data: &[Record],
+    out_img: &Path,
+    options: &PlotOptions,
+) -> Result<(), Box<dyn Error>> {
+    let labels = series_labels(data);
+    plot_labelled(
+        data,
+        &labels,
+        out_img,
+        ImageFormat::of_path(out_img),
+        options,
+    )
+}
+
+/// The records of several files together, each labelled with the name of its file, followed by
+/// its series label if the file holds more than one series
+fn overlay(data_files: &[PathBuf]) -> Result<(Vec<Record>, Vec<String>), Box<dyn Error>> {
+    // Files are named by their stems, such as `before` and `after`, unless those don't tell
+    // them apart
+    let stems: Vec<String> = data_files
+        .iter()
+        .map(|path| {
+            path.file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    let distinct = stems.iter().collect::<BTreeSet<_>>().len() == stems.len();
+    let (mut data, mut labels) = (Vec::new(), Vec::new());
+    for (data_file, stem) in data_files.iter().zip(stems) {
+        let records = read_records(data_file)?;
+        if records.iter().any(|record| record.mode == Mode::PingPong) {
+            return Err(format!(
+                "{} holds ping-pong results, which are drawn as a heatmap of their own rather than overlaid",
+                data_file.display()
+            )
+            .into());
+        }
+        let name = if distinct {
+            stem
+        } else {
+            data_file.to_string_lossy().into_owned()
+        };
+        let series = series_labels(&records);
+        let single = series.windows(2).all(|pair| pair[0] == pair[1]);
+        labels.extend(series.into_iter().map(|label| {
+            if single {
+                name.clone()
+            } else {
+                format!("{name} {label}")
+            }
+        }));
+        data.extend(records);
+    }
+    Ok((data, labels))
+}
+
+/// Plots records, each series labelled as given, in `format`. PDFs are drawn as svg and
+/// converted, with the system's fonts embedded for the text
+fn plot_labelled(
+    data: &[Record],
+    labels: &[String],
+    out_img: &Path,
+    format: ImageFormat,
+    options: &PlotOptions,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        ImageFormat::Png => draw_records(
+            &BitMapBackend::new(out_img, SIZE).into_drawing_area(),
+            data,
+            labels,
+            options,
+        ),
+        ImageFormat::Svg => draw_records(
+            &SVGBackend::new(out_img, SIZE).into_drawing_area(),
+            data,
+            labels,
+            options,
+        ),
+        ImageFormat::Pdf => {
+            let mut svg = String::new();
+            draw_records(
+                &SVGBackend::with_string(&mut svg, SIZE).into_drawing_area(),
+                data,
+                labels,
+                options,
+            )?;
+            let mut svg_options = svg2pdf::usvg::Options::default();
+            let fonts = svg_options.fontdb_mut();
+            fonts.load_system_fonts();
+            // Plots are labelled in sans-serif, which would otherwise be looked for as Arial and
+            // leave the text out where it isn't installed
+            let families: Vec<String> = fonts
+                .faces()
+                .flat_map(|face| face.families.iter().map(|(family, _)| family.clone()))
+                .collect();
+            let sans_serif = ["DejaVu Sans", "Liberation Sans", "Arial", "Helvetica"]
+                .into_iter()
+                .map(str::to_string)
+                .find(|family| families.contains(family))
+                .or_else(|| {
+                    let sans =
+                        |family: &&String| family.contains("Sans") && !family.contains("Mono");
+                    families.iter().find(sans).or(families.first()).cloned()
+                })
+                .ok_or("no fonts are installed to label the plot with")?;
+            fonts.set_sans_serif_family(sans_serif);
+            let tree = svg2pdf::usvg::Tree::from_str(&svg, &svg_options)?;
+            let pdf = svg2pdf::to_pdf(&tree, Default::default(), Default::default())
+                .map_err(|err| format!("failed to convert the plot to pdf ({err})"))?;
+            Ok(fs::write(out_img, pdf)?)
+        }
+    }
+}
+
+/// Draws records in whichever way suits them: ping-pong results as a heatmap of cores, sweeps
+/// over both the step size and the total size of a single series as a heatmap of sizes, and
+/// anything else as lines
+fn draw_records<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    data: &[Record],
+    labels: &[String],
+    options: &PlotOptions,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    if !data.is_empty() && data.iter().all(|record| record.mode == Mode::PingPong) {
+        plot_core_heatmap(root, data, options)
+    } else if !constant(data, |record| Some(record.step_size))
+        && !constant(data, |record| record.total_size)
+        && labels.windows(2).all(|pair| pair[0] == pair[1])
+    {
+        plot_sweep_heatmap(root, data, options)
+    } else {
+        plot_lines(root, data, labels, options)
+    }
+}
+
+/// A plot of a run's results that's redrawn as each step finishes, so long sweeps can be watched
+/// as they go
+pub struct LivePlot {
+    path: PathBuf,
+    records: Vec<Record>,
+}
+
+impl LivePlot {
+    pub fn new(path: PathBuf) -> Self {
+        LivePlot {
+            path,
+            records: Vec::new(),
+        }
+    }
+
+    /// Adds a step's records and redraws the plot. The plot is drawn to a temporary file and
+    /// renamed over the last one, so that image viewers never load a half written image. Failing
+    /// to draw only warns, rather than ending a run that may have been going for hours
+    pub fn add(&mut self, records: impl IntoIterator<Item = Record>) {
+        self.records.extend(records);
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(format!(
+            ".tmp.{}",
+            ImageFormat::of_path(&self.path).extension()
+        ));
+        let temporary = PathBuf::from(temporary);
+        let result = plot_records(&self.records, &temporary, &PlotOptions::default())
+            .and_then(|()| Ok(fs::rename(&temporary, &self.path)?));
+        if let Err(err) = result {
+            println!("Warning: failed to update the live plot ({err})");
+        }
+    }
+}
+
+/// Plots throughput against step size, with one line per series. Throughput is shown in GB/s
+/// when every record measured it, and in steps/sec otherwise. When the step size is the same
+/// throughout and the total size or thread count varies instead, throughput is plotted against
+/// that. Steps that were thermally throttled are marked with a cross. Steps with repeats are
+/// shaded with the confidence interval of their mean, and a miss rate, when given, is drawn as
+/// points against a second axis. The axes are labelled with the size and unit plotted, unless
+/// the options label them otherwise
+fn plot_lines<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    data: &[Record],
+    labels: &[String],
+    options: &PlotOptions,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let miss_rate = options.miss_rate;
+    if let Some(miss_rate) = miss_rate {
+        if data
+            .iter()
+            .any(|record| miss_rate.per_access(record).is_none())
+        {
+            return Err(format!(
+                "every record needs {} miss rates to plot them; record them with --perf",
+                miss_rate.name()
+            )
+            .into());
+        }
+    }
+    let (size_name, x) = swept_size(data);
+    let (unit, y): (&str, fn(&Record) -> f32) =
+        if data.iter().all(|r| r.gigabytes_per_second.is_some()) {
+            ("GB/s", |record| {
+                record.gigabytes_per_second.unwrap_or_default()
+            })
+        } else {
+            ("steps/sec", |record| record.steps_per_second)
+        };
+    let x_label = options.x_label.clone().unwrap_or_else(|| match size_name {
+        "threads" => size_name.to_string(),
+        _ => format!("{size_name} (bytes)"),
+    });
+    let y_label = options.y_label.clone().unwrap_or_else(|| unit.to_string());
+
+    let min_x = data.iter().map(x).min().ok_or("No data")?;
+    let max_x = data.iter().map(x).max().ok_or("No data")?;
+    let max_miss_rate = miss_rate
+        .and_then(|miss_rate| {
+            data.iter()
+                .filter_map(|record| miss_rate.per_access(record))
+                .max_by(|a, b| a.total_cmp(b))
+        })
+        .filter(|&max| max > 0.0)
+        .unwrap_or(1.0);
+    // The confidence interval of a step's mean, in the units plotted, for steps with repeats to
+    // estimate it from
+    let band = |record: &Record| -> Option<(f32, f32)> {
+        let summary = summary(record);
+        let half_width = summary.confidence_interval(options.confidence?)?;
+        let scale = y(record) as f64 / record.steps_per_second as f64;
+        let low = ((summary.mean - half_width) * scale).max(0.0);
+        Some((low as f32, ((summary.mean + half_width) * scale) as f32))
+    };
+    let max_y = data
+        .iter()
+        .map(|record| band(record).map_or(y(record), |(_, high)| high.max(y(record))))
+        .max_by(|a, b| a.total_cmp(b))
+        .ok_or("No data")?;
+
+    root.fill(&WHITE)?;
+    let mut plot = chart_builder(root, options)
+        .right_y_label_area_size(if miss_rate.is_some() { 100 } else { 0 })
+        .build_cartesian_2d((min_x..max_x).log_scale(), 0.0..max_y)?
+        .set_secondary_coord((min_x..max_x).log_scale(), 0.0..max_miss_rate);
+
+    plot.configure_mesh()
+        .x_desc(x_label)
+        .y_desc(y_label)
+        .draw()?;
+    if let Some(miss_rate) = miss_rate {
+        plot.configure_secondary_axes()
+            .y_desc(format!("{} misses per access", miss_rate.name()))
+            .draw()?;
+    }
+
+    let mut runs: BTreeMap<String, Vec<Vec<&Record>>> = BTreeMap::new();
+    for (record, label) in data.iter().zip(labels.iter().cloned()) {
+        // Files appended to hold several runs of a series one after another, so a size that
+        // comes before the last one starts another run, drawn as a line of its own
+        let runs = runs.entry(label).or_default();
+        match runs.last_mut() {
+            Some(run) if run.last().is_some_and(|&last| x(last) <= x(record)) => run.push(record),
+            _ => runs.push(vec![record]),
+        }
+    }
+    let series: Vec<(String, Vec<&Record>)> = runs
+        .into_iter()
+        .flat_map(|(label, runs)| {
+            let count = runs.len();
+            runs.into_iter().enumerate().map(move |(i, run)| {
+                let label = if count > 1 {
+                    format!("{label}, run {}", i + 1)
+                } else {
+                    label.clone()
+                };
+                (label, run)
+            })
+        })
+        .collect();
+    let throttled: Vec<(u64, f32)> = data
+        .iter()
+        .filter(|record| record.throttled == Some(true))
+        .map(|record| (x(record), y(record)))
+        .collect();
+    let throttled_any = !throttled.is_empty();
+    let multiple_series = series.len() > 1;
+    let mut banded = false;
+    for (i, (label, records)) in series.into_iter().enumerate() {
+        let color = if multiple_series {
+            Palette99::pick(i).to_rgba()
+        } else {
+            RED.to_rgba()
+        };
+        let bands: Vec<Option<(f32, f32)>> = records.iter().map(|&record| band(record)).collect();
+        if bands.iter().any(Option::is_some) {
+            banded = true;
+            // Around the top of the band, then back along its bottom, pinched to the line at
+            // steps without repeats
+            let upper = records
+                .iter()
+                .zip(&bands)
+                .map(|(&record, band)| (x(record), band.map_or(y(record), |(_, high)| high)));
+            let lower = records
+                .iter()
+                .zip(&bands)
+                .rev()
+                .map(|(&record, band)| (x(record), band.map_or(y(record), |(low, _)| low)));
+            plot.draw_series(std::iter::once(Polygon::new(
+                upper.chain(lower).collect::<Vec<_>>(),
+                color.mix(0.2).filled(),
+            )))?;
+        }
+        let points = records.iter().map(|&record| (x(record), y(record)));
+        plot.draw_series(LineSeries::new(points, color))?
+            .label(&label)
+            .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], color));
+        if let Some(miss_rate) = miss_rate {
+            let points = records.iter().map(|&record| {
+                let rate = miss_rate.per_access(record).unwrap_or_default();
+                Circle::new((x(record), rate), 3, color.filled())
+            });
+            plot.draw_secondary_series(points)?
+                .label(format!("{label} {} miss rate", miss_rate.name()))
+                .legend(move |(x, y)| Circle::new((x + 10, y), 3, color.filled()));
+        }
+    }
+    if throttled_any {
+        plot.draw_series(
+            throttled
+                .into_iter()
+                .map(|point| Cross::new(point, 6, BLACK.stroke_width(2))),
+        )?
+        .label("thermally throttled")
+        .legend(|(x, y)| Cross::new((x + 10, y), 6, BLACK.stroke_width(2)));
+    }
+    if let (true, Some(confidence)) = (banded, options.confidence) {
+        plot.draw_series(std::iter::empty::<PathElement<(u64, f32)>>())?
+            .label(format!(
+                "{}% confidence interval of the mean",
+                confidence * 100.0
+            ))
+            .legend(|(x, y)| {
+                Rectangle::new([(x, y - 5), (x + 20, y + 5)], BLACK.mix(0.2).filled())
+            });
+    }
+    let position = match options.legend {
+        Legend::Auto if multiple_series || throttled_any || banded || miss_rate.is_some() => {
+            Some(SeriesLabelPosition::MiddleRight)
+        }
+        Legend::Auto | Legend::Hidden => None,
+        Legend::At(position) => Some(position.position()),
+    };
+    if let Some(position) = position {
+        plot.configure_series_labels()
+            .position(position)
+            .background_style(WHITE)
+            .border_style(BLACK)
+            .draw()?;
+    }
+
+    root.present()?;
+
+    Ok(())
+}
+
+/// A chart on `root` with room for its axis labels, titled if the options give it a title
+fn chart_builder<'a, 'b, DB: DrawingBackend>(
+    root: &'a DrawingArea<DB, Shift>,
+    options: &PlotOptions,
+) -> ChartBuilder<'a, 'b, DB> {
+    let mut builder = ChartBuilder::on(root);
+    builder
+        .margin(5)
+        .x_label_area_size(50)
+        .y_label_area_size(100);
+    if let Some(title) = &options.title {
+        builder.caption(title, ("sans-serif", 30));
+    }
+    builder
+}
+
+/// Name of the size the data was swept over, and how to read it from a record: the step size, or
+/// the total size when the step size is the same throughout and the total size varies, or the
+/// number of threads when only it varies
+pub fn swept_size(data: &[Record]) -> (&'static str, fn(&Record) -> u64) {
+    let constant_step = constant(data, |record| Some(record.step_size));
+    let constant_total = constant(data, |record| record.total_size);
+    if constant_step && !constant_total {
+        ("total size", |record| record.total_size.unwrap_or_default())
+    } else if constant_step && !constant(data, |record| record.threads) {
+        ("threads", |record| record.threads.unwrap_or(1))
+    } else {
+        ("step size", |record| record.step_size)
+    }
+}
+
+/// Whether `value` is the same for every record
+fn constant(data: &[Record], value: fn(&Record) -> Option<u64>) -> bool {
+    data.windows(2)
+        .all(|pair| value(&pair[0]) == value(&pair[1]))
+}
+
+/// Labels each record with the values of its series parameters that vary across the data, or
+/// with its mode if they're all the same
+pub fn series_labels(data: &[Record]) -> Vec<String> {
+    let parameters: Vec<BTreeMap<&str, String>> = data
+        .iter()
+        .map(|record| record.series_parameters().into_iter().collect())
+        .collect();
+    // Keep the parameters in the order records list them, so labels read naturally
+    let mut names: Vec<&str> = Vec::new();
+    for record in data {
+        for (name, _) in record.series_parameters() {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    let varying: Vec<&str> = names
+        .into_iter()
+        .filter(|name| {
+            let mut values = parameters.iter().map(|parameters| parameters.get(name));
+            let first = values.next().flatten();
+            values.any(|value| value != first)
+        })
+        .collect();
+    data.iter()
+        .zip(&parameters)
+        .map(|(record, parameters)| {
+            let label: Vec<&str> = varying
+                .iter()
+                .filter_map(|name| parameters.get(name).map(String::as_str))
+                .collect();
+            if label.is_empty() {
+                record.mode.to_string()
+            } else {
+                label.join(" ")
+            }
+        })
+        .collect()
+}
+
+/// Plots ping-pong results as a core by core grid of round trip latencies
+fn plot_core_heatmap<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    data: &[Record],
+    options: &PlotOptions,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let cores: BTreeSet<usize> = data
+        .iter()
+        .flat_map(|record| [record.first_core, record.second_core])
+        .flatten()
+        .collect();
+    let cores: Vec<usize> = cores.into_iter().collect();
+    let index = |core| cores.binary_search(&core).unwrap() as i32;
+    let cells = data
+        .iter()
+        .filter_map(|record| {
+            let latency_ns = record
+                .avg_latency_ns
+                .unwrap_or(1e9 / record.steps_per_second as f64);
+            let (first, second) = (index(record.first_core?), index(record.second_core?));
+            Some([(first, second, latency_ns), (second, first, latency_ns)])
+        })
+        .flatten()
+        .collect();
+    let labels: Vec<String> = cores.iter().map(usize::to_string).collect();
+    draw_heatmap(
+        root,
+        &Heatmap {
+            x_labels: labels.clone(),
+            y_labels: labels,
+            x_desc: "core",
+            y_desc: "core",
+            cells,
+            format: |latency_ns| format!("{latency_ns:.0}"),
+        },
+        options,
+    )
+}
+
+/// Plots a two dimensional sweep as a grid of throughput, with step sizes across and total sizes
+/// up
+fn plot_sweep_heatmap<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    data: &[Record],
+    options: &PlotOptions,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let step_sizes: Vec<u64> = data
+        .iter()
+        .map(|record| record.step_size)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let total_sizes: Vec<u64> = data
+        .iter()
+        .filter_map(|record| record.total_size)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let cells = data
+        .iter()
+        .filter_map(|record| {
+            let x = step_sizes.binary_search(&record.step_size).ok()? as i32;
+            let y = total_sizes.binary_search(&record.total_size?).ok()? as i32;
+            Some((x, y, record.steps_per_second as f64))
+        })
+        .collect();
+    draw_heatmap(
+        root,
+        &Heatmap {
+            x_labels: step_sizes.iter().map(u64::to_string).collect(),
+            y_labels: total_sizes.iter().map(u64::to_string).collect(),
+            x_desc: "step size",
+            y_desc: "total size",
+            cells,
+            format: |steps_per_second| format!("{:.1}M", steps_per_second / 1e6),
+        },
+        options,
+    )
+}
+
+/// A grid of values, with cells given as column index, row index and value
+struct Heatmap {
+    x_labels: Vec<String>,
+    y_labels: Vec<String>,
+    x_desc: &'static str,
+    y_desc: &'static str,
+    cells: Vec<(i32, i32, f64)>,
+    format: fn(f64) -> String,
+}
+
+/// Draws a heatmap, colored from blue for the lowest value to red for the highest
+fn draw_heatmap<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    heatmap: &Heatmap,
+    options: &PlotOptions,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let (min_value, max_value) = heatmap
+        .cells
+        .iter()
+        .map(|&(_, _, value)| value)
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+
+    root.fill(&WHITE)?;
+    // Segmented ranges include their end, so this gives one segment per label
+    let last_x = heatmap.x_labels.len() as i32 - 1;
+    let last_y = heatmap.y_labels.len() as i32 - 1;
+    let mut plot = chart_builder(root, options)
+        .build_cartesian_2d((0..last_x).into_segmented(), (0..last_y).into_segmented())?;
+
+    let label = |labels: &[String], value: &SegmentValue<i32>| match value {
+        SegmentValue::CenterOf(i) => labels.get(*i as usize).cloned().unwrap_or_default(),
+        _ => String::new(),
+    };
+    plot.configure_mesh()
+        .disable_mesh()
+        .x_labels(heatmap.x_labels.len())
+        .y_labels(heatmap.y_labels.len())
+        .x_label_formatter(&|value| label(&heatmap.x_labels, value))
+        .y_label_formatter(&|value| label(&heatmap.y_labels, value))
+        .x_desc(options.x_label.as_deref().unwrap_or(heatmap.x_desc))
+        .y_desc(options.y_label.as_deref().unwrap_or(heatmap.y_desc))
+        .draw()?;
+
+    plot.draw_series(heatmap.cells.iter().map(|&(x, y, value)| {
+        let t = if max_value > min_value {
+            (value - min_value) / (max_value - min_value)
+        } else {
+            0.0
+        };
+        Rectangle::new(
+            [
+                (SegmentValue::Exact(x), SegmentValue::Exact(y)),
+                (SegmentValue::Exact(x + 1), SegmentValue::Exact(y + 1)),
+            ],
+            HSLColor(2.0 / 3.0 * (1.0 - t), 1.0, 0.5).filled(),
+        )
+    }))?;
+
+    // Annotate each cell with its value when there's room for the text
+    if heatmap.x_labels.len() <= 32 && heatmap.y_labels.len() <= 32 {
+        plot.draw_series(heatmap.cells.iter().map(|&(x, y, value)| {
+            Text::new(
+                (heatmap.format)(value),
+                (SegmentValue::CenterOf(x), SegmentValue::CenterOf(y)),
+                ("sans-serif", 12).into_font().color(&BLACK),
+            )
+        }))?;
+    }
+
+    root.present()?;
+
+    Ok(())
+}
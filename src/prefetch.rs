@@ -0,0 +1,159 @@
+use crate::{
+    buffer::{Buffer, BufferOptions},
+    measure::measure,
+    record::{unix_millis, Mode, Output, Record},
+};
+use clap::{Parser, ValueEnum};
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt, path::PathBuf, time::Duration};
+
+/// Cache level hint given to the prefetch instruction
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hint {
+    /// Don't prefetch at all, as a baseline; ignores the distance
+    None,
+
+    /// prefetcht0: fetch into all levels of the cache
+    T0,
+
+    /// prefetcht1: fetch into the second level cache and beyond
+    T1,
+
+    /// prefetcht2: fetch into the third level cache and beyond
+    T2,
+
+    /// prefetchnta: fetch close to the core while minimizing cache pollution
+    Nta,
+}
+
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+#[derive(Parser)]
+pub struct PrefetchArgs {
+    /// Amount of memory to allocate for test
+    #[clap(short, long, default_value_t = 1024 * 1024 * 1024)]
+    total_size: usize,
+
+    /// Number of random positions to generate before timing; they are visited in a loop
+    #[clap(long, default_value_t = 1 << 24)]
+    positions: usize,
+
+    /// Maximum prefetch distance, in accesses ahead of the demand load, doubling from 1
+    #[clap(short, long, default_value_t = 1024)]
+    max_distance: usize,
+
+    /// Number of iterations to run per distance
+    #[clap(short, long, default_value_t = 100_000_000)]
+    iterations: usize,
+
+    /// Prefetch hints to test at each distance
+    #[clap(long, value_delimiter = ',', default_value = "none,t0,nta")]
+    hint: Vec<Hint>,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_prefetch(args: PrefetchArgs) -> Result<(), Box<dyn Error>> {
+    if args.total_size == 0 || args.positions == 0 || args.max_distance == 0 {
+        return Err("--total-size, --positions and --max-distance must all be at least 1".into());
+    }
+    if cfg!(not(target_arch = "x86_64")) && args.hint.iter().any(|&hint| hint != Hint::None) {
+        return Err("software prefetching is only supported on x86_64".into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    println!("Allocating random data");
+    let mem = Buffer::random(args.total_size, &BufferOptions::default())?;
+    let mut rng = thread_rng();
+    let positions: Vec<usize> = (0..args.positions)
+        .map(|_| rng.gen_range(0..args.total_size))
+        .collect();
+
+    let mut distance = 1;
+    while distance <= args.max_distance {
+        for &hint in &args.hint {
+            println!("Testing prefetch distance {distance} with {hint} hint");
+            let start_time = unix_millis();
+            let (sum, total_duration) = match hint {
+                Hint::None => visit(&mem, &positions, args.iterations, |_| {}),
+                #[cfg(target_arch = "x86_64")]
+                hint => {
+                    use std::arch::x86_64::*;
+                    let prefetch: fn(*const u8) = match hint {
+                        // SAFETY: prefetching is only a hint and never faults, whatever the
+                        // address
+                        Hint::T0 => |p| unsafe { _mm_prefetch::<_MM_HINT_T0>(p.cast()) },
+                        Hint::T1 => |p| unsafe { _mm_prefetch::<_MM_HINT_T1>(p.cast()) },
+                        Hint::T2 => |p| unsafe { _mm_prefetch::<_MM_HINT_T2>(p.cast()) },
+                        Hint::Nta | Hint::None => {
+                            |p| unsafe { _mm_prefetch::<_MM_HINT_NTA>(p.cast()) }
+                        }
+                    };
+                    visit(&mem, &positions, args.iterations, |ahead| {
+                        prefetch(&mem[positions[(ahead + distance) % positions.len()]])
+                    })
+                }
+                #[cfg(not(target_arch = "x86_64"))]
+                _ => unreachable!("prefetch hints are rejected up front"),
+            };
+            let total_duration_float = total_duration.as_secs_f32();
+            let steps_per_second = (args.iterations as f32) / total_duration_float;
+            let latency_ns = total_duration.as_nanos() as f64 / args.iterations as f64;
+            println!(
+                "\rCompleted testing: took {total_duration_float:.3} secs, with an average access rate of {steps_per_second:.2} steps/sec ({latency_ns:.2} ns/access). sum: {sum}"
+            );
+            out.write(&Record {
+                start_time,
+                step_size: distance as u64,
+                total_duration_millis: total_duration.as_millis(),
+                steps_per_second,
+                avg_latency_ns: Some(latency_ns),
+                mode: Mode::Prefetch,
+                total_size: Some(args.total_size as u64),
+                prefetch_distance: Some(distance as u64),
+                prefetch_hint: Some(hint),
+                ..Record::default()
+            })?;
+        }
+        distance <<= 1;
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Reads the byte at each of `positions` in turn, looping back to the start, for `iterations`
+/// accesses. Before each read, `prefetch` is called with the index into `positions` of the
+/// access being made. Returns the wrapping sum of all bytes read along with the time taken.
+fn visit(
+    mem: &[u8],
+    positions: &[usize],
+    iterations: usize,
+    mut prefetch: impl FnMut(usize),
+) -> (u8, Duration) {
+    let mut sum: u8 = 0;
+    let mut index = 0;
+    let total_duration = measure(iterations, || {
+        prefetch(index);
+        sum = sum.wrapping_add(mem[positions[index]]);
+        index += 1;
+        if index == positions.len() {
+            index = 0;
+        }
+    });
+    (sum, total_duration)
+}
@@ -0,0 +1,945 @@
+use crate::{
+    affinity::ThreadPlacement,
+    bandwidth::Kernel,
+    buffer::{BufferInit, NumaPolicy},
+    columnar::{read_ipc, read_parquet, record_batch, ARROW_MAGIC, PARQUET_MAGIC},
+    compress::{decompress, Compression, Sink},
+    contention::Atomics,
+    influx::{self, read_lines, MEASUREMENT},
+    matrix::Traversal,
+    measure::Timer,
+    placement::Placement,
+    prefetch::Hint,
+    sharing::Layout,
+    stats::Aggregate,
+    store::Store,
+    system::System,
+    walk::{Branch, Direction, Op, Pattern, Sampling, ThreadBuffers},
+    writeback::Eviction,
+};
+use clap::ValueEnum;
+use csv::Reader;
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+use std::{
+    collections::HashSet,
+    error::Error,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, IsTerminal, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// A single measurement, written as one row of the results file
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Record {
+    pub start_time: u128,
+    pub step_size: u64,
+    pub total_duration_millis: u128,
+    pub steps_per_second: f32,
+    #[serde(default)]
+    pub pattern: Option<Pattern>,
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default)]
+    pub op: Option<Op>,
+    #[serde(default)]
+    pub write_ratio: Option<f64>,
+    #[serde(default)]
+    pub zipf_exponent: Option<f64>,
+    #[serde(default)]
+    pub sigma: Option<f64>,
+    #[serde(default)]
+    pub hot_size: Option<u64>,
+    #[serde(default)]
+    pub hot_probability: Option<f64>,
+    #[serde(default)]
+    pub traversal: Option<Traversal>,
+    #[serde(default)]
+    pub layout: Option<Layout>,
+    #[serde(default)]
+    pub first_core: Option<usize>,
+    #[serde(default)]
+    pub second_core: Option<usize>,
+    #[serde(default)]
+    pub pages_touched: Option<u64>,
+    #[serde(default)]
+    pub page_size: Option<u64>,
+    #[serde(default)]
+    pub store: Option<Store>,
+    #[serde(default)]
+    pub gigabytes_per_second: Option<f32>,
+    #[serde(default)]
+    pub kernel: Option<Kernel>,
+    #[serde(default)]
+    pub total_size: Option<u64>,
+    #[serde(default)]
+    pub prefetch_distance: Option<u64>,
+    #[serde(default)]
+    pub prefetch_hint: Option<Hint>,
+    #[serde(default)]
+    pub element_size: Option<u64>,
+    #[serde(default)]
+    pub vector_width: Option<u64>,
+    #[serde(default)]
+    pub atomics: Option<Atomics>,
+    #[serde(default)]
+    pub region_size: Option<u64>,
+    #[serde(default)]
+    pub memory_node: Option<usize>,
+    #[serde(default)]
+    pub cpu_node: Option<usize>,
+    #[serde(default)]
+    pub direction: Option<Direction>,
+    #[serde(default)]
+    pub tile_accesses: Option<u64>,
+    #[serde(default)]
+    pub chains: Option<u64>,
+    #[serde(default)]
+    pub stride: Option<u64>,
+    #[serde(default)]
+    pub backing: Option<String>,
+    #[serde(default)]
+    pub flush_batch: Option<u64>,
+    #[serde(default)]
+    pub eviction: Option<Eviction>,
+    #[serde(default)]
+    pub branch: Option<Branch>,
+    #[serde(default)]
+    pub burst_page: Option<u64>,
+    #[serde(default)]
+    pub chunk_size: Option<u64>,
+    #[serde(default)]
+    pub placement: Option<Placement>,
+    #[serde(default)]
+    pub cycles: Option<u64>,
+    #[serde(default)]
+    pub instructions: Option<u64>,
+    #[serde(default)]
+    pub l1d_misses: Option<u64>,
+    #[serde(default)]
+    pub llc_misses: Option<u64>,
+    #[serde(default)]
+    pub dtlb_misses: Option<u64>,
+    #[serde(default)]
+    pub avg_latency_ns: Option<f64>,
+    #[serde(default)]
+    pub repeats: Option<u64>,
+    #[serde(default)]
+    pub steps_per_second_stddev: Option<f32>,
+    #[serde(default)]
+    pub steps_per_second_min: Option<f32>,
+    #[serde(default)]
+    pub steps_per_second_max: Option<f32>,
+    #[serde(default)]
+    pub warmup_iterations: Option<u64>,
+    #[serde(default)]
+    pub iterations: Option<u64>,
+    #[serde(default)]
+    pub steps_per_second_median: Option<f32>,
+    #[serde(default)]
+    pub trim: Option<f64>,
+    #[serde(default)]
+    pub aggregate: Option<Aggregate>,
+    #[serde(default)]
+    pub index_buffer: Option<u64>,
+    #[serde(default)]
+    pub overhead_ns: Option<f64>,
+    #[serde(default)]
+    pub overhead_subtracted: Option<bool>,
+    #[serde(default)]
+    pub sampling: Option<Sampling>,
+    #[serde(default)]
+    pub histogram_batch: Option<u64>,
+    #[serde(default)]
+    pub p50_ns: Option<f64>,
+    #[serde(default)]
+    pub p90_ns: Option<f64>,
+    #[serde(default)]
+    pub p99_ns: Option<f64>,
+    #[serde(default)]
+    pub p999_ns: Option<f64>,
+    #[serde(default)]
+    pub timer: Option<Timer>,
+    #[serde(default)]
+    pub cycles_per_access: Option<f64>,
+    #[serde(default)]
+    pub frequency_mhz: Option<f64>,
+    #[serde(default)]
+    pub frequency_min_mhz: Option<f64>,
+    #[serde(default)]
+    pub frequency_max_mhz: Option<f64>,
+    #[serde(default)]
+    pub package_joules: Option<f64>,
+    #[serde(default)]
+    pub dram_joules: Option<f64>,
+    #[serde(default)]
+    pub package_nj_per_access: Option<f64>,
+    #[serde(default)]
+    pub dram_nj_per_access: Option<f64>,
+    #[serde(default)]
+    pub max_temperature_c: Option<f64>,
+    #[serde(default)]
+    pub throttle_events: Option<u64>,
+    #[serde(default)]
+    pub throttled: Option<bool>,
+    #[serde(default)]
+    pub l1d_misses_per_access: Option<f64>,
+    #[serde(default)]
+    pub llc_misses_per_access: Option<f64>,
+    #[serde(default)]
+    pub dtlb_misses_per_access: Option<f64>,
+    #[serde(default)]
+    pub ipc: Option<f64>,
+    #[serde(default)]
+    pub stlb_misses: Option<u64>,
+    #[serde(default)]
+    pub stlb_misses_per_access: Option<f64>,
+    #[serde(default)]
+    pub minor_faults: Option<u64>,
+    #[serde(default)]
+    pub major_faults: Option<u64>,
+    #[serde(default)]
+    pub swapped: Option<bool>,
+    #[serde(default)]
+    pub swapped_pages: Option<u64>,
+    #[serde(default)]
+    pub memory_stall_us: Option<u64>,
+    #[serde(default)]
+    pub mlock: Option<bool>,
+    #[serde(default)]
+    pub prefault: Option<bool>,
+    #[serde(default)]
+    pub interleaved: Option<bool>,
+    #[serde(default)]
+    pub steps_per_second_mean: Option<f32>,
+    #[serde(default)]
+    pub repeats_kept: Option<u64>,
+    /// Wrapping sum of every value loaded, or of the count of elements written
+    #[serde(default)]
+    pub sum: Option<u64>,
+    /// Seed of the random number generator that filled the buffer and chose the positions
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Checksum of every position visited, in order
+    #[serde(default)]
+    pub index_checksum: Option<u64>,
+    /// Number of threads accessing the buffer at once
+    #[serde(default)]
+    pub threads: Option<u64>,
+    /// Rate of each thread, when several ran at once
+    #[serde(default)]
+    pub steps_per_second_per_thread: Option<f32>,
+    /// Cores the threads were pinned to, in order
+    #[serde(default)]
+    pub pinned_cores: Option<String>,
+    /// How the buffer was placed across NUMA nodes, when it was
+    #[serde(default)]
+    pub numa_policy: Option<NumaPolicy>,
+    /// How the threads were placed by the cpu's topology
+    #[serde(default)]
+    pub thread_placement: Option<ThreadPlacement>,
+    /// Which thread a per-thread record describes, counting from 0; aggregate records of every
+    /// thread leave it empty
+    #[serde(default)]
+    pub thread: Option<u64>,
+    /// Core the thread was last seen running on
+    #[serde(default)]
+    pub core: Option<usize>,
+    /// Whether the threads shared one buffer or each walked their own
+    #[serde(default)]
+    pub thread_buffers: Option<ThreadBuffers>,
+    /// What the buffer was filled with
+    #[serde(default)]
+    pub init: Option<BufferInit>,
+    /// Model of the cpu the record was measured on, and the rest of the machine's description
+    /// from `System`
+    #[serde(default)]
+    pub cpu_model: Option<String>,
+    #[serde(default)]
+    pub cpu_count: Option<usize>,
+    #[serde(default)]
+    pub l1d_cache_bytes: Option<u64>,
+    #[serde(default)]
+    pub l2_cache_bytes: Option<u64>,
+    #[serde(default)]
+    pub l3_cache_bytes: Option<u64>,
+    #[serde(default)]
+    pub memory_bytes: Option<u64>,
+    #[serde(default)]
+    pub os: Option<String>,
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Version of the layout of the record, see `SCHEMA_VERSION`
+    #[serde(default)]
+    pub schema_version: Option<u32>,
+    /// Command line of the run that measured the record
+    #[serde(default)]
+    pub arguments: Option<String>,
+    /// Which interval of its step a sample record covers, counting from 0; the step's own record
+    /// leaves it empty
+    #[serde(default)]
+    pub sample: Option<u64>,
+    /// How long the step had been timed for when the sample's interval ended, in milliseconds
+    #[serde(default)]
+    pub sample_time_ms: Option<f64>,
+}
+
+/// Version of the layout of the records this version writes. Columns are only ever added, with
+/// defaults for files without them, so this is raised when one is renamed or changes meaning;
+/// records from before it was recorded read as version 1
+pub const SCHEMA_VERSION: u32 = 2;
+
+impl Record {
+    /// Named parameters identifying the series this record belongs to, formatted for use in a plot
+    /// label. Only the parameters that differ between series need to be shown.
+    pub fn series_parameters(&self) -> Vec<(&'static str, String)> {
+        let mut parameters = vec![("mode", self.mode.to_string())];
+        if self.mode == Mode::Test {
+            parameters.push(("pattern", self.pattern.unwrap_or_default().to_string()));
+            parameters.push(("op", self.op.unwrap_or_default().to_string()));
+        }
+        let optional = [
+            ("direction", self.direction.map(|d| d.to_string())),
+            ("branch", self.branch.map(|b| b.to_string())),
+            ("sampling", self.sampling.map(|s| format!("{s} sampling"))),
+            (
+                "write_ratio",
+                self.write_ratio.map(|r| format!("write ratio {r}")),
+            ),
+            (
+                "zipf_exponent",
+                self.zipf_exponent.map(|e| format!("exponent {e}")),
+            ),
+            ("sigma", self.sigma.map(|s| format!("sigma {s}"))),
+            ("hot_size", self.hot_size.map(|s| format!("hot size {s}"))),
+            (
+                "hot_probability",
+                self.hot_probability.map(|p| format!("hot probability {p}")),
+            ),
+            (
+                "tile_accesses",
+                self.tile_accesses.map(|a| format!("{a} accesses per tile")),
+            ),
+            (
+                "burst_page",
+                self.burst_page.map(|p| format!("{p} byte burst pages")),
+            ),
+            ("chains", self.chains.map(|c| format!("{c} chains"))),
+            (
+                "chunk_size",
+                self.chunk_size.map(|c| match self.total_size {
+                    Some(total_size) if c == total_size => "contiguous".to_string(),
+                    _ => format!("{c} byte chunks"),
+                }),
+            ),
+            ("stride", self.stride.map(|s| format!("stride {s}"))),
+            ("traversal", self.traversal.map(|t| t.to_string())),
+            ("layout", self.layout.map(|l| l.to_string())),
+            ("placement", self.placement.map(|p| p.to_string())),
+            ("store", self.store.map(|s| s.to_string())),
+            (
+                "element_size",
+                self.element_size.map(|s| format!("{s}-byte elements")),
+            ),
+            (
+                "vector_width",
+                self.vector_width.map(|w| format!("{w}-byte vector loads")),
+            ),
+            ("atomics", self.atomics.map(|a| format!("{a} atomics"))),
+            (
+                "region_size",
+                self.region_size.map(|s| format!("region {s}")),
+            ),
+            (
+                "memory_node",
+                self.memory_node.map(|n| format!("memory on node {n}")),
+            ),
+            ("thread", self.thread.map(|t| format!("thread {t}"))),
+            (
+                "thread_buffers",
+                self.thread_buffers.map(|b| format!("{b} thread buffers")),
+            ),
+            (
+                "thread_placement",
+                self.thread_placement
+                    .map(|p| format!("{p} thread placement")),
+            ),
+            (
+                "numa_policy",
+                self.numa_policy.map(|p| format!("{p} NUMA policy")),
+            ),
+            (
+                "cpu_node",
+                self.cpu_node.map(|n| format!("cpu on node {n}")),
+            ),
+            ("eviction", self.eviction.map(|e| format!("{e} evictions"))),
+            ("kernel", self.kernel.map(|k| k.to_string())),
+            (
+                "prefetch_hint",
+                self.prefetch_hint.map(|h| format!("{h} hint")),
+            ),
+            ("backing", self.backing.clone()),
+            (
+                "flush_batch",
+                self.flush_batch.map(|b| format!("flushed every {b}")),
+            ),
+            (
+                "page_size",
+                self.page_size.map(|s| format!("{}KiB pages", s / 1024)),
+            ),
+        ];
+        parameters.extend(
+            optional
+                .into_iter()
+                .filter_map(|(name, value)| Some((name, value?))),
+        );
+        parameters
+    }
+}
+
+/// The benchmark that produced a record
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    #[default]
+    Test,
+    Chase,
+    Matrix,
+    FalseSharing,
+    PingPong,
+    Tlb,
+    Store,
+    Bandwidth,
+    Prefetch,
+    Icache,
+    Contention,
+    Copy,
+    Alias,
+    Writeback,
+    Combining,
+    Fragment,
+    Placement,
+    Handoff,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Mode::Test => "test",
+            Mode::Chase => "chase",
+            Mode::Matrix => "matrix",
+            Mode::FalseSharing => "false-sharing",
+            Mode::PingPong => "ping-pong",
+            Mode::Tlb => "tlb",
+            Mode::Store => "store",
+            Mode::Bandwidth => "bandwidth",
+            Mode::Prefetch => "prefetch",
+            Mode::Icache => "icache",
+            Mode::Contention => "contention",
+            Mode::Copy => "copy",
+            Mode::Alias => "alias",
+            Mode::Writeback => "writeback",
+            Mode::Combining => "combining",
+            Mode::Fragment => "fragment",
+            Mode::Placement => "placement",
+            Mode::Handoff => "handoff",
+        })
+    }
+}
+
+/// Reads every record from a results file, in any format and of any version, warning about
+/// records of a later version than this one, whose columns may not mean what they used to. The
+/// records of the samples within steps are left out, as they'd be taken for steps of their own
+pub fn read_records(path: &Path) -> Result<Vec<Record>, Box<dyn Error>> {
+    let mut records = read_any(path)?;
+    records.retain(|record| record.sample.is_none());
+    let latest = records
+        .iter()
+        .map(|record| record.schema_version.unwrap_or(1))
+        .max();
+    if let Some(latest) = latest.filter(|&latest| latest > SCHEMA_VERSION) {
+        println!(
+            "Warning: {} holds records of version {latest} of the results layout, newer than this version's {SCHEMA_VERSION}; columns this version doesn't know are ignored",
+            path.display()
+        );
+    }
+    Ok(records)
+}
+
+fn read_any(path: &Path) -> Result<Vec<Record>, Box<dyn Error>> {
+    if Format::of_path(path) == Format::Sqlite {
+        return Err(format!(
+            "{} is a SQLite database, which can hold many runs; select the records of one with sqlite3 and export them as csv to read them",
+            path.display()
+        )
+        .into());
+    }
+    let bytes = decompress(fs::read(path)?)?;
+    if bytes.starts_with(ARROW_MAGIC) {
+        return Ok(read_ipc(bytes)?
+            .into_iter()
+            .map(|fields| serde_json::from_value(serde_json::Value::Object(fields)))
+            .try_collect()?);
+    }
+    if bytes.starts_with(PARQUET_MAGIC) && bytes.ends_with(PARQUET_MAGIC) {
+        return Ok(read_parquet(bytes)?
+            .into_iter()
+            .map(|fields| serde_json::from_value(serde_json::Value::Object(fields)))
+            .try_collect()?);
+    }
+    let contents = String::from_utf8(bytes)
+        .map_err(|_| format!("{} isn't a results file of any format", path.display()))?;
+    // Each line of json lines is a whole record, where json's first line only opens its object
+    let first_line = contents.lines().next().unwrap_or_default();
+    if serde_json::from_str::<Record>(first_line).is_ok() {
+        return Ok(contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .try_collect()?);
+    }
+    if first_line.starts_with(&format!("{MEASUREMENT},")) {
+        return Ok(read_lines(&contents)?
+            .into_iter()
+            .map(|fields| serde_json::from_value(serde_json::Value::Object(fields)))
+            .try_collect()?);
+    }
+    if contents.trim_start().starts_with('{') {
+        let results: JsonResults = serde_json::from_str(&contents)
+            .map_err(|err| format!("failed to read {} as json ({err})", path.display()))?;
+        return Ok(results.records);
+    }
+    Ok(Reader::from_reader(contents.as_bytes())
+        .deserialize()
+        .try_collect()?)
+}
+
+/// Format of a results file
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// One row per record, with a header row naming the columns
+    #[default]
+    Csv,
+
+    /// A json object holding a `metadata` object describing the run, and the `records` array
+    Json,
+
+    /// Json lines: one json object per record on a line of its own, appended as each step
+    /// finishes, which suits piping into other tools
+    Jsonl,
+
+    /// A SQLite database, which each run adds a row of `runs` and its rows of `records` to
+    Sqlite,
+
+    /// Parquet, with a typed column per field, written whole when the run finishes
+    Parquet,
+
+    /// An Arrow IPC file, also known as feather, typed the same way as parquet and likewise
+    /// written when the run finishes
+    Arrow,
+
+    /// InfluxDB line protocol: a point of the `cache_locality` measurement per record, tagged
+    /// with the machine, mode, series and step, appended as each step finishes and ready for
+    /// `influx write`
+    Influx,
+}
+
+impl Format {
+    /// The format suggested by a file's extension, under any compression's, or csv for any other
+    pub fn of_path(path: &Path) -> Format {
+        let path = match Compression::of_path(path) {
+            Compression::None => path,
+            _ => Path::new(path.file_stem().unwrap_or_default()),
+        };
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Format::Json,
+            Some("jsonl" | "ndjson") => Format::Jsonl,
+            Some("sqlite" | "sqlite3" | "db") => Format::Sqlite,
+            Some("parquet") => Format::Parquet,
+            Some("arrow" | "feather" | "ipc") => Format::Arrow,
+            Some("lp" | "influx") => Format::Influx,
+            _ => Format::Csv,
+        }
+    }
+}
+
+/// The layout of a json results file, as far as reading its records back goes
+#[derive(Deserialize)]
+struct JsonResults {
+    records: Vec<Record>,
+}
+
+/// Describes the run writing a results file, for the header of formats that have one
+fn metadata() -> serde_json::Value {
+    serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "arguments": std::env::args().collect::<Vec<_>>(),
+        "start_time": unix_millis(),
+        "schema_version": SCHEMA_VERSION,
+        "system": System::detect(),
+    })
+}
+
+enum Writer {
+    Csv(Box<csv::Writer<Sink>>),
+    /// Whether any record has been written yet, to know whether the next needs a comma
+    Json(BufWriter<Sink>, bool),
+    Jsonl(Sink),
+    Influx(Sink),
+    Sqlite(Database),
+    /// Columnar formats are written all at once, so the records' fields are kept until then
+    Columnar(Format, Sink, Vec<Map<String, serde_json::Value>>),
+}
+
+/// The fields a record serializes to, by name
+pub fn fields(record: &Record) -> Result<Map<String, serde_json::Value>, Box<dyn Error>> {
+    match serde_json::to_value(record)? {
+        serde_json::Value::Object(fields) => Ok(fields),
+        _ => unreachable!("records serialize to objects"),
+    }
+}
+
+/// A SQLite database of results, with a table of `runs` and a table of the `records` of every
+/// run, keyed by their `run_id`
+struct Database {
+    connection: rusqlite::Connection,
+    run_id: i64,
+    /// Columns the records table has so far, which grows as later versions add fields
+    columns: HashSet<String>,
+}
+
+impl Database {
+    /// Opens the database, creating it if need be, and adds this run to it
+    fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let connection = rusqlite::Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS runs (
+                run_id INTEGER PRIMARY KEY,
+                metadata TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS records (run_id INTEGER NOT NULL REFERENCES runs (run_id));",
+        )?;
+        connection.execute(
+            "INSERT INTO runs (metadata) VALUES (?1)",
+            [metadata().to_string()],
+        )?;
+        let run_id = connection.last_insert_rowid();
+        let columns = connection
+            .prepare("SELECT name FROM pragma_table_info('records')")?
+            .query_map([], |row| row.get(0))?
+            .try_collect()?;
+        Ok(Database {
+            connection,
+            run_id,
+            columns,
+        })
+    }
+
+    /// Inserts a record, adding a column for any field the table doesn't have yet. Each insert
+    /// is committed on its own, so interrupted runs keep every record written
+    fn insert(&mut self, record: &Record) -> Result<(), Box<dyn Error>> {
+        let fields = fields(record)?;
+        for name in fields.keys() {
+            if !self.columns.contains(name) {
+                self.connection
+                    .execute(&format!("ALTER TABLE records ADD COLUMN \"{name}\""), [])?;
+                self.columns.insert(name.clone());
+            }
+        }
+        let names: Vec<String> = fields.keys().map(|name| format!("\"{name}\"")).collect();
+        let placeholders: Vec<String> = (2..=fields.len() + 1).map(|n| format!("?{n}")).collect();
+        let values = fields.into_iter().map(|(_, value)| sql_value(value));
+        self.connection.execute(
+            &format!(
+                "INSERT INTO records (run_id, {}) VALUES (?1, {})",
+                names.join(", "),
+                placeholders.join(", ")
+            ),
+            rusqlite::params_from_iter(
+                std::iter::once(rusqlite::types::Value::Integer(self.run_id)).chain(values),
+            ),
+        )?;
+        Ok(())
+    }
+}
+
+/// Converts a field of a record to the closest SQLite type. SQLite's integers are signed, so
+/// integers too large for them, such as seeds and checksums, are stored exactly as text
+fn sql_value(value: serde_json::Value) -> rusqlite::types::Value {
+    use rusqlite::types::Value;
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(value) => Value::Integer(value as i64),
+        serde_json::Value::Number(number) => {
+            if let Some(integer) = number.as_i64() {
+                Value::Integer(integer)
+            } else if number.is_u64() {
+                Value::Text(number.to_string())
+            } else {
+                Value::Real(number.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        serde_json::Value::String(text) => Value::Text(text),
+        other => Value::Text(other.to_string()),
+    }
+}
+
+/// Destination for the records produced by a benchmark run
+pub struct Output {
+    path: Option<PathBuf>,
+    writer: Option<Writer>,
+    /// The machine every record is noted as measured on
+    system: System,
+    arguments: String,
+}
+
+impl Output {
+    /// Creates the output in the format suggested by its extension
+    pub fn create(path: Option<PathBuf>, append: bool) -> Result<Self, Box<dyn Error>> {
+        Output::create_as(path, None, append)
+    }
+
+    /// Creates the output in `format`, or the one suggested by its extension, appending to the
+    /// file if it already exists and `append` is set
+    pub fn create_as(
+        path: Option<PathBuf>,
+        format: Option<Format>,
+        append: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        // Results go to stdout when asked for with `-`, or when there's no file for them and
+        // stdout is piped somewhere rather than shown
+        let to_stdout = match &path {
+            Some(path) => path.as_os_str() == "-",
+            None => !io::stdout().is_terminal(),
+        };
+        let path = path.filter(|_| !to_stdout);
+        if path.is_none() && !to_stdout {
+            return Ok(Output {
+                path,
+                writer: None,
+                system: System::detect(),
+                arguments: arguments(),
+            });
+        }
+        let format = format
+            .or(path.as_deref().map(Format::of_path))
+            .unwrap_or_default();
+        // Stdout is only ever added to, so appending to it changes nothing
+        let append = append && path.is_some();
+        if append && matches!(format, Format::Json | Format::Parquet | Format::Arrow) {
+            return Err("only csv, json lines, line protocol and sqlite results can be appended to; json, parquet and arrow files are written whole".into());
+        }
+        let compression = path
+            .as_deref()
+            .map_or(Compression::None, Compression::of_path);
+        if format == Format::Sqlite && compression != Compression::None {
+            return Err("SQLite databases can't be compressed".into());
+        }
+        // Appending to a compressed file adds another stream after it, which is read after the
+        // ones before
+        let open = || -> Result<Sink, Box<dyn Error>> {
+            let file = match &path {
+                Some(path) if append => OpenOptions::new().create(true).append(true).open(path)?,
+                Some(path) => File::create(path)?,
+                None => redirect_stdout()?,
+            };
+            Ok(Sink::new(file, compression)?)
+        };
+        let writer = match format {
+            Format::Csv => {
+                let continuing = append
+                    && path
+                        .as_deref()
+                        .is_some_and(|path| fs::metadata(path).is_ok_and(|file| file.len() > 0));
+                let file = open()?;
+                // Records appended to earlier ones go under their header, which has to name the
+                // same columns
+                if let (true, Some(path)) = (continuing, &path) {
+                    let header = fields(&Record::default())?
+                        .keys()
+                        .map(String::as_str)
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    let existing = decompress(fs::read(path)?)?;
+                    let existing = String::from_utf8_lossy(&existing);
+                    if existing.lines().next().unwrap_or_default() != header {
+                        return Err(format!(
+                            "{} has different columns to the results of this version, so it can't be appended to",
+                            path.display()
+                        )
+                        .into());
+                    }
+                }
+                let writer = csv::WriterBuilder::new()
+                    .has_headers(!continuing)
+                    .from_writer(file);
+                Writer::Csv(Box::new(writer))
+            }
+            Format::Json => {
+                let mut file = BufWriter::new(open()?);
+                write!(file, "{{\"metadata\":{},\"records\":[", metadata())?;
+                Writer::Json(file, false)
+            }
+            Format::Jsonl => Writer::Jsonl(open()?),
+            Format::Influx => Writer::Influx(open()?),
+            Format::Sqlite => match &path {
+                Some(path) => Writer::Sqlite(Database::open(path)?),
+                None => return Err("SQLite databases can't be written to stdout".into()),
+            },
+            Format::Parquet | Format::Arrow => Writer::Columnar(format, open()?, Vec::new()),
+        };
+        Ok(Output {
+            path,
+            writer: Some(writer),
+            system: System::detect(),
+            arguments: arguments(),
+        })
+    }
+
+    /// Writes a record and flushes it to disk immediately, so that interrupted runs keep
+    /// everything measured so far
+    pub fn write(&mut self, record: &Record) -> Result<(), Box<dyn Error>> {
+        let record = &Record {
+            schema_version: Some(SCHEMA_VERSION),
+            arguments: Some(self.arguments.clone()),
+            ..self.system.describe(record)
+        };
+        match &mut self.writer {
+            None => {}
+            Some(Writer::Csv(writer)) => {
+                writer.serialize(record)?;
+                writer.flush()?;
+            }
+            Some(Writer::Json(file, written)) => {
+                if *written {
+                    file.write_all(b",")?;
+                }
+                file.write_all(b"\n")?;
+                serde_json::to_writer(&mut *file, record)?;
+                file.flush()?;
+                *written = true;
+            }
+            Some(Writer::Jsonl(file)) => {
+                // Written all at once, so that a run dying midway never leaves half a line
+                let mut line = serde_json::to_vec(record)?;
+                line.push(b'\n');
+                file.write_all(&line)?;
+                file.flush()?;
+            }
+            Some(Writer::Influx(file)) => {
+                let mut line = influx::line(record)?;
+                line.push('\n');
+                file.write_all(line.as_bytes())?;
+                file.flush()?;
+            }
+            Some(Writer::Sqlite(database)) => database.insert(record)?,
+            Some(Writer::Columnar(_, _, rows)) => rows.push(fields(record)?),
+        }
+        Ok(())
+    }
+
+    /// Completes the results file, closing json's array and object, or writing out the columnar
+    /// formats
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        match self.writer {
+            Some(Writer::Csv(writer)) => writer
+                .into_inner()
+                .map_err(|err| err.into_error())?
+                .finish()?,
+            Some(Writer::Json(mut file, _)) => {
+                file.write_all(b"\n]}\n")?;
+                file.into_inner()
+                    .map_err(io::IntoInnerError::into_error)?
+                    .finish()?;
+            }
+            Some(Writer::Jsonl(file) | Writer::Influx(file)) => file.finish()?,
+            Some(Writer::Columnar(format, file, rows)) => {
+                let batch = record_batch(&rows)?;
+                let file = if format == Format::Parquet {
+                    let mut writer =
+                        parquet::arrow::ArrowWriter::try_new(file, batch.schema(), None)?;
+                    writer.write(&batch)?;
+                    // Writes the footer on the way
+                    writer.into_inner()?
+                } else {
+                    let mut writer = arrow_ipc::writer::FileWriter::try_new(file, &batch.schema())?;
+                    writer.write(&batch)?;
+                    writer.into_inner()?
+                };
+                file.finish()?;
+            }
+            Some(Writer::Sqlite(_)) | None => {}
+        }
+        if let Some(path) = &self.path {
+            println!("Saved results to {}", path.to_string_lossy());
+        }
+        Ok(())
+    }
+}
+
+/// Moves stdout over to stderr, so that everything printed along the way stays out of the
+/// results, and returns the original stdout to write them to
+#[cfg(unix)]
+fn redirect_stdout() -> Result<File, Box<dyn Error>> {
+    use std::os::fd::FromRawFd;
+
+    io::stdout().flush()?;
+    // SAFETY: dup and dup2 only rearrange this process's file descriptors, and the duplicate of
+    // stdout is owned by the returned file alone
+    unsafe {
+        let results = libc::dup(libc::STDOUT_FILENO);
+        if results < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(File::from_raw_fd(results))
+    }
+}
+
+#[cfg(not(unix))]
+fn redirect_stdout() -> Result<File, Box<dyn Error>> {
+    Err("writing results to stdout is only supported on unix".into())
+}
+
+/// Whether an error, or any error it was caused by, is a write to a pipe whose reader has gone,
+/// as when results piped into `head` have been read as far as it wanted
+pub fn is_broken_pipe(err: &(dyn Error + 'static)) -> bool {
+    let mut err = Some(err);
+    while let Some(cause) = err {
+        if cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|cause| cause.kind() == io::ErrorKind::BrokenPipe)
+        {
+            return true;
+        }
+        err = cause.source();
+    }
+    false
+}
+
+/// This process's command line, with arguments quoted as a shell would need them to run it again
+fn arguments() -> String {
+    let quote = |argument: String| {
+        let plain = !argument.is_empty()
+            && argument
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+        if plain {
+            argument
+        } else {
+            format!("'{}'", argument.replace('\'', "'\\''"))
+        }
+    };
+    std::env::args().map(quote).collect::<Vec<_>>().join(" ")
+}
+
+/// Milliseconds since the unix epoch, used to timestamp records
+pub fn unix_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+}
@@ -0,0 +1,99 @@
+use crate::{
+    compare::{step_key, summary},
+    record::{read_records, Record},
+};
+use std::{collections::HashMap, error::Error, path::Path};
+
+/// Checks each step of a run against the throughput a baseline run measured for it, so that a
+/// run can fail when a change made it slower
+pub struct Baseline {
+    /// Mean steps per second of each step of the baseline
+    expected: HashMap<String, f64>,
+    /// Largest drop in throughput allowed, as a percentage
+    max_regression: f64,
+    checked: usize,
+    /// Each step that regressed too far, described, and by how much
+    regressed: Vec<(String, f64)>,
+    missing: usize,
+}
+
+impl Baseline {
+    /// Reads the results to check against
+    pub fn open(path: &Path, max_regression: f64) -> Result<Self, Box<dyn Error>> {
+        let expected: HashMap<String, f64> = read_records(path)?
+            .iter()
+            .filter(|record| record.thread.is_none())
+            .map(|record| (step_key(record), summary(record).mean))
+            .collect();
+        if expected.is_empty() {
+            return Err(format!("no steps in the baseline {}", path.display()).into());
+        }
+        Ok(Baseline {
+            expected,
+            max_regression,
+            checked: 0,
+            regressed: Vec::new(),
+            missing: 0,
+        })
+    }
+
+    /// Compares a step with the baseline's, printing how much its throughput changed
+    pub fn check(&mut self, record: &Record) {
+        let Some(&expected) = self.expected.get(&step_key(record)) else {
+            println!("Baseline: this step wasn't in the baseline");
+            self.missing += 1;
+            return;
+        };
+        self.checked += 1;
+        let actual = summary(record).mean;
+        let change = (actual / expected - 1.0) * 100.0;
+        if -change > self.max_regression {
+            println!(
+                "Baseline: REGRESSED {change:+.2}% from {expected:.2} steps/sec, beyond the {}% allowed",
+                self.max_regression
+            );
+            let series: Vec<String> = record
+                .series_parameters()
+                .into_iter()
+                .map(|(_, value)| value)
+                .collect();
+            let mut step = format!("{} at step size {}", series.join(", "), record.step_size);
+            if let Some(total_size) = record.total_size {
+                step += &format!(" of {total_size} bytes");
+            }
+            if let Some(threads) = record.threads.filter(|&threads| threads > 1) {
+                step += &format!(" on {threads} threads");
+            }
+            self.regressed.push((step, change));
+        } else {
+            println!("Baseline: {change:+.2}% from {expected:.2} steps/sec");
+        }
+    }
+
+    /// Fails if any step's throughput fell further below the baseline's than allowed
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        if self.missing > 0 {
+            println!(
+                "{} steps weren't in the baseline, so weren't checked against it",
+                self.missing
+            );
+        }
+        if !self.regressed.is_empty() {
+            for (step, change) in &self.regressed {
+                println!("Regressed {step}: {change:+.2}%");
+            }
+            return Err(format!(
+                "{} of {} steps regressed by more than {}% from the baseline",
+                self.regressed.len(),
+                self.checked,
+                self.max_regression
+            )
+            .into());
+        }
+        println!(
+            "No step of the {} checked regressed by more than {}% from the baseline",
+            self.checked, self.max_regression
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+// Draws each report's latency curves into its chart. Hovering reads off every series' latency at
+// the nearest size, dragging across the chart zooms in on the sizes spanned, double clicking
+// zooms back out, and clicking a series in the legend hides or shows it
+(function () {
+  const SVG = "http://www.w3.org/2000/svg";
+  const WIDTH = 960;
+  const HEIGHT = 480;
+  const MARGIN = { left: 70, right: 20, top: 16, bottom: 56 };
+  const COLORS = [
+    "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4",
+    "#f032e6", "#9a6324", "#469990", "#800000", "#808000", "#000075",
+  ];
+
+  const reports = JSON.parse(document.getElementById("report-data").textContent);
+  reports.forEach((report, index) => {
+    const container = document.getElementById("chart-" + index);
+    if (report && container) {
+      chart(container, report);
+    }
+  });
+
+  function element(name, attributes, parent) {
+    const node = document.createElementNS(SVG, name);
+    for (const [key, value] of Object.entries(attributes)) {
+      node.setAttribute(key, value);
+    }
+    parent.appendChild(node);
+    return node;
+  }
+
+  // A size in the largest binary unit it's at least one of, if it's a size in bytes
+  function formatSize(size, bytes) {
+    if (!bytes) {
+      return String(size);
+    }
+    const units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let value = size;
+    let unit = 0;
+    while (value >= 1024 && unit < units.length - 1) {
+      value /= 1024;
+      unit += 1;
+    }
+    return (Number.isInteger(value) ? value : value.toFixed(1)) + " " + units[unit];
+  }
+
+  // Around five round values spanning 0 to `max`
+  function ticks(max) {
+    const rough = max / 5;
+    const magnitude = Math.pow(10, Math.floor(Math.log10(rough)));
+    const step = [1, 2, 5, 10].map((m) => m * magnitude).find((step) => step >= rough);
+    const values = [];
+    for (let value = 0; value <= max + step / 1e6; value += step) {
+      values.push(value);
+    }
+    return values;
+  }
+
+  function chart(container, report) {
+    const sizes = [...new Set(report.series.flatMap((series) => series.points.map(([size]) => size)))]
+      .sort((a, b) => a - b);
+    if (sizes.length === 0) {
+      return;
+    }
+    const full = [Math.log2(sizes[0]), Math.log2(sizes[sizes.length - 1])];
+    let view = full.slice();
+    const hidden = new Set();
+
+    const svg = element("svg", { viewBox: `0 0 ${WIDTH} ${HEIGHT}`, class: "plot" }, container);
+    const tooltip = document.createElement("div");
+    tooltip.className = "tooltip";
+    container.appendChild(tooltip);
+    const legend = document.createElement("div");
+    legend.className = "legend";
+    container.appendChild(legend);
+    report.series.forEach((series, index) => {
+      const item = document.createElement("span");
+      item.className = "item";
+      const swatch = document.createElement("span");
+      swatch.className = "swatch";
+      swatch.style.background = COLORS[index % COLORS.length];
+      item.append(swatch, series.label);
+      item.addEventListener("click", () => {
+        hidden.has(index) ? hidden.delete(index) : hidden.add(index);
+        item.classList.toggle("hidden", hidden.has(index));
+        draw();
+      });
+      legend.appendChild(item);
+    });
+
+    const inView = (size) => Math.log2(size) >= view[0] - 1e-9 && Math.log2(size) <= view[1] + 1e-9;
+    let x = () => 0;
+    let y = () => 0;
+    let overlay = null;
+
+    function draw() {
+      svg.replaceChildren();
+      const visible = report.series
+        .filter((_, index) => !hidden.has(index))
+        .flatMap((series) => series.points.filter(([size]) => inView(size)));
+      const maxLatency = Math.max(...visible.map(([, latency]) => latency), 1e-9) * 1.05;
+      const span = view[1] - view[0] || 1;
+      const left = view[1] > view[0] ? view[0] : view[0] - 0.5;
+      x = (size) => MARGIN.left + ((Math.log2(size) - left) / span) * (WIDTH - MARGIN.left - MARGIN.right);
+      y = (latency) => HEIGHT - MARGIN.bottom - (latency / maxLatency) * (HEIGHT - MARGIN.top - MARGIN.bottom);
+
+      const axes = element("g", { class: "axes" }, svg);
+      for (const value of ticks(maxLatency)) {
+        element("line", { x1: MARGIN.left, x2: WIDTH - MARGIN.right, y1: y(value), y2: y(value), class: "grid" }, axes);
+        element("text", { x: MARGIN.left - 6, y: y(value) + 4, "text-anchor": "end" }, axes).textContent =
+          +value.toPrecision(6);
+      }
+      const shown = sizes.filter(inView);
+      const every = Math.ceil(shown.length / 12);
+      shown.forEach((size, index) => {
+        if (index % every === 0) {
+          element("line", { x1: x(size), x2: x(size), y1: MARGIN.top, y2: HEIGHT - MARGIN.bottom, class: "grid" }, axes);
+          element("text", { x: x(size), y: HEIGHT - MARGIN.bottom + 16, "text-anchor": "middle" }, axes).textContent =
+            formatSize(size, report.bytes);
+        }
+      });
+      element("text", { x: (MARGIN.left + WIDTH - MARGIN.right) / 2, y: HEIGHT - 10, "text-anchor": "middle", class: "label" }, axes)
+        .textContent = report.size_name;
+      element("text", {
+        x: 16, y: (MARGIN.top + HEIGHT - MARGIN.bottom) / 2, "text-anchor": "middle", class: "label",
+        transform: `rotate(-90 16 ${(MARGIN.top + HEIGHT - MARGIN.bottom) / 2})`,
+      }, axes).textContent = "ns/access";
+
+      const clip = "clip-" + container.id;
+      const defs = element("defs", {}, svg);
+      element("rect", {
+        x: MARGIN.left, y: MARGIN.top, width: WIDTH - MARGIN.left - MARGIN.right, height: HEIGHT - MARGIN.top - MARGIN.bottom,
+      }, element("clipPath", { id: clip }, defs));
+      const lines = element("g", { "clip-path": `url(#${clip})` }, svg);
+      report.series.forEach((series, index) => {
+        if (hidden.has(index)) {
+          return;
+        }
+        const color = COLORS[index % COLORS.length];
+        const points = series.points.map(([size, latency]) => `${x(size)},${y(latency)}`).join(" ");
+        element("polyline", { points, stroke: color, class: "line" }, lines);
+        for (const [size, latency] of series.points.filter(([size]) => inView(size))) {
+          element("circle", { cx: x(size), cy: y(latency), r: 2.5, fill: color }, lines);
+        }
+      });
+      overlay = element("g", { class: "overlay" }, svg);
+    }
+
+    // Where the pointer is, in the chart's own coordinates
+    function pointer(event) {
+      const point = new DOMPoint(event.clientX, event.clientY).matrixTransform(svg.getScreenCTM().inverse());
+      return point.x;
+    }
+
+    function nearest(px) {
+      return sizes.filter(inView).reduce((best, size) => (Math.abs(x(size) - px) < Math.abs(x(best) - px) ? size : best));
+    }
+
+    let dragStart = null;
+    svg.addEventListener("mousedown", (event) => {
+      dragStart = pointer(event);
+      event.preventDefault();
+    });
+    svg.addEventListener("mousemove", (event) => {
+      const px = pointer(event);
+      overlay.replaceChildren();
+      if (dragStart !== null) {
+        element("rect", {
+          x: Math.min(dragStart, px), y: MARGIN.top, width: Math.abs(px - dragStart), height: HEIGHT - MARGIN.top - MARGIN.bottom,
+          class: "selection",
+        }, overlay);
+      }
+      const size = nearest(px);
+      element("line", { x1: x(size), x2: x(size), y1: MARGIN.top, y2: HEIGHT - MARGIN.bottom, class: "crosshair" }, overlay);
+      const rows = report.series
+        .map((series, index) => [series, index, series.points.find(([point]) => point === size)])
+        .filter(([, index, point]) => point && !hidden.has(index));
+      tooltip.replaceChildren();
+      const title = document.createElement("div");
+      title.className = "title";
+      title.textContent = `${report.size_name} ${formatSize(size, report.bytes)}` + (report.bytes && size >= 1024 ? ` (${size} bytes)` : "");
+      tooltip.appendChild(title);
+      for (const [series, index, [, latency]] of rows) {
+        element("circle", { cx: x(size), cy: y(latency), r: 4.5, fill: COLORS[index % COLORS.length] }, overlay);
+        const row = document.createElement("div");
+        const swatch = document.createElement("span");
+        swatch.className = "swatch";
+        swatch.style.background = COLORS[index % COLORS.length];
+        row.append(swatch, `${series.label}: ${latency.toFixed(2)} ns/access`);
+        tooltip.appendChild(row);
+      }
+      const bounds = container.getBoundingClientRect();
+      const left = event.clientX - bounds.left + 16;
+      tooltip.style.display = "block";
+      tooltip.style.left = Math.min(left, bounds.width - tooltip.offsetWidth) + "px";
+      tooltip.style.top = event.clientY - bounds.top + 16 + "px";
+    });
+    const hide = () => {
+      overlay.replaceChildren();
+      tooltip.style.display = "none";
+    };
+    svg.addEventListener("mouseleave", () => {
+      dragStart = null;
+      hide();
+    });
+    svg.addEventListener("mouseup", (event) => {
+      const px = pointer(event);
+      if (dragStart !== null && Math.abs(px - dragStart) > 4) {
+        const selected = sizes.filter(inView).filter((size) => x(size) >= Math.min(dragStart, px) && x(size) <= Math.max(dragStart, px));
+        if (selected.length > 0) {
+          view = [Math.log2(selected[0]), Math.log2(selected[selected.length - 1])];
+          draw();
+        }
+      }
+      dragStart = null;
+      hide();
+    });
+    svg.addEventListener("dblclick", () => {
+      view = full.slice();
+      draw();
+    });
+
+    draw();
+  }
+})();
@@ -0,0 +1,457 @@
+use crate::{
+    analyze::{format_bytes, knees, latency_curves},
+    plot::{plot_records, swept_size, PlotOptions},
+    record::{fields, read_records, Record},
+};
+use clap::{Parser, ValueEnum};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    error::Error,
+    fmt::{self, Write as _},
+    fs,
+    path::PathBuf,
+};
+
+/// Styles of an HTML report
+const STYLE: &str = include_str!("report.css");
+/// Draws the interactive charts of an HTML report
+const SCRIPT: &str = include_str!("report.js");
+
+/// Parameters listed in a report when every record of a file shares them, by column and name
+const PARAMETERS: &[(&str, &str)] = &[
+    ("mode", "Mode"),
+    ("arguments", "Command line"),
+    ("total_size", "Total size"),
+    ("iterations", "Iterations"),
+    ("repeats", "Repeats"),
+    ("warmup_iterations", "Warmup iterations"),
+    ("aggregate", "Aggregate"),
+    ("trim", "Trim"),
+    ("pattern", "Pattern"),
+    ("op", "Op"),
+    ("element_size", "Element size"),
+    ("page_size", "Page size"),
+    ("backing", "Backing"),
+    ("init", "Buffer contents"),
+    ("threads", "Threads"),
+    ("timer", "Timer"),
+    ("seed", "Seed"),
+];
+
+/// Formats a report can be written in
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Markdown, with the plots drawn as images beside it
+    Markdown,
+
+    /// A single HTML page holding the data and an interactive chart of each file, which reads
+    /// off exact values under the pointer and zooms in on dragged ranges
+    Html,
+}
+
+#[derive(Parser)]
+pub struct ReportArgs {
+    /// Files containing test data to report on
+    #[clap(required = true)]
+    data_files: Vec<PathBuf>,
+
+    /// File to write the report to, with the plots a Markdown report embeds drawn alongside it
+    /// [default stdout, with the plots drawn alongside each data file]
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Format to write the report in [default html for `.html` and `.htm` files, otherwise
+    /// markdown]
+    #[clap(short, long, value_enum)]
+    format: Option<ReportFormat>,
+
+    /// Smallest bend that counts as a knee, as `analyze --knee-threshold`
+    #[clap(long, default_value_t = 0.1)]
+    knee_threshold: f64,
+
+    /// Leave out the plots, rather than drawing one of each file to embed
+    #[clap(long)]
+    no_plots: bool,
+}
+
+/// What a report says about one data file, whatever format it's written in
+struct Section {
+    name: String,
+    /// The machine the first record was measured on, if the version that measured it recorded
+    /// that
+    system: Option<Vec<(&'static str, String)>>,
+    /// The parameters every record shares
+    parameters: Vec<Parameter>,
+    size_name: &'static str,
+    curves: BTreeMap<String, Vec<(u64, f64)>>,
+    /// Descriptions of the knees in each series' latency curve
+    knees: Vec<(String, Vec<String>)>,
+    /// Path to embed the plot of the file by, in a Markdown report
+    image: Option<PathBuf>,
+}
+
+/// A parameter every record of a file shares
+struct Parameter {
+    column: &'static str,
+    name: &'static str,
+    value: String,
+}
+
+pub fn run_report(args: ReportArgs) -> Result<(), Box<dyn Error>> {
+    if args.knee_threshold <= 0.0 {
+        return Err("--knee-threshold must be greater than 0".into());
+    }
+    let format = args.format.unwrap_or_else(|| {
+        let extension = args.out.as_deref().and_then(|out| out.extension());
+        match extension.and_then(|extension| extension.to_str()) {
+            Some("html" | "htm") => ReportFormat::Html,
+            _ => ReportFormat::Markdown,
+        }
+    });
+    let mut sections = Vec::new();
+    let mut images = HashSet::new();
+    for (index, data_file) in args.data_files.iter().enumerate() {
+        let data = read_records(data_file)?;
+        if data.is_empty() {
+            return Err(format!("No data in {}", data_file.display()).into());
+        }
+        let (size_name, _) = swept_size(&data);
+        let curves = latency_curves(&data);
+        let mut image = None;
+        if format == ReportFormat::Markdown && !args.no_plots {
+            // Keep the plots beside the report, so that the paths it embeds stay valid wherever
+            // the two are moved together
+            let image_path = |path: PathBuf| match args.out.as_deref() {
+                Some(out) => {
+                    let file_name = PathBuf::from(path.file_name().unwrap_or_default());
+                    (out.with_file_name(&file_name), file_name)
+                }
+                None => (path.clone(), path),
+            };
+            let (mut path, mut link) = image_path(data_file.with_extension("png"));
+            // Data files of the same name, from different directories or in different formats,
+            // would otherwise draw over each other's plots
+            if !images.insert(path.clone()) {
+                let stem = data_file.file_stem().unwrap_or_default().to_string_lossy();
+                (path, link) = image_path(data_file.with_file_name(format!("{stem}-{index}.png")));
+                if !images.insert(path.clone()) {
+                    return Err(
+                        format!("more than one plot would be saved as {}", path.display()).into(),
+                    );
+                }
+            }
+            plot_records(&data, &path, &PlotOptions::default())?;
+            image = Some(link);
+        }
+        sections.push(Section {
+            name: data_file.to_string_lossy().into_owned(),
+            system: system(&data[0]),
+            parameters: parameters(&data)?,
+            size_name,
+            knees: knees_of(&curves, size_name, args.knee_threshold),
+            curves,
+            image,
+        });
+    }
+
+    let report = match format {
+        ReportFormat::Markdown => markdown(&sections)?,
+        ReportFormat::Html => html(&sections, !args.no_plots)?,
+    };
+    match &args.out {
+        Some(out) => {
+            fs::write(out, report)?;
+            println!("Saved report to {}", out.to_string_lossy());
+        }
+        None => print!("{report}"),
+    }
+
+    Ok(())
+}
+
+/// The machine a record was measured on, as rows of a table
+fn system(record: &Record) -> Option<Vec<(&'static str, String)>> {
+    let os = record.os.as_ref()?;
+    let cpu = match (&record.cpu_model, record.cpu_count) {
+        (Some(model), Some(count)) => format!("{model}, {count} logical cpus"),
+        (Some(model), None) => model.clone(),
+        (None, Some(count)) => format!("{count} logical cpus"),
+        (None, None) => "unknown".to_string(),
+    };
+    let caches: Vec<String> = [
+        ("L1d", record.l1d_cache_bytes),
+        ("L2", record.l2_cache_bytes),
+        ("L3", record.l3_cache_bytes),
+    ]
+    .into_iter()
+    .filter_map(|(level, size)| Some(format!("{level} {}", format_bytes(size? as f64))))
+    .collect();
+    let rows = [
+        ("CPU", cpu),
+        ("Caches", caches.join(", ")),
+        (
+            "Memory",
+            record
+                .memory_bytes
+                .map(|size| format_bytes(size as f64))
+                .unwrap_or_default(),
+        ),
+        ("OS", os.clone()),
+        ("Host", record.hostname.clone().unwrap_or_default()),
+        ("Version", record.version.clone().unwrap_or_default()),
+    ];
+    Some(
+        rows.into_iter()
+            .filter(|(_, value)| !value.is_empty())
+            .collect(),
+    )
+}
+
+/// The parameters every record shares; those that differ tell the series apart instead
+fn parameters(data: &[Record]) -> Result<Vec<Parameter>, Box<dyn Error>> {
+    let records: Vec<_> = data.iter().map(fields).try_collect()?;
+    let mut parameters = Vec::new();
+    for &(column, name) in PARAMETERS {
+        let mut values = records.iter().map(|fields| fields.get(column));
+        let Some(Some(first)) = values.next() else {
+            continue;
+        };
+        if first.is_null() || values.any(|value| value != Some(first)) {
+            continue;
+        }
+        let value = match first {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        };
+        parameters.push(Parameter {
+            column,
+            name,
+            value,
+        });
+    }
+    Ok(parameters)
+}
+
+/// Where each series' latency curve bends upward, as `analyze` finds them
+fn knees_of(
+    curves: &BTreeMap<String, Vec<(u64, f64)>>,
+    size_name: &str,
+    threshold: f64,
+) -> Vec<(String, Vec<String>)> {
+    curves
+        .iter()
+        .map(|(label, points)| {
+            let knees = knees(points, threshold)
+                .into_iter()
+                .map(|knee| knee.describe(points, size_name))
+                .collect();
+            (label.clone(), knees)
+        })
+        .collect()
+}
+
+/// Every size any series was measured at, and each series' latency at it if it was
+fn latency_rows(curves: &BTreeMap<String, Vec<(u64, f64)>>) -> Vec<(u64, Vec<Option<f64>>)> {
+    let sizes: BTreeSet<u64> = curves
+        .values()
+        .flat_map(|points| points.iter().map(|&(size, _)| size))
+        .collect();
+    sizes
+        .into_iter()
+        .map(|size| {
+            let latencies = curves
+                .values()
+                .map(|points| {
+                    points
+                        .iter()
+                        .find(|&&(point_size, _)| point_size == size)
+                        .map(|&(_, latency)| latency)
+                })
+                .collect();
+            (size, latencies)
+        })
+        .collect()
+}
+
+fn markdown(sections: &[Section]) -> Result<String, fmt::Error> {
+    let mut report = String::from("# Cache locality results\n");
+    for section in sections {
+        writeln!(report, "\n## {}", section.name)?;
+
+        writeln!(report, "\n### System\n")?;
+        match &section.system {
+            Some(rows) => {
+                writeln!(report, "| | |\n|---|---|")?;
+                for (name, value) in rows {
+                    writeln!(report, "| {name} | {} |", cell(value))?;
+                }
+            }
+            None => writeln!(
+                report,
+                "Not recorded by the version that measured these results"
+            )?,
+        }
+
+        writeln!(report, "\n### Parameters\n")?;
+        writeln!(report, "| Parameter | Value |\n|---|---|")?;
+        for Parameter {
+            column,
+            name,
+            value,
+        } in &section.parameters
+        {
+            let value = match *column {
+                "arguments" => format!("`{value}`"),
+                _ => value.clone(),
+            };
+            writeln!(report, "| {name} | {} |", cell(&value))?;
+        }
+
+        writeln!(report, "\n### Knees\n")?;
+        for (label, knees) in &section.knees {
+            if knees.is_empty() {
+                writeln!(report, "- **{label}**: no knees")?;
+                continue;
+            }
+            writeln!(report, "- **{label}**:")?;
+            for knee in knees {
+                writeln!(report, "  - {knee}")?;
+            }
+        }
+
+        writeln!(report, "\n### Latency in ns/access\n")?;
+        let labels: Vec<String> = section.curves.keys().map(|label| cell(label)).collect();
+        writeln!(report, "| {} | {} |", section.size_name, labels.join(" | "))?;
+        writeln!(report, "|---:|{}", "---:|".repeat(section.curves.len()))?;
+        for (size, latencies) in latency_rows(&section.curves) {
+            let latencies: Vec<String> = latencies
+                .into_iter()
+                .map(|latency| latency.map_or(String::new(), |latency| format!("{latency:.2}")))
+                .collect();
+            writeln!(report, "| {size} | {} |", latencies.join(" | "))?;
+        }
+
+        if let Some(image) = &section.image {
+            writeln!(
+                report,
+                "\n![Plot of {}]({})",
+                section.name,
+                image.to_string_lossy()
+            )?;
+        }
+    }
+    Ok(report)
+}
+
+/// A page needing nothing beyond itself, with the data its charts draw embedded as json
+fn html(sections: &[Section], charts: bool) -> Result<String, Box<dyn Error>> {
+    let mut report = String::from(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Cache locality results</title>\n",
+    );
+    writeln!(report, "<style>\n{}</style>\n</head>\n<body>", STYLE)?;
+    writeln!(report, "<h1>Cache locality results</h1>")?;
+    let mut data = Vec::new();
+    for (index, section) in sections.iter().enumerate() {
+        writeln!(report, "<section>\n<h2>{}</h2>", escape(&section.name))?;
+
+        writeln!(report, "<h3>System</h3>")?;
+        match &section.system {
+            Some(rows) => {
+                writeln!(report, "<table>")?;
+                for (name, value) in rows {
+                    writeln!(report, "<tr><th>{name}</th><td>{}</td></tr>", escape(value))?;
+                }
+                writeln!(report, "</table>")?;
+            }
+            None => writeln!(
+                report,
+                "<p>Not recorded by the version that measured these results</p>"
+            )?,
+        }
+
+        writeln!(report, "<h3>Parameters</h3>\n<table>")?;
+        for Parameter {
+            column,
+            name,
+            value,
+        } in &section.parameters
+        {
+            let value = match *column {
+                "arguments" => format!("<code>{}</code>", escape(value)),
+                _ => escape(value),
+            };
+            writeln!(report, "<tr><th>{name}</th><td>{value}</td></tr>")?;
+        }
+        writeln!(report, "</table>")?;
+
+        writeln!(report, "<h3>Knees</h3>\n<ul>")?;
+        for (label, knees) in &section.knees {
+            if knees.is_empty() {
+                writeln!(report, "<li><b>{}</b>: no knees</li>", escape(label))?;
+                continue;
+            }
+            writeln!(report, "<li><b>{}</b>:<ul>", escape(label))?;
+            for knee in knees {
+                writeln!(report, "<li>{}</li>", escape(knee))?;
+            }
+            writeln!(report, "</ul></li>")?;
+        }
+        writeln!(report, "</ul>")?;
+
+        writeln!(report, "<h3>Latency in ns/access</h3>")?;
+        if charts {
+            writeln!(report, "<div class=\"chart\" id=\"chart-{index}\"></div>")?;
+        }
+        writeln!(
+            report,
+            "<table class=\"latency\">\n<tr><th>{}</th>",
+            section.size_name
+        )?;
+        for label in section.curves.keys() {
+            write!(report, "<th>{}</th>", escape(label))?;
+        }
+        writeln!(report, "</tr>")?;
+        for (size, latencies) in latency_rows(&section.curves) {
+            write!(report, "<tr><td>{size}</td>")?;
+            for latency in latencies {
+                let latency = latency.map_or(String::new(), |latency| format!("{latency:.2}"));
+                write!(report, "<td>{latency}</td>")?;
+            }
+            writeln!(report, "</tr>")?;
+        }
+        writeln!(report, "</table>\n</section>")?;
+
+        data.push(charts.then(|| {
+            json!({
+                "size_name": section.size_name,
+                "bytes": section.size_name.ends_with("size"),
+                "series": section.curves.iter().map(|(label, points)| json!({
+                    "label": label,
+                    "points": points,
+                })).collect::<Vec<_>>(),
+            })
+        }));
+    }
+    // Json can't end the script it sits in unless it holds `</`, which it can say as `<\/`
+    let data = serde_json::to_string(&data)?.replace("</", "<\\/");
+    writeln!(
+        report,
+        "<script type=\"application/json\" id=\"report-data\">{data}</script>"
+    )?;
+    writeln!(report, "<script>\n{}</script>\n</body>\n</html>", SCRIPT)?;
+    Ok(report)
+}
+
+/// Text escaped to sit in html
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Text escaped to sit in a table cell
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
@@ -0,0 +1,48 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Time between samples
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Periodically calls a function from a background thread, collecting the values it returns,
+/// until it's finished
+pub struct Sampler {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Vec<f64>>,
+}
+
+impl Sampler {
+    /// Starts sampling; `sample` returns `None` for samples it couldn't take
+    pub fn start(mut sample: impl FnMut() -> Option<f64> + Send + 'static) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            move || {
+                let mut samples = Vec::new();
+                // Always sample once more after being stopped, so even the shortest step gets a
+                // sample from its end as well as its start
+                loop {
+                    let stopped = stop.load(Ordering::Relaxed);
+                    samples.extend(sample());
+                    if stopped {
+                        return samples;
+                    }
+                    thread::sleep(SAMPLE_INTERVAL);
+                }
+            }
+        });
+        Sampler { stop, thread }
+    }
+
+    /// Stops sampling, returning every sample taken
+    pub fn finish(self) -> Vec<f64> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread.join().unwrap_or_default()
+    }
+}
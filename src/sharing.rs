@@ -0,0 +1,141 @@
+use crate::record::{unix_millis, Mode, Output, Record};
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Barrier,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How the per-thread counters are laid out in memory
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// Counters packed next to each other, so up to 8 threads share each cache line
+    Packed,
+
+    /// Each counter padded out to its own cache line
+    Padded,
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+/// A counter aligned to, and filling, a whole 64 byte cache line
+#[repr(align(64))]
+#[derive(Default)]
+struct Padded(AtomicU64);
+
+#[derive(Parser)]
+pub struct SharingArgs {
+    /// Maximum number of threads, doubling from 1 [default number of available cpus]
+    #[clap(short, long)]
+    max_threads: Option<usize>,
+
+    /// Number of increments each thread performs per test
+    #[clap(short, long, default_value_t = 100_000_000)]
+    iterations: usize,
+
+    /// Counter layouts to test at each thread count
+    #[clap(short, long, value_delimiter = ',', default_value = "packed,padded")]
+    layout: Vec<Layout>,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_sharing(args: SharingArgs) -> Result<(), Box<dyn Error>> {
+    let max_threads = match args.max_threads {
+        Some(max_threads) => max_threads,
+        None => thread::available_parallelism()?.get(),
+    };
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    let mut threads = 1;
+    while threads <= max_threads {
+        for &layout in &args.layout {
+            println!("Testing {threads} threads with {layout} counters");
+            let start_time = unix_millis();
+            let (sum, total_duration) = match layout {
+                Layout::Packed => {
+                    let counters: Vec<AtomicU64> =
+                        (0..threads).map(|_| AtomicU64::default()).collect();
+                    increment(&counters, args.iterations, |counter| counter)
+                }
+                Layout::Padded => {
+                    let counters: Vec<Padded> = (0..threads).map(|_| Padded::default()).collect();
+                    increment(&counters, args.iterations, |counter| &counter.0)
+                }
+            };
+            let total_duration_float = total_duration.as_secs_f32();
+            let steps_per_second = ((threads * args.iterations) as f32) / total_duration_float;
+            println!(
+                "Completed testing: took {total_duration_float:.3} secs, with an average increment rate of {steps_per_second:.2} steps/sec. sum: {sum}"
+            );
+            out.write(&Record {
+                start_time,
+                step_size: threads as u64,
+                total_duration_millis: total_duration.as_millis(),
+                steps_per_second,
+                mode: Mode::FalseSharing,
+                layout: Some(layout),
+                ..Record::default()
+            })?;
+        }
+        threads <<= 1;
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Spawns one thread per counter, each incrementing its own counter `iterations` times. Returns
+/// the sum of all counters afterwards along with the time taken from when all threads started.
+fn increment<T: Sync>(
+    counters: &[T],
+    iterations: usize,
+    counter: impl Fn(&T) -> &AtomicU64 + Sync,
+) -> (u64, Duration) {
+    let barrier = Barrier::new(counters.len() + 1);
+    let start_instant = thread::scope(|scope| {
+        for slot in counters {
+            let counter = counter(slot);
+            let barrier = &barrier;
+            scope.spawn(move || {
+                barrier.wait();
+                for _ in 0..iterations {
+                    // A relaxed load and store compiles to plain moves, so the only cost beyond a
+                    // normal increment is keeping the cache line coherent between cores
+                    counter.store(counter.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+                }
+            });
+        }
+        // Start the clock before releasing the threads; once released they may well finish
+        // before this thread is scheduled again
+        let start_instant = Instant::now();
+        barrier.wait();
+        start_instant
+    });
+    let total_duration = Instant::now().duration_since(start_instant);
+    let sum = counters
+        .iter()
+        .map(|slot| counter(slot).load(Ordering::Relaxed))
+        .sum();
+    (sum, total_duration)
+}
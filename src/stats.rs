@@ -0,0 +1,199 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How repeated measurements are combined into the single value recorded for a step
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Aggregate {
+    /// The arithmetic mean of the kept measurements
+    #[default]
+    Mean,
+
+    /// The median of the kept measurements, which a few slow outliers can't drag down
+    Median,
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+/// Summary of repeated measurements of the same quantity
+#[derive(Clone, Copy)]
+pub struct Stats {
+    pub mean: f64,
+    pub median: f64,
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Number of samples left after trimming
+    pub kept: usize,
+}
+
+impl Stats {
+    /// Summarizes a non-empty set of samples, after discarding the `trim` fraction of them from
+    /// each end. At least one sample is always kept. The standard deviation is the sample
+    /// standard deviation, and is zero for a single sample
+    pub fn of(samples: &[f64], trim: f64) -> Stats {
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let discard = ((sorted.len() as f64 * trim) as usize).min((sorted.len() - 1) / 2);
+        let kept = &sorted[discard..sorted.len() - discard];
+
+        let n = kept.len() as f64;
+        let mean = kept.iter().sum::<f64>() / n;
+        let variance = if kept.len() > 1 {
+            kept.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        let middle = kept.len() / 2;
+        let median = if kept.len().is_multiple_of(2) {
+            (kept[middle - 1] + kept[middle]) / 2.0
+        } else {
+            kept[middle]
+        };
+        Stats {
+            mean,
+            median,
+            stddev: variance.sqrt(),
+            min: kept[0],
+            max: kept[kept.len() - 1],
+            kept: kept.len(),
+        }
+    }
+
+    /// The value to record, as chosen by `aggregate`
+    pub fn aggregate(&self, aggregate: Aggregate) -> f64 {
+        match aggregate {
+            Aggregate::Mean => self.mean,
+            Aggregate::Median => self.median,
+        }
+    }
+}
+
+/// The mean, sample standard deviation and size of a set of samples, which is all a t-test needs
+#[derive(Clone, Copy)]
+pub struct Summary {
+    pub mean: f64,
+    pub stddev: f64,
+    pub count: usize,
+}
+
+impl Summary {
+    /// Half the width of the confidence interval of the mean at `confidence`, such as 0.95, by
+    /// Student's t-distribution. Needs at least two samples
+    pub fn confidence_interval(&self, confidence: f64) -> Option<f64> {
+        if self.count < 2 {
+            return None;
+        }
+        let t = t_critical((self.count - 1) as f64, confidence);
+        Some(t * self.stddev / (self.count as f64).sqrt())
+    }
+}
+
+/// Two-sided critical value of Student's t-distribution with `df` degrees of freedom at
+/// `confidence`, such as about 2.776 for 4 degrees of freedom at 0.95, found by bisecting the
+/// probability of the tails beyond it
+fn t_critical(df: f64, confidence: f64) -> f64 {
+    let alpha = 1.0 - confidence;
+    let tails = |t: f64| incomplete_beta(df / 2.0, 0.5, df / (df + t * t));
+    let (mut low, mut high) = (0.0, 1.0);
+    while tails(high) > alpha && high < 1e9 {
+        high *= 2.0;
+    }
+    for _ in 0..100 {
+        let middle = (low + high) / 2.0;
+        if tails(middle) > alpha {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Two-sided p-value of Welch's t-test that two sets of samples have the same mean, without
+/// assuming they have the same variance. Needs at least two samples in each set
+pub fn welch_p_value(a: Summary, b: Summary) -> Option<f64> {
+    if a.count < 2 || b.count < 2 {
+        return None;
+    }
+    let (va, vb) = (
+        a.stddev.powi(2) / a.count as f64,
+        b.stddev.powi(2) / b.count as f64,
+    );
+    let variance = va + vb;
+    if variance == 0.0 {
+        return Some(if a.mean == b.mean { 1.0 } else { 0.0 });
+    }
+    let t = (a.mean - b.mean) / variance.sqrt();
+    let df =
+        variance.powi(2) / (va.powi(2) / (a.count - 1) as f64 + vb.powi(2) / (b.count - 1) as f64);
+    Some(incomplete_beta(df / 2.0, 0.5, df / (df + t * t)))
+}
+
+/// The regularized incomplete beta function I_x(a, b), by its continued fraction, which
+/// converges quickly on whichever side of the mean x falls
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Evaluates the continued fraction for the incomplete beta function with Lentz's method
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let clamp = |v: f64| if v.abs() < TINY { TINY } else { v };
+    let mut c = 1.0;
+    let mut d = 1.0 / clamp(1.0 - (a + b) * x / (a + 1.0));
+    let mut fraction = d;
+    for m in 1..300 {
+        let m = m as f64;
+        let even = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 / clamp(1.0 + even * d);
+        c = clamp(1.0 + even / c);
+        fraction *= d * c;
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 / clamp(1.0 + odd * d);
+        c = clamp(1.0 + odd / c);
+        let delta = d * c;
+        fraction *= delta;
+        if (delta - 1.0).abs() < 1e-12 {
+            break;
+        }
+    }
+    fraction
+}
+
+/// Natural log of the gamma function for positive arguments, by the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .fold(1.000_000_000_190_015, |sum, (i, c)| {
+            sum + c / (x + 1.0 + i as f64)
+        });
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
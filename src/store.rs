@@ -0,0 +1,131 @@
+use crate::record::{unix_millis, Mode, Output, Record};
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt, hint::black_box, mem::size_of, path::PathBuf, time::Instant};
+
+/// Kind of store instruction used to fill the buffer
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Store {
+    /// Ordinary stores, which pull each line into the cache before writing it
+    Regular,
+
+    /// Non-temporal streaming stores, which bypass the cache and write straight to memory.
+    /// Only available on x86_64
+    NonTemporal,
+}
+
+impl fmt::Display for Store {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+#[derive(Parser)]
+pub struct StoreArgs {
+    /// Initial buffer size in bytes
+    #[clap(short = 'd', long, default_value_t = 4096)]
+    min_size: usize,
+
+    /// Maximum buffer size in bytes
+    #[clap(short, long, default_value_t = 1024 * 1024 * 1024)]
+    max_size: usize,
+
+    /// Minimum number of 8 byte stores to perform per store kind and size. Small buffers are
+    /// filled repeatedly until at least this many stores have been made
+    #[clap(short, long, default_value_t = 1_000_000_000)]
+    iterations: usize,
+
+    /// Kinds of stores to test at each size
+    #[clap(
+        short,
+        long,
+        value_delimiter = ',',
+        default_value = "regular,non-temporal"
+    )]
+    store: Vec<Store>,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_store(args: StoreArgs) -> Result<(), Box<dyn Error>> {
+    if args.min_size < size_of::<u64>() || args.max_size < args.min_size {
+        return Err("--min-size must be at least 8 bytes and no larger than --max-size".into());
+    }
+    if cfg!(not(target_arch = "x86_64")) && args.store.contains(&Store::NonTemporal) {
+        return Err("non-temporal stores are only supported on x86_64".into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    println!("Allocating buffer");
+    let mut buffer: Vec<u64> = vec![0; args.max_size / size_of::<u64>()];
+
+    let mut size = args.min_size;
+    while size <= args.max_size {
+        let elements = size / size_of::<u64>();
+        let passes = args.iterations.div_ceil(elements);
+        for &store in &args.store {
+            println!("Testing {store} stores over {size} bytes");
+            let start_time = unix_millis();
+            let start_instant = Instant::now();
+            for pass in 0..passes {
+                // Hide the buffer from the optimizer so earlier passes aren't discarded as dead
+                // stores
+                let buffer = black_box(&mut buffer[..elements]);
+                fill(buffer, pass as u64, store);
+            }
+            let total_duration = Instant::now().duration_since(start_instant);
+            let total_duration_float = total_duration.as_secs_f32();
+            let stores = passes * elements;
+            let steps_per_second = (stores as f32) / total_duration_float;
+            let gigabytes_per_second =
+                (stores * size_of::<u64>()) as f32 / total_duration_float / 1e9;
+            println!(
+                "Completed testing: took {total_duration_float:.3} secs, with an average store rate of {steps_per_second:.2} steps/sec ({gigabytes_per_second:.2} GB/s)"
+            );
+            out.write(&Record {
+                start_time,
+                step_size: size as u64,
+                total_duration_millis: total_duration.as_millis(),
+                steps_per_second,
+                mode: Mode::Store,
+                store: Some(store),
+                gigabytes_per_second: Some(gigabytes_per_second),
+                ..Record::default()
+            })?;
+        }
+        size <<= 1;
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Overwrites every element of `buffer` with `value` using the given kind of store
+fn fill(buffer: &mut [u64], value: u64, store: Store) {
+    match store {
+        Store::Regular => buffer.fill(value),
+        #[cfg(target_arch = "x86_64")]
+        Store::NonTemporal => {
+            use std::arch::x86_64::{_mm_sfence, _mm_stream_si64};
+            for element in buffer.iter_mut() {
+                // SAFETY: the pointer comes from a valid, aligned mutable reference
+                unsafe { _mm_stream_si64(element as *mut u64 as *mut i64, value as i64) };
+            }
+            // Streaming stores are weakly ordered; fence so they've all completed before the
+            // clock is read
+            // SAFETY: sse is always available on x86_64
+            unsafe { _mm_sfence() };
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        Store::NonTemporal => unreachable!("non-temporal stores are rejected up front"),
+    }
+}
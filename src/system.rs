@@ -0,0 +1,160 @@
+use crate::record::Record;
+use serde::Serialize;
+use std::fs;
+
+/// The machine results were measured on, recorded with every record so that results files can
+/// still be told apart long after anyone remembers where they came from
+#[derive(Clone, Serialize)]
+pub struct System {
+    pub cpu_model: Option<String>,
+    /// Logical cpus online
+    pub cpu_count: Option<usize>,
+    pub l1d_cache_bytes: Option<u64>,
+    pub l2_cache_bytes: Option<u64>,
+    pub l3_cache_bytes: Option<u64>,
+    pub memory_bytes: Option<u64>,
+    pub os: String,
+    pub hostname: Option<String>,
+    /// Version of this crate that measured the results
+    pub version: String,
+}
+
+impl System {
+    /// Describes the machine this is running on, leaving out whatever it doesn't expose
+    pub fn detect() -> Self {
+        let (l1d_cache_bytes, l2_cache_bytes, l3_cache_bytes) = cache_sizes();
+        System {
+            cpu_model: cpu_model(),
+            cpu_count: cpu_count(),
+            l1d_cache_bytes,
+            l2_cache_bytes,
+            l3_cache_bytes,
+            memory_bytes: memory_bytes(),
+            os: os(),
+            hostname: hostname(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// A copy of `record` noting that it was measured on this machine
+    pub fn describe(&self, record: &Record) -> Record {
+        Record {
+            cpu_model: self.cpu_model.clone(),
+            cpu_count: self.cpu_count,
+            l1d_cache_bytes: self.l1d_cache_bytes,
+            l2_cache_bytes: self.l2_cache_bytes,
+            l3_cache_bytes: self.l3_cache_bytes,
+            memory_bytes: self.memory_bytes,
+            os: Some(self.os.clone()),
+            hostname: self.hostname.clone(),
+            version: Some(self.version.clone()),
+            ..record.clone()
+        }
+    }
+}
+
+/// The cpu's name from /proc/cpuinfo, which arm cpus don't always give
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        ["model name", "Model", "cpu model"]
+            .contains(&key.trim())
+            .then(|| value.trim().to_string())
+    })
+}
+
+#[cfg(unix)]
+fn cpu_count() -> Option<usize> {
+    // SAFETY: sysconf has no preconditions
+    let count = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+    usize::try_from(count).ok().filter(|&count| count > 0)
+}
+
+#[cfg(not(unix))]
+fn cpu_count() -> Option<usize> {
+    std::thread::available_parallelism().ok().map(usize::from)
+}
+
+/// Sizes of the first core's level 1 data, level 2 and level 3 caches, from linux's description
+/// of them in /sys
+fn cache_sizes() -> (Option<u64>, Option<u64>, Option<u64>) {
+    let mut sizes = (None, None, None);
+    let caches = fs::read_dir("/sys/devices/system/cpu/cpu0/cache")
+        .into_iter()
+        .flatten()
+        .flatten();
+    for cache in caches {
+        let read = |name: &str| fs::read_to_string(cache.path().join(name)).ok();
+        let (Some(level), Some(kind), Some(size)) = (read("level"), read("type"), read("size"))
+        else {
+            continue;
+        };
+        // Sizes are given in kibibytes, such as "48K"
+        let Some(size) = size
+            .trim()
+            .strip_suffix('K')
+            .and_then(|kibibytes| kibibytes.parse::<u64>().ok())
+            .map(|kibibytes| kibibytes * 1024)
+        else {
+            continue;
+        };
+        match (level.trim(), kind.trim()) {
+            ("1", "Data" | "Unified") => sizes.0 = Some(size),
+            ("2", _) => sizes.1 = Some(size),
+            ("3", _) => sizes.2 = Some(size),
+            _ => {}
+        }
+    }
+    sizes
+}
+
+/// Total memory from /proc/meminfo
+fn memory_bytes() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let total = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))?;
+    let kibibytes: u64 = total.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kibibytes * 1024)
+}
+
+/// The distribution and kernel, such as "Debian GNU/Linux 12 (bookworm), Linux 6.1.0", or just
+/// the name of the os where they aren't known
+fn os() -> String {
+    let distribution = fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|release| {
+            release.lines().find_map(|line| {
+                let name = line.strip_prefix("PRETTY_NAME=")?;
+                Some(name.trim_matches('"').to_string())
+            })
+        });
+    let kernel = ["ostype", "osrelease"]
+        .map(|name| fs::read_to_string(format!("/proc/sys/kernel/{name}")).ok());
+    let kernel = match kernel {
+        [Some(kind), Some(release)] => format!("{} {}", kind.trim(), release.trim()),
+        _ => std::env::consts::OS.to_string(),
+    };
+    match distribution {
+        Some(distribution) => format!("{distribution}, {kernel}"),
+        None => kernel,
+    }
+}
+
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let mut name = [0u8; 256];
+    // SAFETY: gethostname writes at most the given length into the buffer
+    let result = unsafe { libc::gethostname(name.as_mut_ptr().cast(), name.len()) };
+    if result != 0 {
+        return None;
+    }
+    let length = name.iter().position(|&byte| byte == 0)?;
+    Some(String::from_utf8_lossy(&name[..length]).into_owned())
+}
+
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
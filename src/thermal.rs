@@ -0,0 +1,150 @@
+use crate::sampler::Sampler;
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Where linux exposes the cpu's temperature sensors and thermal throttling counters
+#[derive(Clone)]
+pub struct Thermal {
+    /// Package temperature in millidegrees Celsius
+    temperature: Option<PathBuf>,
+    /// Number of times each core and package has been throttled since boot, on intel cpus
+    throttle_counts: Vec<PathBuf>,
+}
+
+/// Temperature and throttling observed over some span of time
+#[derive(Clone, Copy)]
+pub struct ThermalReport {
+    pub max_temperature_c: Option<f64>,
+    /// Throttling events counted across every core, each of which counts throttling of its
+    /// package as well as of itself
+    pub throttle_events: Option<u64>,
+}
+
+/// Samples the cpu's temperature from a background thread, and counts throttling events, until
+/// it's finished
+pub struct ThermalSampler {
+    thermal: Thermal,
+    sampler: Option<Sampler>,
+    throttle_start: Option<u64>,
+}
+
+impl Thermal {
+    /// Finds the cpu's package temperature sensor and throttling counters, failing if there are
+    /// neither
+    pub fn open() -> Result<Self, Box<dyn Error>> {
+        let entries = |dir: &str| -> Vec<PathBuf> {
+            let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.path())
+                .collect();
+            paths.sort();
+            paths
+        };
+        let contents = |path: PathBuf| fs::read_to_string(path).unwrap_or_default();
+
+        // Prefer the cpu's own sensor drivers, then the generic thermal zones they feed
+        let hwmon = entries("/sys/class/hwmon").into_iter().find(|dir| {
+            ["coretemp", "k10temp", "zenpower"].contains(&contents(dir.join("name")).trim())
+        });
+        let zone = entries("/sys/class/thermal").into_iter().find(|dir| {
+            ["x86_pkg_temp", "cpu-thermal", "cpu_thermal"]
+                .contains(&contents(dir.join("type")).trim())
+        });
+        let temperature = hwmon
+            .map(|dir| dir.join("temp1_input"))
+            .or(zone.map(|dir| dir.join("temp")))
+            .filter(|path| read_celsius(path).is_some());
+
+        let throttle_counts: Vec<PathBuf> = entries("/sys/devices/system/cpu")
+            .into_iter()
+            .flat_map(|cpu| {
+                ["core_throttle_count", "package_throttle_count"]
+                    .map(|name| cpu.join("thermal_throttle").join(name))
+            })
+            .filter(|path| path.exists())
+            .collect();
+
+        if temperature.is_none() && throttle_counts.is_empty() {
+            return Err("neither a cpu temperature sensor nor thermal throttling counters were found in /sys; they usually aren't exposed in virtual machines".into());
+        }
+        Ok(Thermal {
+            temperature,
+            throttle_counts,
+        })
+    }
+
+    /// Starts sampling the temperature and counting throttling events
+    pub fn start(&self) -> Result<ThermalSampler, Box<dyn Error>> {
+        let sampler = self
+            .temperature
+            .clone()
+            .map(|path| Sampler::start(move || read_celsius(&path)));
+        Ok(ThermalSampler {
+            thermal: self.clone(),
+            sampler,
+            throttle_start: self.throttle_count()?,
+        })
+    }
+
+    /// Total throttling events since boot, if the cpu counts them
+    fn throttle_count(&self) -> Result<Option<u64>, Box<dyn Error>> {
+        if self.throttle_counts.is_empty() {
+            return Ok(None);
+        }
+        let mut total = 0;
+        for path in &self.throttle_counts {
+            total += read_count(path)?;
+        }
+        Ok(Some(total))
+    }
+}
+
+impl ThermalReport {
+    /// Combines this report with one from a later span of time
+    pub fn merge(&mut self, other: ThermalReport) {
+        self.max_temperature_c = match (self.max_temperature_c, other.max_temperature_c) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        self.throttle_events = match (self.throttle_events, other.throttle_events) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+impl ThermalSampler {
+    /// Stops sampling, returning the highest temperature reached and how many times the cpu was
+    /// throttled since starting
+    pub fn finish(self) -> Result<ThermalReport, Box<dyn Error>> {
+        let max_temperature_c = self
+            .sampler
+            .map(Sampler::finish)
+            .and_then(|samples| samples.into_iter().max_by(f64::total_cmp));
+        let throttle_events = self
+            .throttle_start
+            .zip(self.thermal.throttle_count()?)
+            .map(|(start, end)| end.saturating_sub(start));
+        Ok(ThermalReport {
+            max_temperature_c,
+            throttle_events,
+        })
+    }
+}
+
+/// Reads a temperature sensor reporting millidegrees Celsius
+fn read_celsius(path: &Path) -> Option<f64> {
+    let millidegrees: f64 = fs::read_to_string(path).ok()?.trim().parse().ok()?;
+    Some(millidegrees / 1000.0)
+}
+
+fn read_count(path: &Path) -> Result<u64, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read {} ({err})", path.display()))?;
+    Ok(contents.trim().parse()?)
+}
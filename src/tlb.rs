@@ -0,0 +1,90 @@
+use crate::{
+    chase::{follow, link_random_cycle, CACHE_LINE},
+    record::{unix_millis, Mode, Output, Record},
+};
+use clap::Parser;
+use rand::thread_rng;
+use std::{error::Error, mem::size_of, path::PathBuf};
+
+#[derive(Parser)]
+pub struct TlbArgs {
+    /// Initial number of distinct pages to touch
+    #[clap(short = 'd', long, default_value_t = 1)]
+    min_pages: usize,
+
+    /// Maximum number of distinct pages to touch
+    #[clap(short, long, default_value_t = 65536)]
+    max_pages: usize,
+
+    /// Size of a page in bytes
+    #[clap(short, long, default_value_t = 4096)]
+    page_size: usize,
+
+    /// Number of dependent page accesses to perform per page count
+    #[clap(short, long, default_value_t = 100_000_000)]
+    iterations: usize,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_tlb(args: TlbArgs) -> Result<(), Box<dyn Error>> {
+    if args.min_pages == 0 || args.max_pages < args.min_pages {
+        return Err("--min-pages must be at least 1 and no larger than --max-pages".into());
+    }
+    if !args.page_size.is_power_of_two() || args.page_size < CACHE_LINE {
+        return Err(
+            format!("--page-size must be a power of two of at least {CACHE_LINE} bytes").into(),
+        );
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    println!("Allocating {} pages", args.max_pages);
+    let page_elements = args.page_size / size_of::<usize>();
+    let mut chain: Vec<usize> = vec![0; args.max_pages * page_elements];
+
+    let lines_per_page = args.page_size / CACHE_LINE;
+    let line_elements = CACHE_LINE / size_of::<usize>();
+    let mut rng = thread_rng();
+    let mut pages = args.min_pages;
+    while pages <= args.max_pages {
+        // Touch a single element per page, at a different cache line in each page so that the
+        // accesses don't all compete for the same cache sets
+        let mut nodes: Vec<usize> = (0..pages)
+            .map(|page| page * page_elements + (page % lines_per_page) * line_elements)
+            .collect();
+        let start = link_random_cycle(&mut chain, &mut nodes, &mut rng);
+
+        println!("Touching {pages} pages");
+        let start_time = unix_millis();
+        let (index, total_duration) = follow(&chain, start, args.iterations);
+        let total_duration_float = total_duration.as_secs_f32();
+        let steps_per_second = (args.iterations as f32) / total_duration_float;
+        let latency_ns = total_duration.as_nanos() as f64 / args.iterations as f64;
+        println!(
+            "\rCompleted testing: took {total_duration_float:.3} secs, with an average latency of {latency_ns:.2} ns/access. end: {index}"
+        );
+        out.write(&Record {
+            start_time,
+            step_size: pages as u64,
+            total_duration_millis: total_duration.as_millis(),
+            steps_per_second,
+            avg_latency_ns: Some(latency_ns),
+            mode: Mode::Tlb,
+            pages_touched: Some(pages as u64),
+            page_size: Some(args.page_size as u64),
+            ..Record::default()
+        })?;
+        pages <<= 1;
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}
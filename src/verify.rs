@@ -0,0 +1,78 @@
+use crate::{
+    compare::step_key,
+    record::{read_records, Record},
+};
+use std::{collections::HashMap, error::Error, path::Path};
+
+/// Checks the steps of a replayed run against the sums and position checksums recorded by the
+/// original run
+pub struct Verifier {
+    seed: Option<u64>,
+    /// Recorded sum and position checksum of each step
+    expected: HashMap<String, (Option<u64>, Option<u64>)>,
+    mismatched: usize,
+    missing: usize,
+}
+
+impl Verifier {
+    /// Reads the results of the run to replay
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let records = read_records(path)?;
+        let seed = records.iter().find_map(|record| record.seed);
+        let expected = records
+            .iter()
+            .map(|record| (step_key(record), (record.sum, record.index_checksum)))
+            .collect();
+        Ok(Verifier {
+            seed,
+            expected,
+            mismatched: 0,
+            missing: 0,
+        })
+    }
+
+    /// Seed the original run was made with, if it recorded one
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Compares a replayed step with the original, printing whether it matched
+    pub fn check(&mut self, record: &Record) {
+        let Some(&(sum, index_checksum)) = self.expected.get(&step_key(record)) else {
+            println!("Verification: this step wasn't in the original run");
+            self.missing += 1;
+            return;
+        };
+        let checksum_matches = match (index_checksum, record.index_checksum) {
+            (Some(expected), Some(actual)) => expected == actual,
+            _ => true,
+        };
+        if sum == record.sum && checksum_matches {
+            println!("Verification: matched the original run");
+        } else {
+            println!(
+                "Verification: MISMATCH, the original run recorded sum {} and position checksum {}",
+                display(sum),
+                display(index_checksum)
+            );
+            self.mismatched += 1;
+        }
+    }
+
+    /// Fails if any step didn't match or wasn't in the original run
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        if self.mismatched > 0 || self.missing > 0 {
+            return Err(format!(
+                "verification failed: {} steps differed from the original run, and {} weren't in it",
+                self.mismatched, self.missing
+            )
+            .into());
+        }
+        println!("Verified every step against the original run");
+        Ok(())
+    }
+}
+
+fn display(value: Option<u64>) -> String {
+    value.map_or("nothing".to_string(), |value| value.to_string())
+}
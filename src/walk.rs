@@ -0,0 +1,2474 @@
+use crate::{
+    affinity::{available_cores, current_core, order_cores, pin_to_core, ThreadPlacement},
+    buffer::{Backing, Buffer, BufferInit, BufferOptions, NumaPolicy, PageSize},
+    chase::CACHE_LINE,
+    compare::step_key,
+    energy::{Energy, Rapl},
+    frequency::FrequencySampler,
+    measure::{finish_recording, measure, quiet, start_recording, Recorded, Sample, Timer},
+    metrics::Metrics,
+    numa::node_cores,
+    paging::Paging,
+    perf::{Counters, Counts},
+    plot::LivePlot,
+    record::{read_records, unix_millis, Format, Output, Record},
+    regression::Baseline,
+    stats::{Aggregate, Stats},
+    thermal::{Thermal, ThermalReport},
+    verify::Verifier,
+};
+use clap::{Parser, ValueEnum};
+use rand::{
+    distributions::{Bernoulli, Uniform},
+    rngs::StdRng,
+    thread_rng, Rng, SeedableRng,
+};
+use rand_distr::{Normal, Zipf};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::Cell,
+    collections::HashSet,
+    error::Error,
+    fmt,
+    hint::black_box,
+    mem::size_of,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Barrier,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Fewest iterations run between checks of the --duration-per-step budget, so that restarting the
+/// walk for each batch stays negligible
+const MIN_TIMED_BATCH: usize = 1 << 20;
+
+/// Prime multiplier used to scatter zipf ranks across the buffer. Since it's coprime to any
+/// practical buffer size, multiplying by it modulo the size is a bijection
+const ZIPF_SCATTER: u128 = (1 << 61) - 1;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pattern {
+    /// Bounded random walk, moving up or down by less than the step size on each access. Whether
+    /// it moves up or down is decided as given by --branch
+    #[default]
+    Random,
+
+    /// Linear scan through the buffer in --direction, one element after another; ignores the
+    /// step size
+    Sequential,
+
+    /// Fixed-stride scan in --direction, advancing by exactly the step size on each access and
+    /// wrapping around at the end of the buffer
+    Stride,
+
+    /// Independent positions drawn from a Zipfian distribution over the whole buffer, with the
+    /// exponent given by --zipf-exponent; ignores the step size. Ranks are scattered across the
+    /// buffer rather than packed at its start, so hot positions don't share cache lines
+    Zipf,
+
+    /// Random walk moving by a normally distributed offset on each access, with a standard
+    /// deviation of --sigma times the step size
+    Gaussian,
+
+    /// Uniformly random positions in either a hot region of --hot-size bytes at the start of the
+    /// buffer, chosen with probability --hot-probability, or a cold region of the step size
+    /// following it
+    HotCold,
+
+    /// Independent, uniformly random positions over the whole buffer; ignores the step size.
+    /// Sweeping the total size with this pattern gives the textbook cache size curve
+    Uniform,
+
+    /// Pointer chase through a single random cycle over positions the step size (rounded up to a
+    /// multiple of 8 bytes) apart, written into the buffer with Sattolo's algorithm before timing.
+    /// Every position is visited exactly once per lap and no random numbers are drawn while
+    /// timing. Only supports the read operation, and ignores the element size
+    Cycle,
+
+    /// Uniformly random positions within a block of the step size, moving on to the next block
+    /// after --tile-accesses accesses. Models blocked algorithms; sweeping the step size shows how
+    /// much tiling recovers locality
+    Tiled,
+
+    /// As the tiled pattern, but moving to a uniformly random block rather than the next one
+    RandomTiled,
+
+    /// Bursts of the step size in bytes, each read sequentially from the start of a uniformly
+    /// random page of --burst-page bytes. Models packet and record processing, between the fully
+    /// random and fully sequential extremes
+    Burst,
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+impl Pattern {
+    /// Whether the pattern scans through the buffer in a --direction
+    fn is_scan(self) -> bool {
+        matches!(self, Pattern::Sequential | Pattern::Stride)
+    }
+}
+
+/// Direction in which a scanning pattern moves through the buffer
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    /// Towards higher addresses
+    Forward,
+
+    /// Towards lower addresses, to check how the prefetcher handles negative strides
+    Backward,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+/// How the random walk decides between moving up and down
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Branch {
+    /// Negate the step arithmetically, so there's nothing for the branch predictor to miss
+    Branchless,
+
+    /// Take a deliberately unpredictable branch, adding the cost of a branch miss to about half
+    /// of all accesses
+    Branchy,
+}
+
+impl fmt::Display for Branch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+/// How the random walk draws its steps and wraps around the ends of the buffer
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sampling {
+    /// Draw each step uniformly from below the step size, and wrap around the ends of the buffer
+    /// exactly, whatever its size
+    #[default]
+    Unbiased,
+
+    /// Reduce a random word modulo the step size, and wrap by reducing the wrapping sum modulo the
+    /// total size. This is slightly biased towards small steps, and when the total size isn't a
+    /// power of two, moving down past the start of the buffer jumps to an arbitrary position
+    /// rather than its end. Kept to reproduce results measured before the unbiased scheme
+    Modulo,
+}
+
+impl fmt::Display for Sampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+/// The parameter varied from one measurement to the next
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Sweep {
+    /// Double the step size from --initial-step-size to --max-step-size over a buffer of
+    /// --total-size bytes
+    StepSize,
+
+    /// Double the buffer size from --min-total-size to --total-size, allocating a new buffer for
+    /// each, while keeping the step size at --initial-step-size
+    TotalSize,
+
+    /// Sweep over every combination of the total sizes and step sizes above, skipping step sizes
+    /// larger than the buffer
+    Both,
+
+    /// Double the number of threads from 1 to --threads, keeping the step size at
+    /// --initial-step-size over a buffer of --total-size bytes, to find where the memory system
+    /// saturates
+    Threads,
+}
+
+/// The memory operation performed at each position visited
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Op {
+    /// Load the element and add it to a running sum
+    #[default]
+    Read,
+
+    /// Store an element without reading it first
+    Write,
+
+    /// Load the element, increment it and store it back, adding the new value to a running sum
+    Rmw,
+
+    /// Randomly either read or write, storing with the probability given by --write-ratio
+    Mixed,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+/// Which buffer each thread of a multi-threaded step walks
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThreadBuffers {
+    /// Every thread walks the same buffer, contending for its cache lines as well as for the
+    /// memory system
+    #[default]
+    Shared,
+
+    /// Each thread walks a buffer of the same size of its own, so they only contend for
+    /// capacity and bandwidth
+    Private,
+}
+
+impl fmt::Display for ThreadBuffers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+#[derive(Parser)]
+pub struct TestArgs {
+    /// Amount of memory to allocate for test
+    #[clap(short, long, default_value_t = 1024 * 1024 * 1024)]
+    total_size: usize,
+
+    /// Parameter to sweep over
+    #[clap(short, long, value_enum, default_value_t = Sweep::StepSize)]
+    sweep: Sweep,
+
+    /// Initial buffer size when sweeping over the total size
+    #[clap(long, default_value_t = 4096)]
+    min_total_size: usize,
+
+    /// Initial step size
+    #[clap(short = 'd', long, default_value_t = 1)]
+    initial_step_size: usize,
+
+    /// Maximum step size. Must be >= --initial-step-size & <= --total-size [default same as --total-size]
+    #[clap(short, long)]
+    max_step_size: Option<usize>,
+
+    /// Number of iterations to run per step
+    #[clap(short, long, default_value_t = 1_000_000_000)]
+    iterations: usize,
+
+    /// Run each step for this long, such as `5s` or `500ms`, instead of a fixed number of
+    /// iterations. Iterations are run in batches until the budget is spent, so every step gets the
+    /// same statistical quality however fast it runs
+    #[clap(long, conflicts_with = "iterations", value_parser = humantime::parse_duration)]
+    duration_per_step: Option<Duration>,
+
+    /// Measure each step for at least this long, such as `2s`, running more than `--iterations`
+    /// when a step finishes sooner. The extra iterations are scaled from how fast the step ran, so
+    /// small steps get enough iterations to be meaningful without large steps getting absurd ones
+    #[clap(long, conflicts_with = "duration_per_step", value_parser = humantime::parse_duration)]
+    min_duration_per_step: Option<Duration>,
+
+    /// Number of times to measure each step, each time running every iteration. The mean rate is
+    /// recorded along with its standard deviation, minimum and maximum, to quantify the noise
+    #[clap(long, default_value_t = 1)]
+    repeats: usize,
+
+    /// Fraction of the repeats to discard from each end, slowest and fastest, before aggregating,
+    /// so that an occasional repeat slowed by a background process doesn't skew the result
+    #[clap(long, default_value_t = 0.0, value_parser = parse_trim)]
+    trim: f64,
+
+    /// How to combine the kept repeats into the recorded rate
+    #[clap(long, value_enum, default_value_t = Aggregate::Mean)]
+    aggregate: Aggregate,
+
+    /// Number of iterations to run untimed before measuring each step, so that page faults and
+    /// filling the cache aren't counted against the first pass
+    #[clap(long, default_value_t = 0)]
+    warmup_iterations: usize,
+
+    /// Access patterns to test at each step size; multiple patterns are run one after another
+    #[clap(short, long, value_delimiter = ',', default_value = "random")]
+    pattern: Vec<Pattern>,
+
+    /// Directions for the sequential and stride patterns to scan in; multiple directions are run
+    /// one after another
+    #[clap(long, value_delimiter = ',', default_value = "forward")]
+    direction: Vec<Direction>,
+
+    /// How the random pattern chooses its direction; multiple choices are run one after another,
+    /// to separate the cost of branch misses from the cost of memory accesses
+    #[clap(long, value_delimiter = ',', default_value = "branchless")]
+    branch: Vec<Branch>,
+
+    /// How the random pattern draws its steps and wraps around the ends of the buffer
+    #[clap(long, value_enum, default_value_t = Sampling::Unbiased)]
+    sampling: Sampling,
+
+    /// Memory operations to test at each step size; multiple operations are run one after another
+    #[clap(long, value_delimiter = ',', default_value = "read")]
+    op: Vec<Op>,
+
+    /// Fractions of accesses that are stores in the mixed operation mode, from 0 to 1; multiple
+    /// ratios are run one after another
+    #[clap(long, value_delimiter = ',', default_value = "0.5", value_parser = parse_ratio)]
+    write_ratio: Vec<f64>,
+
+    /// Exponent of the distribution used by the zipf pattern; larger values concentrate accesses
+    /// on fewer positions
+    #[clap(long, default_value_t = 1.0)]
+    zipf_exponent: f64,
+
+    /// Standard deviation of the gaussian pattern's offsets, as a multiple of the step size
+    #[clap(long, default_value_t = 1.0)]
+    sigma: f64,
+
+    /// Size in bytes of the hot region used by the hot-cold pattern
+    #[clap(long, default_value_t = 32 * 1024)]
+    hot_size: usize,
+
+    /// Probability of each access in the hot-cold pattern landing in the hot region
+    #[clap(long, default_value_t = 0.9, value_parser = parse_ratio)]
+    hot_probability: f64,
+
+    /// Number of accesses the tiled patterns make within each block before moving on
+    #[clap(long, default_value_t = 1024)]
+    tile_accesses: usize,
+
+    /// Sizes in bytes of the element accessed at each position, from 1, 2, 4, 8, 16, 32 or 64;
+    /// multiple sizes are run one after another. Positions are rounded down to a multiple of the element
+    /// size, so the step size stays in bytes
+    #[clap(short, long, value_delimiter = ',', default_value = "1", value_parser = parse_element_size)]
+    element_size: Vec<usize>,
+
+    /// Read elements of 16 bytes or more with a single SSE, AVX or AVX-512 vector load, rather
+    /// than as separate 8-byte words. Only supported by the read operation on x86_64
+    #[clap(long)]
+    simd: bool,
+
+    /// Sizes of the pages backing the buffer; the whole sweep is run once for each page size
+    #[clap(long, value_delimiter = ',', default_value = "base")]
+    page_size: Vec<PageSize>,
+
+    /// Size in bytes of the pages the burst pattern starts each burst in
+    #[clap(long, default_value_t = 4096)]
+    burst_page: usize,
+
+    /// Generate this many positions at a time into an index buffer before timing, then only replay
+    /// them from it while timing, so that drawing random numbers doesn't hide the cost of the
+    /// accesses at small step sizes. Reading the index buffer streams through another
+    /// `8 * this` bytes, which the prefetcher handles well but which does share the cache. The mixed
+    /// operation still draws whether to store while timing, and the cycle pattern, which draws no
+    /// random numbers, ignores this
+    #[clap(long)]
+    index_buffer: Option<usize>,
+
+    /// Also time accesses in batches of this many, and record percentiles of the batches'
+    /// average latency, showing the tail that the overall average hides. Smaller batches resolve
+    /// the tail more finely, but spend more of their time reading the clock
+    #[clap(long)]
+    histogram_batch: Option<usize>,
+
+    /// Clock to time --histogram-batch batches with. The tsc timer also counts the cycles each
+    /// step takes, and records the cycles per access
+    #[clap(long, value_enum, default_value_t = Timer::Instant)]
+    timer: Timer,
+
+    /// Also record the throughput of every interval that each step's rate is printed after, as a
+    /// record of its own numbered by its `sample`, to look at the variance within a step and how
+    /// it drifts over time. Intervals start short and double until they reach about 100ms. plot,
+    /// analyze and compare leave these records out
+    #[clap(long)]
+    samples: bool,
+
+    /// Sample the frequency of the core the test runs on throughout each step, recording its mean,
+    /// minimum and maximum. Turbo and thermal throttling bend the curves, so a warning is printed
+    /// whenever it varies by more than --frequency-tolerance. Only supported on linux
+    #[clap(long)]
+    sample_frequency: bool,
+
+    /// Largest variation in the sampled frequency during a step, as a fraction of its mean, that
+    /// doesn't print a warning
+    #[clap(long, default_value_t = 0.05)]
+    frequency_tolerance: f64,
+
+    /// Read the RAPL package and DRAM energy counters around each step, recording the joules
+    /// consumed and the nanojoules per access. The counters cover every core of each package, so
+    /// other work on the machine is included too. Needs linux, and usually root
+    #[clap(long)]
+    energy: bool,
+
+    /// Sample the cpu's package temperature throughout each step, and count the times it's
+    /// thermally throttled, flagging steps that were throttled in the output and the plot. Long
+    /// sweeps on laptops often throttle midway, which looks like a change in the memory system
+    #[clap(long)]
+    thermal: bool,
+
+    /// Temperature in degrees Celsius at or above which --thermal flags a step as throttled, for
+    /// cpus that don't count throttling events
+    #[clap(long, default_value_t = 95.0)]
+    throttle_temperature: f64,
+
+    /// Run the repeats round robin across the sweep, measuring every step size and variant once
+    /// before measuring any of them again, rather than finishing each step before starting the
+    /// next. Slow drift in temperature or frequency then affects every step alike instead of
+    /// looking like a trend in step size. Results are printed and recorded once every round is done
+    #[clap(long)]
+    interleave: bool,
+
+    /// Seed the random number generator that fills the buffer and chooses positions, so that the
+    /// same arguments replay the same accesses. A random seed is used otherwise; either way, it's
+    /// recorded
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// Compute a checksum of every position each step visits, in order, and record it alongside
+    /// the sum of the values loaded. Costs a little time per access
+    #[clap(long)]
+    checksum: bool,
+
+    /// Replay the run that recorded these results, with its seed unless --seed is given, and fail
+    /// if any step's sum or checksum of positions differs. Catches the compiler eliding accesses
+    /// and changes to the harness between versions; the rest of the arguments must match the
+    /// original run's
+    #[clap(long, conflicts_with_all = ["duration_per_step", "min_duration_per_step"])]
+    verify: Option<PathBuf>,
+
+    /// Results of an earlier run to check this one's throughput against, step by step, failing
+    /// once the run finishes if any step fell further below it than --max-regression allows, so
+    /// the run can gate a change on its performance
+    #[clap(long)]
+    baseline: Option<PathBuf>,
+
+    /// Largest drop in a step's throughput from the --baseline allowed, as a percentage such as
+    /// `10%`
+    #[clap(long, value_parser = parse_percent, default_value = "10%", requires = "baseline")]
+    max_regression: f64,
+
+    /// Number of threads running each step's accesses at once, all over the same buffer, to see
+    /// how memory behaves under load from several cores. The recorded rate is that of every
+    /// thread together, and its latency the time per access at that rate. Only the read
+    /// operation is supported, and not the cycle pattern, since the threads would race to store.
+    /// --perf, --timer, --histogram-batch and --sample-frequency only cover the first thread
+    /// [default 1, or the number of available cpus with `--sweep threads`]
+    #[clap(long)]
+    threads: Option<usize>,
+
+    /// Whether the threads of a multi-threaded step all walk one shared buffer, or each walks a
+    /// private buffer of the same size. Comparing the two separates contention over the same
+    /// lines from the effects of the threads' combined footprint; private buffers take a whole
+    /// --total-size each, and only anonymous memory is supported. Each is run in turn
+    #[clap(long, value_delimiter = ',', default_value = "shared")]
+    thread_buffers: Vec<ThreadBuffers>,
+
+    /// Before measuring each step, time the same loop without its memory accesses, choosing
+    /// positions and summing them but never touching the buffer, and record that harness
+    /// overhead. Doesn't apply to the cycle pattern, whose accesses choose its positions
+    #[clap(long)]
+    calibrate: bool,
+
+    /// Subtract the overhead measured by --calibrate from each measurement, so that small steps
+    /// report the cost of the accesses rather than of the harness. Steps measuring no slower than
+    /// the overhead are recorded without subtracting it
+    #[clap(long, requires = "calibrate")]
+    subtract_overhead: bool,
+
+    /// Flush the whole buffer from the cache with clflush before every this many accesses, so
+    /// that accesses only hit lines touched since the last flush. The flushes aren't timed, and
+    /// each batch starts again from the first position. Useful as a worst-case cold cache baseline;
+    /// only supported on x86_64
+    #[clap(long)]
+    flush_batch: Option<usize>,
+
+    /// Where the buffer's memory comes from: `anonymous` memory, `mmap:<path>` to map a file,
+    /// `devdax:<path>` to map a device-DAX device such as persistent or CXL attached memory, or
+    /// `fsdax:<path>` to map a file on a DAX filesystem directly. Files are created or resized, and
+    /// devices and files are overwritten. Mapping files on tmpfs, disk or NVMe extends the curve
+    /// into the page cache and storage; the whole sweep is run once for each
+    #[clap(long, value_delimiter = ',', default_value = "anonymous")]
+    backing: Vec<Backing>,
+
+    /// NUMA nodes to allocate the buffer on, binding its pages there with mbind rather than
+    /// leaving them wherever they're first touched; the whole sweep is run once for each node
+    /// [default left to the kernel]
+    #[clap(long, visible_alias = "mem-node", value_delimiter = ',')]
+    memory_node: Vec<usize>,
+
+    /// How to place the buffer across NUMA nodes: bound to each of --memory-node, or interleaved
+    /// across every node with memory. Giving both plots interleaving's cost alongside local and
+    /// remote access; the whole sweep is run once for each
+    #[clap(long, value_delimiter = ',', default_value = "bind")]
+    numa_policy: Vec<NumaPolicy>,
+
+    /// Lock the buffer into memory once it's filled, so none of it can be paged out partway
+    /// through the sweep. Locking more than RLIMIT_MEMLOCK allows needs the limit raised or root
+    #[clap(long)]
+    mlock: bool,
+
+    /// Fault in the whole buffer before timing begins: mappings are populated as they're made, and
+    /// every page is touched once the buffer is filled, so that the first step doesn't pay for
+    /// faults that the random fill left behind
+    #[clap(long)]
+    prefault: bool,
+
+    /// What to fill the buffer with. Filling gigabytes with random data takes a while, and
+    /// fast-random, pattern and zeros take much less; the positions walked are random whatever
+    /// the data, but zeros and the pattern make it compressible, which matters with zswap or
+    /// memory compression
+    #[clap(long, value_enum, default_value_t = BufferInit::Random)]
+    init: BufferInit,
+
+    /// NUMA nodes to run the test on, pinning the threads to the available cores of each in turn,
+    /// starting from the first; the whole sweep is run once for each node, and for each memory
+    /// node. Giving one node here and another for --memory-node measures remote access [default
+    /// left to the scheduler]
+    #[clap(long, value_delimiter = ',')]
+    cpu_node: Vec<usize>,
+
+    /// Pin the measuring threads to these cores, the first thread to the first core and so on,
+    /// so the scheduler can't migrate them midway through a step and change the cache being
+    /// measured. Needs a core for every thread [default left to the scheduler]
+    #[clap(long, value_delimiter = ',', conflicts_with = "cpu_node")]
+    pin_cores: Vec<usize>,
+
+    /// Pin the threads by the cpu's topology instead: packed onto the SMT siblings of each
+    /// physical core, spread across physical cores, or spread across sockets. Siblings share
+    /// their L1 and L2 caches, which changes the curves substantially. Combines with --cpu-node
+    /// to place the threads among that node's cores
+    #[clap(long, conflicts_with = "pin_cores")]
+    thread_placement: Option<ThreadPlacement>,
+
+    /// Count cycles, instructions, and L1 data cache, last level cache and data TLB read misses
+    /// with hardware performance counters while timing each step, and record them with the
+    /// results, totalled over every repeat. Only supported on linux
+    #[clap(long)]
+    perf: bool,
+
+    /// Output file to record results to, or `-` for stdout [default stdout when it's piped or
+    /// redirected, and nowhere otherwise]
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Format to record results in [default chosen by the extension of --out: json, jsonl,
+    /// sqlite or db, parquet, arrow or feather, lp or influx, and csv for any other]
+    #[clap(long, value_enum)]
+    format: Option<Format>,
+
+    /// Add results to the end of the output file, rather than overwriting it, so that runs on
+    /// different machines or days, or of different modes, gather into one file. Only csv, json
+    /// lines, line protocol and sqlite results can be appended to
+    #[clap(long)]
+    append: bool,
+
+    /// Continue a sweep that was interrupted, skipping every step the results file named by --out
+    /// already holds and appending the rest. The sweep's seed is carried on too, unless --seed is
+    /// given
+    #[clap(long)]
+    resume: bool,
+
+    /// Redraw a plot of the results to this png as each step finishes, to watch the curve form
+    /// during long sweeps; open it in an image viewer that reloads files when they change
+    #[clap(long)]
+    live_plot: Option<PathBuf>,
+
+    /// Serve Prometheus metrics of the sweep's progress and the latest results of each series on
+    /// this address, such as `0.0.0.0:9184`, for dashboards to scrape at `/metrics`
+    #[clap(long)]
+    metrics: Option<SocketAddr>,
+
+    /// Push the same metrics to the Prometheus Pushgateway at this http url, such as
+    /// `http://gateway:9091`, as each step starts and finishes, grouped by job `cache_locality`
+    /// and this machine's hostname
+    #[clap(long)]
+    push_gateway: Option<String>,
+}
+
+fn parse_ratio(s: &str) -> Result<f64, String> {
+    let ratio: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&ratio) {
+        Ok(ratio)
+    } else {
+        Err(format!("{ratio} is not between 0 and 1"))
+    }
+}
+
+fn parse_percent(s: &str) -> Result<f64, String> {
+    let percent: f64 = s
+        .strip_suffix('%')
+        .unwrap_or(s)
+        .parse()
+        .map_err(|e| format!("{e}"))?;
+    if percent >= 0.0 {
+        Ok(percent)
+    } else {
+        Err(format!("{percent}% is negative"))
+    }
+}
+
+fn parse_trim(s: &str) -> Result<f64, String> {
+    let trim: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..0.5).contains(&trim) {
+        Ok(trim)
+    } else {
+        Err(format!("{trim} is not at least 0 and less than 0.5"))
+    }
+}
+
+fn parse_element_size(s: &str) -> Result<usize, String> {
+    let size: usize = s.parse().map_err(|e| format!("{e}"))?;
+    if [1, 2, 4, 8, 16, 32, 64].contains(&size) {
+        Ok(size)
+    } else {
+        Err(format!("{size} is not one of 1, 2, 4, 8, 16, 32 or 64"))
+    }
+}
+
+/// One combination of test parameters, run at every step size
+#[derive(Clone, Copy)]
+struct Variant {
+    pattern: Pattern,
+    /// Direction of the scan, for the patterns that scan through the buffer
+    direction: Option<Direction>,
+    /// How the direction is chosen, for the random pattern
+    branch: Option<Branch>,
+    op: Op,
+    write_ratio: Option<f64>,
+    element_size: usize,
+    /// Whether each element is loaded with one vector instruction
+    vector: bool,
+    /// Number of positions to generate ahead of timing, when replaying them from an index buffer
+    index_buffer: Option<usize>,
+    /// Whether to run only the harness, choosing positions without accessing them
+    overhead: bool,
+    /// Seeds the mixed operation's choices of whether to store, drawn afresh for each walk from
+    /// the run's generator so that a seeded run makes the same choices
+    store_seed: u64,
+    /// Number of threads running the accesses at once
+    threads: usize,
+    /// Which buffer each thread walks, when several run at once
+    buffers: ThreadBuffers,
+}
+
+impl Variant {
+    /// Every combination of the parameters given on the command line, at each of the thread
+    /// counts
+    fn all(args: &TestArgs, thread_counts: &[usize]) -> Vec<Variant> {
+        let mut variants = Vec::new();
+        for &element_size in &args.element_size {
+            let vector = args.simd && element_size >= 16;
+            for &pattern in &args.pattern {
+                let directions = if pattern.is_scan() {
+                    optional_list(&args.direction)
+                } else {
+                    vec![None]
+                };
+                let branches = if pattern == Pattern::Random {
+                    optional_list(&args.branch)
+                } else {
+                    vec![None]
+                };
+                for (direction, branch) in directions
+                    .into_iter()
+                    .flat_map(|direction| branches.iter().map(move |&branch| (direction, branch)))
+                {
+                    for &op in &args.op {
+                        let write_ratios = if op == Op::Mixed {
+                            optional_list(&args.write_ratio)
+                        } else {
+                            vec![None]
+                        };
+                        for write_ratio in write_ratios {
+                            for &threads in thread_counts {
+                                // A single thread walks the same buffer either way
+                                let buffers = if threads > 1 {
+                                    &args.thread_buffers[..]
+                                } else {
+                                    &[ThreadBuffers::Shared]
+                                };
+                                for &buffers in buffers {
+                                    variants.push(Variant {
+                                        pattern,
+                                        direction,
+                                        branch,
+                                        op,
+                                        write_ratio,
+                                        element_size,
+                                        vector,
+                                        index_buffer: args.index_buffer,
+                                        overhead: false,
+                                        store_seed: 0,
+                                        threads,
+                                        buffers,
+                                    });
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        variants
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} pattern (", self.pattern)?;
+        if let Some(direction) = self.direction {
+            write!(f, "{direction}, ")?;
+        }
+        if let Some(branch) = self.branch {
+            write!(f, "{branch}, ")?;
+        }
+        write!(f, "{}", self.op)?;
+        if let Some(write_ratio) = self.write_ratio {
+            write!(f, ", write ratio {write_ratio}")?;
+        }
+        let kind = if self.vector { "vector" } else { "elements" };
+        write!(f, ", {}-byte {kind}", self.element_size)?;
+        if self.threads > 1 {
+            write!(f, ", {} threads on {} buffers", self.threads, self.buffers)?;
+        }
+        f.write_str(")")
+    }
+}
+
+pub fn run_test(args: TestArgs) -> Result<(), Box<dyn Error>> {
+    if args.initial_step_size == 0 || args.min_total_size == 0 {
+        return Err("--initial-step-size and --min-total-size must be at least 1".into());
+    }
+    if args.pattern.contains(&Pattern::HotCold) && args.hot_size == 0 {
+        return Err("--hot-size must be greater than 0".into());
+    }
+    if let Some(batch) = args.flush_batch {
+        if cfg!(not(target_arch = "x86_64")) {
+            return Err("--flush-batch is only supported on x86_64".into());
+        }
+        if batch == 0 {
+            return Err("--flush-batch must be at least 1".into());
+        }
+    }
+    if args.burst_page == 0 {
+        return Err("--burst-page must be at least 1".into());
+    }
+    if args.repeats == 0 {
+        return Err("--repeats must be at least 1".into());
+    }
+    args.timer.check()?;
+    if args.histogram_batch == Some(0) {
+        return Err("--histogram-batch must be at least 1".into());
+    }
+    if args.index_buffer == Some(0) {
+        return Err("--index-buffer must be at least 1".into());
+    }
+    if args.tile_accesses == 0 {
+        return Err("--tile-accesses must be at least 1".into());
+    }
+    if args.simd {
+        check_simd(&args)?;
+    }
+    if args.pattern.contains(&Pattern::Cycle) && args.op.iter().any(|&op| op != Op::Read) {
+        return Err("the cycle pattern only supports the read operation".into());
+    }
+    if args.threads == Some(0) {
+        return Err("--threads must be at least 1".into());
+    }
+    let thread_counts = match (args.sweep, args.threads) {
+        (Sweep::Threads, threads) => {
+            let max_threads = match threads {
+                Some(threads) => threads,
+                None => thread::available_parallelism()?.get(),
+            };
+            doublings(1, max_threads)
+        }
+        (_, threads) => vec![threads.unwrap_or(1)],
+    };
+    let max_threads = thread_counts.iter().copied().max().unwrap_or(1);
+    if !args.pin_cores.is_empty() {
+        let available = available_cores()?;
+        if let Some(core) = args.pin_cores.iter().find(|core| !available.contains(core)) {
+            return Err(
+                format!("core {core} of --pin-cores isn't available to this process").into(),
+            );
+        }
+        if args.pin_cores.len() < max_threads {
+            return Err(format!(
+                "--pin-cores lists {} cores, but up to {max_threads} threads run at once",
+                args.pin_cores.len()
+            )
+            .into());
+        }
+    }
+    if thread_counts.iter().any(|&threads| threads > 1) {
+        if args.op.iter().any(|&op| op != Op::Read) {
+            return Err("--threads only supports the read operation".into());
+        }
+        if args.pattern.contains(&Pattern::Cycle) {
+            return Err("--threads doesn't support the cycle pattern".into());
+        }
+        if args.thread_buffers.contains(&ThreadBuffers::Private)
+            && args
+                .backing
+                .iter()
+                .any(|backing| *backing != Backing::Anonymous)
+        {
+            return Err("private thread buffers are only supported with anonymous memory".into());
+        }
+    }
+
+    let resumed = match args.out.as_deref() {
+        _ if !args.resume => Vec::new(),
+        Some(path) if path.as_os_str() != "-" => {
+            if path.exists() {
+                read_records(path)?
+            } else {
+                Vec::new()
+            }
+        }
+        _ => return Err("--resume needs --out to name the results file to continue".into()),
+    };
+    // Per-thread records are written alongside their step's, so the step's own stand for both
+    let completed: HashSet<String> = resumed
+        .iter()
+        .filter(|record| record.thread.is_none())
+        .map(step_key)
+        .collect();
+    // Created first, so that when results go to stdout everything printed goes to stderr
+    let mut out = Output::create_as(args.out.clone(), args.format, args.append || args.resume)?;
+    let monitors = Monitors {
+        counters: args.perf.then(Counters::open).transpose()?,
+        rapl: args.energy.then(Rapl::open).transpose()?,
+        thermal: args.thermal.then(Thermal::open).transpose()?,
+    };
+    let variants = Variant::all(&args, &thread_counts);
+    let mut verifier = args.verify.as_deref().map(Verifier::open).transpose()?;
+    let mut baseline = args
+        .baseline
+        .as_deref()
+        .map(|path| Baseline::open(path, args.max_regression))
+        .transpose()?;
+    let seed = match (args.seed, &verifier) {
+        (Some(seed), _) => seed,
+        (None, Some(verifier)) => verifier.seed().ok_or(
+            "the results to verify don't record a seed, so pass the one they used with --seed",
+        )?,
+        (None, None) => resumed
+            .iter()
+            .find_map(|record| record.seed)
+            .unwrap_or_else(|| thread_rng().gen()),
+    };
+    println!("Seeding the random number generator with {seed}");
+    let mut live_plot = args.live_plot.clone().map(LivePlot::new);
+    let mut swapped_steps = 0;
+
+    let total_sizes = match args.sweep {
+        Sweep::StepSize | Sweep::Threads => vec![args.total_size],
+        Sweep::TotalSize | Sweep::Both => {
+            doublings(args.min_total_size.min(args.total_size), args.total_size)
+        }
+    };
+    let largest_element = args.element_size.iter().copied().max().unwrap_or(1);
+    if total_sizes.iter().any(|size| size % largest_element != 0) {
+        return Err(format!(
+            "--total-size and --min-total-size must be multiples of the {largest_element} byte element size"
+        )
+        .into());
+    }
+    // Interleaving ignores --memory-node, and is only run once
+    let mut memory_placements = Vec::new();
+    for &policy in &args.numa_policy {
+        match policy {
+            NumaPolicy::Bind => memory_placements.extend(
+                optional_list(&args.memory_node)
+                    .into_iter()
+                    .map(|node| (node, false)),
+            ),
+            NumaPolicy::Interleave => memory_placements.push((None, true)),
+        }
+    }
+    let mut node_cores = Vec::new();
+    for &node in &args.cpu_node {
+        let mut cores = available_node_cores(node)?;
+        if let Some(placement) = args.thread_placement {
+            cores = order_cores(&cores, placement)?;
+        }
+        if cores.len() < max_threads {
+            return Err(format!(
+                "NUMA node {node} has {} cores available, but up to {max_threads} threads run at once",
+                cores.len()
+            )
+            .into());
+        }
+        node_cores.push(cores);
+    }
+    let cpu_nodes = optional_list(&args.cpu_node);
+    let default_cores = match args.thread_placement {
+        Some(placement) => {
+            let cores = order_cores(&available_cores()?, placement)?;
+            if cores.len() < max_threads {
+                return Err(format!(
+                    "{} cores are available, but up to {max_threads} threads run at once",
+                    cores.len()
+                )
+                .into());
+            }
+            cores
+        }
+        None => args.pin_cores.clone(),
+    };
+    let mut placements = Vec::new();
+    for backing in &args.backing {
+        for &page_size in &args.page_size {
+            for &(node, interleave) in &memory_placements {
+                for &cpu_node in &cpu_nodes {
+                    let options = BufferOptions {
+                        backing: backing.clone(),
+                        page_size,
+                        node,
+                        interleave,
+                        mlock: args.mlock,
+                        prefault: args.prefault,
+                        init: args.init,
+                    };
+                    placements.push((options, cpu_node));
+                }
+            }
+        }
+    }
+
+    let metrics = if args.metrics.is_some() || args.push_gateway.is_some() {
+        let steps_per_placement: usize = total_sizes
+            .iter()
+            .map(|&total_size| step_sizes(&args, total_size).len() * variants.len())
+            .sum();
+        Some(Metrics::start(
+            args.metrics,
+            args.push_gateway.as_deref(),
+            placements.len() * steps_per_placement,
+        )?)
+    } else {
+        None
+    };
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    for (options, cpu_node) in placements {
+        let page_size = options.page_size;
+        for &total_size in &total_sizes {
+            print!(
+                "Allocating {total_size} bytes of {} on {page_size} pages",
+                options.init.describe()
+            );
+            if let Some(node) = options.node {
+                print!(" on node {node}");
+            }
+            if options.interleave {
+                print!(" interleaved across NUMA nodes");
+            }
+            if options.backing != Backing::Anonymous {
+                print!(" backed by {}", options.backing);
+            }
+            println!();
+            let mut mem = Buffer::seeded(total_size, &options, rng.gen())?;
+            let private_threads = if variants
+                .iter()
+                .any(|variant| variant.buffers == ThreadBuffers::Private)
+            {
+                max_threads - 1
+            } else {
+                0
+            };
+            if private_threads > 0 {
+                println!(
+                    "Allocating {private_threads} more buffers of {total_size} bytes, one for each thread but the first"
+                );
+            }
+            // The first thread walks the main buffer, and each other thread the next of these
+            let private: Vec<Buffer> = (0..private_threads)
+                .map(|_| Buffer::seeded(total_size, &options, rng.gen()))
+                .try_collect()?;
+            let cores = match cpu_node {
+                Some(node) => {
+                    let index = args.cpu_node.iter().position(|&n| n == node).unwrap();
+                    node_cores[index].clone()
+                }
+                None => default_cores.clone(),
+            };
+            // Pin only after allocating, so the threads filling the buffer aren't confined too
+            if let Some(&core) = cores.first() {
+                match cpu_node {
+                    Some(node) => println!("Running on core {core} of node {node}"),
+                    None => println!("Pinning the first thread to core {core}"),
+                }
+                pin_to_core(core)?;
+            }
+
+            let mut steps: Vec<(usize, Variant)> = step_sizes(&args, total_size)
+                .into_iter()
+                .flat_map(|step_size| variants.iter().map(move |&variant| (step_size, variant)))
+                .collect();
+            let placement = StepPlacement {
+                options: &options,
+                cpu_node,
+                cores,
+                total_size,
+            };
+            let count = steps.len();
+            steps.retain(|&(step_size, variant)| {
+                let step = step_record(&args, &placement, step_size, variant, seed);
+                !completed.contains(&step_key(&step))
+            });
+            if let Some(metrics) = &metrics {
+                metrics.skip_steps(count - steps.len());
+            }
+            if steps.len() < count {
+                println!(
+                    "Skipping {} steps already in the results being resumed",
+                    count - steps.len()
+                );
+            }
+            if args.interleave {
+                let mut measured: Vec<Measured> =
+                    steps.iter().map(|_| Measured::default()).collect();
+                for round in 1..=args.repeats {
+                    for (&(step_size, variant), measured) in steps.iter().zip(&mut measured) {
+                        println!(
+                            "Testing step size {step_size} with {variant}, round {round} of {}",
+                            args.repeats
+                        );
+                        if let Some(metrics) = &metrics {
+                            metrics.start_step(step_size as u64, total_size as u64);
+                        }
+                        measure_repeats(
+                            &args,
+                            &monitors,
+                            &placement.cores,
+                            &mut mem,
+                            &private,
+                            step_size,
+                            variant,
+                            1,
+                            &mut rng,
+                            measured,
+                        )?;
+                    }
+                }
+                for (&(step_size, variant), measured) in steps.iter().zip(measured) {
+                    println!("Results for step size {step_size} with {variant}:");
+                    let records = report(&args, &placement, step_size, variant, seed, measured)?;
+                    finish_step(
+                        records,
+                        &mut out,
+                        live_plot.as_mut(),
+                        metrics.as_ref(),
+                        verifier.as_mut(),
+                        baseline.as_mut(),
+                        &mut swapped_steps,
+                    )?;
+                }
+            } else {
+                for &(step_size, variant) in &steps {
+                    println!("Testing step size {step_size} with {variant}");
+                    if let Some(metrics) = &metrics {
+                        metrics.start_step(step_size as u64, total_size as u64);
+                    }
+                    let mut measured = Measured::default();
+                    measure_repeats(
+                        &args,
+                        &monitors,
+                        &placement.cores,
+                        &mut mem,
+                        &private,
+                        step_size,
+                        variant,
+                        args.repeats,
+                        &mut rng,
+                        &mut measured,
+                    )?;
+                    let records = report(&args, &placement, step_size, variant, seed, measured)?;
+                    finish_step(
+                        records,
+                        &mut out,
+                        live_plot.as_mut(),
+                        metrics.as_ref(),
+                        verifier.as_mut(),
+                        baseline.as_mut(),
+                        &mut swapped_steps,
+                    )?;
+                }
+            }
+        }
+    }
+    if swapped_steps > 0 {
+        println!(
+            "Warning: {swapped_steps} steps took major page faults, and are tagged as swapped in the results; free up memory or use a smaller buffer"
+        );
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    verifier.map_or(Ok(()), Verifier::finish)?;
+    baseline.map_or(Ok(()), Baseline::finish)
+}
+
+/// Writes a step's record, then those of each of its threads, checking the step against the
+/// original run when verifying and the baseline when given one, counting it if it swapped, and
+/// adding them all to the live plot and metrics
+fn finish_step(
+    (record, detail_records): (Record, Vec<Record>),
+    out: &mut Output,
+    live_plot: Option<&mut LivePlot>,
+    metrics: Option<&Metrics>,
+    verifier: Option<&mut Verifier>,
+    baseline: Option<&mut Baseline>,
+    swapped_steps: &mut usize,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(verifier) = verifier {
+        verifier.check(&record);
+    }
+    if let Some(baseline) = baseline {
+        baseline.check(&record);
+    }
+    if record.swapped == Some(true) {
+        *swapped_steps += 1;
+    }
+    out.write(&record)?;
+    if let Some(metrics) = metrics {
+        metrics.finish_step(&record);
+    }
+    for record in &detail_records {
+        out.write(record)?;
+    }
+    if let Some(live_plot) = live_plot {
+        let thread_records = detail_records
+            .into_iter()
+            .filter(|record| record.sample.is_none());
+        live_plot.add(std::iter::once(record).chain(thread_records));
+    }
+    Ok(())
+}
+
+/// Everything measured of a single variant at a single step size, over repeats that may be run
+/// in several blocks when --interleave spreads them across the sweep
+#[derive(Default)]
+struct Measured {
+    /// When the first block started
+    start_time: u128,
+    sum: u64,
+    /// Checksum of the positions visited so far, with --checksum
+    index_checksum: Option<u64>,
+    iterations: usize,
+    total_duration: Duration,
+    /// Average latency of each repeat in nanoseconds per access
+    latencies: Vec<f64>,
+    overhead_ns: Option<f64>,
+    recorded: Option<Recorded>,
+    frequency_samples: Vec<f64>,
+    thermal_report: Option<ThermalReport>,
+    energy: Option<Energy>,
+    paging: Option<Paging>,
+    counts: Option<Counts>,
+    /// What each thread ran, the first thread first, when several ran at once
+    threads: Vec<ThreadRun>,
+}
+
+/// Iterations one of a step's threads ran and the time they took, along with the core it ran on
+#[derive(Clone, Copy)]
+struct ThreadRun {
+    iterations: usize,
+    duration: Duration,
+    core: Option<usize>,
+}
+
+/// Where the buffer a step runs over lives, and the cores it runs on
+struct StepPlacement<'a> {
+    options: &'a BufferOptions,
+    cpu_node: Option<usize>,
+    /// Cores to pin the threads to, the first thread first, or none to leave them to the scheduler
+    cores: Vec<usize>,
+    total_size: usize,
+}
+
+/// What's monitored alongside the timing of each step
+struct Monitors {
+    counters: Option<Counters>,
+    rapl: Option<Rapl>,
+    thermal: Option<Thermal>,
+}
+
+/// Adds `value` to `total`, for measurements taken over several blocks of repeats
+fn accumulate<T>(total: &mut Option<T>, value: Option<T>, merge: impl FnOnce(&mut T, T)) {
+    match (total.as_mut(), value) {
+        (Some(total), Some(value)) => merge(total, value),
+        (None, value) => *total = value,
+        (Some(_), None) => {}
+    }
+}
+
+/// Measures a block of `repeats` repeats of a single variant at the given step size, after the
+/// warmup, adding them to `measured`. The harness overhead is calibrated in the first block only
+#[allow(clippy::too_many_arguments)]
+fn measure_repeats(
+    args: &TestArgs,
+    monitors: &Monitors,
+    cores: &[usize],
+    mem: &mut [u8],
+    private: &[Buffer],
+    step_size: usize,
+    variant: Variant,
+    repeats: usize,
+    rng: &mut impl Rng,
+    measured: &mut Measured,
+) -> Result<(), Box<dyn Error>> {
+    let first = measured.latencies.is_empty();
+    if first {
+        measured.start_time = unix_millis();
+    }
+    if args.warmup_iterations > 0 {
+        run_variant(args, mem, step_size, variant, args.warmup_iterations, rng)?;
+    }
+    if first && args.calibrate && variant.pattern != Pattern::Cycle {
+        let overhead = Variant {
+            overhead: true,
+            ..variant
+        };
+        let (_, overhead_iterations, overhead_duration) =
+            run_budgeted(args, mem, step_size, overhead, None, rng)?;
+        let overhead_ns = overhead_duration.as_nanos() as f64 / overhead_iterations as f64;
+        println!("\rMeasured a harness overhead of {overhead_ns:.2} ns/access");
+        measured.overhead_ns = Some(overhead_ns);
+    }
+    let counters = monitors.counters.as_ref();
+    if let Some(counters) = counters {
+        counters.reset()?;
+    }
+    start_recording(args.timer, args.histogram_batch, args.samples);
+    let sampler = args
+        .sample_frequency
+        .then(FrequencySampler::start)
+        .transpose()?;
+    let thermal_sampler = monitors.thermal.as_ref().map(Thermal::start).transpose()?;
+    let paging_start = Paging::now();
+    let energy_start = monitors.rapl.as_ref().map(Rapl::read).transpose()?;
+    // Carry the checksum on from earlier blocks, so it covers every position timed in order
+    let checksum = args.checksum || args.verify.is_some();
+    INDEX_CHECKSUM.set(checksum.then(|| measured.index_checksum.unwrap_or(CHECKSUM_START)));
+    for _ in 0..repeats {
+        let (repeat_sum, repeat_iterations, repeat_duration) = if variant.threads > 1 {
+            let ((sum, iterations, duration), runs) =
+                run_threads(args, cores, mem, private, step_size, variant, counters, rng)?;
+            if measured.threads.is_empty() {
+                measured.threads = runs;
+            } else {
+                for (total, run) in measured.threads.iter_mut().zip(runs) {
+                    total.iterations += run.iterations;
+                    total.duration += run.duration;
+                    total.core = run.core;
+                }
+            }
+            (sum, iterations, duration)
+        } else {
+            run_budgeted(args, mem, step_size, variant, counters, rng)?
+        };
+        measured.sum = measured.sum.wrapping_add(repeat_sum);
+        measured.iterations += repeat_iterations;
+        measured.total_duration += repeat_duration;
+        measured
+            .latencies
+            .push(repeat_duration.as_nanos() as f64 / repeat_iterations as f64);
+    }
+    measured.index_checksum = INDEX_CHECKSUM.take();
+    let energy = monitors
+        .rapl
+        .as_ref()
+        .zip(energy_start.as_ref())
+        .map(|(rapl, start)| rapl.since(start))
+        .transpose()?;
+    accumulate(&mut measured.energy, energy, Energy::merge);
+    accumulate(
+        &mut measured.paging,
+        Some(paging_start.since()),
+        Paging::merge,
+    );
+    accumulate(
+        &mut measured.recorded,
+        Some(finish_recording()),
+        Recorded::merge,
+    );
+    let thermal_report = thermal_sampler
+        .map(|sampler| sampler.finish())
+        .transpose()?;
+    accumulate(
+        &mut measured.thermal_report,
+        thermal_report,
+        ThermalReport::merge,
+    );
+    if let Some(sampler) = sampler {
+        measured.frequency_samples.extend(sampler.finish());
+    }
+    let counts = counters.map(Counters::read).transpose()?;
+    accumulate(&mut measured.counts, counts, Counts::merge);
+    Ok(())
+}
+
+/// Prints the results of a single variant at a single step size, once every repeat has been
+/// measured, returning its record and those of each of its threads and samples
+fn report(
+    args: &TestArgs,
+    placement: &StepPlacement,
+    step_size: usize,
+    variant: Variant,
+    seed: u64,
+    measured: Measured,
+) -> Result<(Record, Vec<Record>), Box<dyn Error>> {
+    let Measured {
+        start_time,
+        sum,
+        index_checksum,
+        iterations,
+        total_duration,
+        latencies,
+        overhead_ns,
+        recorded,
+        frequency_samples,
+        thermal_report,
+        energy,
+        paging,
+        counts,
+        threads,
+    } = measured;
+    let paging = paging.expect("every step is measured at least once");
+    let throttled = thermal_report.map(|report| {
+        report.throttle_events.is_some_and(|events| events > 0)
+            || report
+                .max_temperature_c
+                .is_some_and(|celsius| celsius >= args.throttle_temperature)
+    });
+    let frequency = (!frequency_samples.is_empty()).then(|| Stats::of(&frequency_samples, 0.0));
+    let percentile_ns = recorded.as_ref().and_then(Recorded::percentile_ns);
+    let cycles_per_access = recorded
+        .as_ref()
+        .and_then(Recorded::cycles)
+        .map(|cycles| cycles as f64 / iterations as f64);
+    let mut latency_ns = total_duration.as_nanos() as f64 / iterations as f64;
+    let subtracted = match overhead_ns {
+        Some(overhead_ns) if args.subtract_overhead => {
+            if latencies.iter().all(|&latency| latency > overhead_ns) {
+                Some(overhead_ns)
+            } else {
+                println!("\rMeasured no slower than the harness overhead, so it isn't subtracted");
+                None
+            }
+        }
+        _ => None,
+    };
+    if let Some(overhead_ns) = subtracted {
+        latency_ns -= overhead_ns;
+    }
+    let rates: Vec<f64> = latencies
+        .iter()
+        .map(|latency| 1e9 / (latency - subtracted.unwrap_or(0.0)))
+        .collect();
+    let rates = Stats::of(&rates, args.trim);
+    let total_duration_float = total_duration.as_secs_f32();
+    let steps_per_second = rates.aggregate(args.aggregate) as f32;
+    let bytes_per_step = if variant.pattern == Pattern::Cycle {
+        size_of::<usize>()
+    } else {
+        variant.element_size
+    };
+    let gigabytes_per_second = steps_per_second * bytes_per_step as f32 / 1e9;
+    println!(
+        "\rCompleted testing: took {total_duration_float:.3} secs, with an average access rate of {steps_per_second:.2} steps/sec ({latency_ns:.2} ns/access). sum: {sum}"
+    );
+    if let Some(frequency) = frequency {
+        println!(
+            "Sampled an average cpu frequency of {:.0} MHz",
+            frequency.mean
+        );
+        if frequency.max - frequency.min > args.frequency_tolerance * frequency.mean {
+            println!(
+                "Warning: the cpu frequency varied from {:.0} to {:.0} MHz during this step",
+                frequency.min, frequency.max
+            );
+        }
+    }
+    if paging.minor_faults > 0 {
+        println!(
+            "Took {} minor page faults during this step",
+            paging.minor_faults
+        );
+    }
+    if paging.swapped() {
+        println!(
+            "Warning: took {} major page faults during this step, so it measured reading pages back from disk rather than the memory system",
+            paging.major_faults
+        );
+    }
+    if let Some(report) = thermal_report {
+        if let Some(celsius) = report.max_temperature_c {
+            println!("Reached a cpu temperature of {celsius:.1} °C");
+        }
+        if throttled == Some(true) {
+            match report.throttle_events {
+                Some(events) if events > 0 => println!(
+                    "Warning: the cpu was thermally throttled {events} times during this step"
+                ),
+                _ => println!(
+                    "Warning: the cpu reached {} °C during this step, and was likely thermally throttled",
+                    args.throttle_temperature
+                ),
+            }
+        }
+    }
+    let per_access = |count: u64| count as f64 / iterations as f64;
+    let ipc = counts
+        .filter(|counts| counts.cycles > 0)
+        .map(|counts| counts.instructions as f64 / counts.cycles as f64);
+    let nj_per_access = |joules: f64| joules * 1e9 / iterations as f64;
+    if let Some(energy) = energy {
+        print!(
+            "Consumed {:.3} J in the package ({:.2} nJ/access)",
+            energy.package_joules,
+            nj_per_access(energy.package_joules)
+        );
+        match energy.dram_joules {
+            Some(dram_joules) => println!(
+                " and {dram_joules:.3} J in DRAM ({:.2} nJ/access)",
+                nj_per_access(dram_joules)
+            ),
+            None => println!(),
+        }
+    }
+    if let Some(cycles_per_access) = cycles_per_access {
+        println!("Counted {cycles_per_access:.2} reference cycles/access");
+    }
+    let thread_rates: Vec<f32> = threads
+        .iter()
+        .map(|run| (run.iterations as f64 / run.duration.as_secs_f64()) as f32)
+        .collect();
+    if variant.threads > 1 {
+        println!(
+            "Each of the {} threads accessed {:.2} steps/sec",
+            variant.threads,
+            steps_per_second / variant.threads as f32
+        );
+        for (thread, (run, rate)) in threads.iter().zip(&thread_rates).enumerate() {
+            let core = run
+                .core
+                .map_or(String::new(), |core| format!(" on core {core}"));
+            println!("Thread {thread}{core} accessed {rate:.2} steps/sec");
+        }
+        let slowest = thread_rates.iter().copied().fold(f32::INFINITY, f32::min);
+        let fastest = thread_rates.iter().copied().fold(0.0, f32::max);
+        println!(
+            "The slowest thread ran at {:.1}% of the rate of the fastest",
+            slowest / fastest * 100.0
+        );
+    }
+    if args.repeats > 1 {
+        println!(
+            "Over {} of {} repeats: mean {:.2}, median {:.2}, stddev {:.2}, min {:.2}, max {:.2} steps/sec",
+            rates.kept,
+            args.repeats,
+            rates.mean,
+            rates.median,
+            rates.stddev,
+            rates.min,
+            rates.max
+        );
+    }
+    if let Some([p50, p90, p99, p999]) = percentile_ns {
+        println!(
+            "Batch latency percentiles: p50 {p50:.2}, p90 {p90:.2}, p99 {p99:.2}, p99.9 {p999:.2} ns/access"
+        );
+    }
+    if let Some(counts) = counts {
+        println!(
+            "Counted {} cycles, {} instructions, {} L1 data cache misses, {} last level cache misses and {} data TLB misses",
+            counts.cycles,
+            counts.instructions,
+            counts.l1d_misses,
+            counts.llc_misses,
+            counts.dtlb_misses
+        );
+        println!(
+            "Missed the L1 data cache on {:.4}, the last level cache on {:.4} and the data TLB on {:.4} of accesses",
+            per_access(counts.l1d_misses),
+            per_access(counts.llc_misses),
+            per_access(counts.dtlb_misses)
+        );
+        if let Some(stlb_misses) = counts.stlb_misses {
+            println!(
+                "Counted {stlb_misses} second level TLB misses, walking the page tables on {:.4} of accesses",
+                per_access(stlb_misses)
+            );
+        }
+        if let Some(ipc) = ipc {
+            println!("Retired {ipc:.2} instructions per cycle");
+        }
+    }
+    if let Some(index_checksum) = index_checksum {
+        println!("Checksum of the positions visited: {index_checksum}");
+    }
+    let record = Record {
+        start_time,
+        total_duration_millis: total_duration.as_millis(),
+        steps_per_second,
+        avg_latency_ns: Some(latency_ns),
+        mlock: Some(placement.options.mlock),
+        prefault: Some(placement.options.prefault),
+        init: Some(placement.options.init),
+        flush_batch: args.flush_batch.map(|batch| batch as u64),
+        gigabytes_per_second: Some(gigabytes_per_second),
+        iterations: Some(iterations as u64),
+        repeats: Some(args.repeats as u64),
+        repeats_kept: Some(rates.kept as u64),
+        interleaved: Some(args.interleave),
+        trim: Some(args.trim),
+        aggregate: Some(args.aggregate),
+        index_buffer: args.index_buffer.map(|entries| entries as u64),
+        overhead_ns,
+        histogram_batch: args.histogram_batch.map(|batch| batch as u64),
+        timer: Some(args.timer),
+        cycles_per_access,
+        frequency_mhz: frequency.map(|frequency| frequency.mean),
+        frequency_min_mhz: frequency.map(|frequency| frequency.min),
+        frequency_max_mhz: frequency.map(|frequency| frequency.max),
+        max_temperature_c: thermal_report.and_then(|report| report.max_temperature_c),
+        throttle_events: thermal_report.and_then(|report| report.throttle_events),
+        throttled,
+        package_joules: energy.map(|energy| energy.package_joules),
+        dram_joules: energy.and_then(|energy| energy.dram_joules),
+        package_nj_per_access: energy.map(|energy| nj_per_access(energy.package_joules)),
+        dram_nj_per_access: energy
+            .and_then(|energy| energy.dram_joules)
+            .map(nj_per_access),
+        p50_ns: percentile_ns.map(|[p50, ..]| p50),
+        p90_ns: percentile_ns.map(|[_, p90, ..]| p90),
+        p99_ns: percentile_ns.map(|[.., p99, _]| p99),
+        p999_ns: percentile_ns.map(|[.., p999]| p999),
+        overhead_subtracted: args.subtract_overhead.then_some(subtracted.is_some()),
+        warmup_iterations: Some(args.warmup_iterations as u64),
+        steps_per_second_mean: Some(rates.mean as f32),
+        steps_per_second_median: Some(rates.median as f32),
+        steps_per_second_stddev: Some(rates.stddev as f32),
+        steps_per_second_min: Some(rates.min as f32),
+        steps_per_second_max: Some(rates.max as f32),
+        cycles: counts.map(|counts| counts.cycles),
+        instructions: counts.map(|counts| counts.instructions),
+        l1d_misses: counts.map(|counts| counts.l1d_misses),
+        llc_misses: counts.map(|counts| counts.llc_misses),
+        dtlb_misses: counts.map(|counts| counts.dtlb_misses),
+        ipc,
+        minor_faults: Some(paging.minor_faults),
+        major_faults: Some(paging.major_faults),
+        swapped: Some(paging.swapped()),
+        swapped_pages: paging.swapped_pages,
+        memory_stall_us: paging.memory_stall_us,
+        stlb_misses: counts.and_then(|counts| counts.stlb_misses),
+        stlb_misses_per_access: counts.and_then(|counts| counts.stlb_misses).map(per_access),
+        l1d_misses_per_access: counts.map(|counts| per_access(counts.l1d_misses)),
+        llc_misses_per_access: counts.map(|counts| per_access(counts.llc_misses)),
+        dtlb_misses_per_access: counts.map(|counts| per_access(counts.dtlb_misses)),
+        sum: Some(sum),
+        index_checksum,
+        steps_per_second_per_thread: Some(steps_per_second / variant.threads as f32),
+        pinned_cores: (!placement.cores.is_empty()).then(|| {
+            let cores: Vec<String> = placement.cores[..variant.threads]
+                .iter()
+                .map(usize::to_string)
+                .collect();
+            cores.join(",")
+        }),
+        ..step_record(args, placement, step_size, variant, seed)
+    };
+    // Only what identifies the step is repeated in each thread's record, as everything else was
+    // measured across all of them
+    let thread_records =
+        threads
+            .iter()
+            .zip(thread_rates)
+            .enumerate()
+            .map(|(thread, (run, steps_per_second))| Record {
+                start_time,
+                total_duration_millis: run.duration.as_millis(),
+                steps_per_second,
+                avg_latency_ns: Some(1e9 / steps_per_second as f64),
+                gigabytes_per_second: Some(steps_per_second * bytes_per_step as f32 / 1e9),
+                iterations: Some(run.iterations as u64),
+                thread: Some(thread as u64),
+                core: run.core,
+                ..step_record(args, placement, step_size, variant, seed)
+            });
+    let samples = recorded.as_ref().map_or(&[][..], Recorded::samples);
+    if !samples.is_empty() {
+        let rates = samples.iter().map(Sample::steps_per_second);
+        let slowest = rates.clone().fold(f64::INFINITY, f64::min);
+        let fastest = rates.fold(0.0, f64::max);
+        println!(
+            "Sampled {} intervals, running from {slowest:.2} to {fastest:.2} steps/sec",
+            samples.len()
+        );
+    }
+    let sample_records = samples.iter().enumerate().map(|(sample, interval)| {
+        let steps_per_second = interval.steps_per_second() as f32;
+        Record {
+            start_time,
+            total_duration_millis: interval.duration.as_millis(),
+            steps_per_second,
+            avg_latency_ns: Some(1e9 / steps_per_second as f64),
+            gigabytes_per_second: Some(steps_per_second * bytes_per_step as f32 / 1e9),
+            iterations: Some(interval.steps as u64),
+            sample: Some(sample as u64),
+            sample_time_ms: Some(interval.time.as_secs_f64() * 1e3),
+            ..step_record(args, placement, step_size, variant, seed)
+        }
+    });
+    Ok((record, thread_records.chain(sample_records).collect()))
+}
+
+/// A record of what identifies a step, and would be measured if it were run, without any of its
+/// results
+fn step_record(
+    args: &TestArgs,
+    placement: &StepPlacement,
+    step_size: usize,
+    variant: Variant,
+    seed: u64,
+) -> Record {
+    Record {
+        step_size: step_size as u64,
+        pattern: Some(variant.pattern),
+        op: Some(variant.op),
+        direction: variant.direction,
+        branch: variant.branch,
+        sampling: (variant.pattern == Pattern::Random).then_some(args.sampling),
+        write_ratio: variant.write_ratio,
+        zipf_exponent: (variant.pattern == Pattern::Zipf).then_some(args.zipf_exponent),
+        sigma: (variant.pattern == Pattern::Gaussian).then_some(args.sigma),
+        hot_size: (variant.pattern == Pattern::HotCold).then_some(args.hot_size as u64),
+        hot_probability: (variant.pattern == Pattern::HotCold).then_some(args.hot_probability),
+        tile_accesses: matches!(variant.pattern, Pattern::Tiled | Pattern::RandomTiled)
+            .then_some(args.tile_accesses as u64),
+        burst_page: (variant.pattern == Pattern::Burst).then_some(args.burst_page as u64),
+        page_size: Some(placement.options.page_size.bytes() as u64),
+        memory_node: placement.options.node,
+        numa_policy: placement.options.numa_policy(),
+        backing: Some(placement.options.backing.to_string()),
+        cpu_node: placement.cpu_node,
+        total_size: Some(placement.total_size as u64),
+        element_size: Some(variant.element_size as u64),
+        vector_width: variant.vector.then_some(variant.element_size as u64),
+        seed: Some(seed),
+        threads: Some(variant.threads as u64),
+        thread_buffers: (variant.threads > 1).then_some(variant.buffers),
+        thread_placement: args.thread_placement,
+        ..Record::default()
+    }
+}
+
+/// Each of `items`, or just `None` when there aren't any
+fn optional_list<T: Copy>(items: &[T]) -> Vec<Option<T>> {
+    if items.is_empty() {
+        vec![None]
+    } else {
+        items.iter().copied().map(Some).collect()
+    }
+}
+
+/// The cores of the given NUMA node that this process may run on, failing if there are none
+fn available_node_cores(node: usize) -> Result<Vec<usize>, Box<dyn Error>> {
+    let available = available_cores()?;
+    let cores: Vec<usize> = node_cores(node)?
+        .into_iter()
+        .filter(|core| available.contains(core))
+        .collect();
+    if cores.is_empty() {
+        return Err(format!("none of the cores of NUMA node {node} are available").into());
+    }
+    Ok(cores)
+}
+
+/// Checks that the vector loads requested by --simd can run on this machine
+fn check_simd(args: &TestArgs) -> Result<(), Box<dyn Error>> {
+    if cfg!(not(target_arch = "x86_64")) {
+        return Err("--simd is only supported on x86_64".into());
+    }
+    if args.op.iter().any(|&op| op != Op::Read) {
+        return Err("--simd is only supported by the read operation".into());
+    }
+    #[cfg(target_arch = "x86_64")]
+    for &element_size in &args.element_size {
+        let (feature, supported) = match element_size {
+            32 => ("AVX", is_x86_feature_detected!("avx")),
+            64 => ("AVX-512", is_x86_feature_detected!("avx512f")),
+            _ => continue,
+        };
+        if !supported {
+            return Err(format!(
+                "{element_size}-byte vector loads need {feature}, which this cpu doesn't support"
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Step sizes swept over a buffer of `total_size` bytes
+fn step_sizes(args: &TestArgs, total_size: usize) -> Vec<usize> {
+    match args.sweep {
+        Sweep::StepSize | Sweep::Both => {
+            let max_step_size = args
+                .max_step_size
+                .unwrap_or(total_size)
+                .clamp(args.initial_step_size, total_size);
+            doublings(args.initial_step_size, max_step_size)
+        }
+        Sweep::TotalSize | Sweep::Threads => vec![args.initial_step_size.min(total_size)],
+    }
+}
+
+/// Powers of two times `start`, up to and including `end`
+fn doublings(start: usize, end: usize) -> Vec<usize> {
+    std::iter::successors(Some(start), |&size| size.checked_mul(2))
+        .take_while(|&size| size <= end)
+        .collect()
+}
+
+/// Measures a single variant at the given step size for --iterations, or in batches until
+/// --duration-per-step has passed, returning the number of iterations run along with the sum and
+/// the time taken
+fn run_budgeted<M: Memory + ?Sized>(
+    args: &TestArgs,
+    mem: &mut M,
+    step_size: usize,
+    variant: Variant,
+    counters: Option<&Counters>,
+    rng: &mut impl Rng,
+) -> Result<(u64, usize, Duration), Box<dyn Error>> {
+    let Some(budget) = args.duration_per_step else {
+        let (mut sum, mut duration) = run_flushed(
+            args,
+            mem,
+            step_size,
+            variant,
+            args.iterations,
+            counters,
+            rng,
+        )?;
+        let mut iterations = args.iterations;
+        let minimum = args.min_duration_per_step.unwrap_or_default();
+        while duration < minimum {
+            // Scale up to about the rest of the minimum at the rate so far, with some headroom so
+            // a noisy batch rarely leaves it just short
+            let rate = iterations as f64 / duration.as_secs_f64().max(1e-9);
+            let batch = ((minimum - duration).as_secs_f64() * rate * 1.1)
+                .max(MIN_TIMED_BATCH as f64) as usize;
+            let (batch_sum, batch_duration) =
+                run_flushed(args, mem, step_size, variant, batch, counters, rng)?;
+            sum = sum.wrapping_add(batch_sum);
+            iterations += batch;
+            duration += batch_duration;
+        }
+        return Ok((sum, iterations, duration));
+    };
+    let (mut sum, mut iterations, mut total_duration) = (0u64, 0, Duration::ZERO);
+    let mut batch = MIN_TIMED_BATCH;
+    let start_instant = Instant::now();
+    loop {
+        let (batch_sum, batch_duration) =
+            run_flushed(args, mem, step_size, variant, batch, counters, rng)?;
+        sum = sum.wrapping_add(batch_sum);
+        iterations += batch;
+        total_duration += batch_duration;
+        // Compare wall clock time rather than the time measured, so that untimed flushes count
+        // against the budget too
+        let elapsed = start_instant.elapsed();
+        if elapsed >= budget {
+            return Ok((sum, iterations, total_duration));
+        }
+        // Size the next batch to about fill the rest of the budget at the rate so far
+        let rate = iterations as f64 / elapsed.as_secs_f64();
+        batch = ((budget - elapsed).as_secs_f64() * rate).max(MIN_TIMED_BATCH as f64) as usize;
+    }
+}
+
+/// Measures a single variant at the given step size, in batches separated by untimed cache
+/// flushes when --flush-batch is given. `counters` only count while timing
+fn run_flushed<M: Memory + ?Sized>(
+    args: &TestArgs,
+    mem: &mut M,
+    step_size: usize,
+    variant: Variant,
+    iterations: usize,
+    counters: Option<&Counters>,
+    rng: &mut impl Rng,
+) -> Result<(u64, Duration), Box<dyn Error>> {
+    let mut counted = |mem: &mut M, iterations| {
+        if let Some(counters) = counters {
+            counters.enable()?;
+        }
+        let result = run_variant(args, mem, step_size, variant, iterations, rng);
+        if let Some(counters) = counters {
+            counters.disable()?;
+        }
+        result
+    };
+    let Some(batch) = args.flush_batch else {
+        return counted(mem, iterations);
+    };
+    let (mut sum, mut total_duration) = (0u64, Duration::ZERO);
+    let mut remaining = iterations;
+    while remaining > 0 {
+        let iterations = batch.min(remaining);
+        flush_cache(mem.bytes());
+        let (batch_sum, batch_duration) = counted(mem, iterations)?;
+        sum = sum.wrapping_add(batch_sum);
+        total_duration += batch_duration;
+        remaining -= iterations;
+    }
+    Ok((sum, total_duration))
+}
+
+/// Evicts every line of `mem` from all levels of the cache
+#[cfg(target_arch = "x86_64")]
+fn flush_cache(mem: &[u8]) {
+    use std::arch::x86_64::{_mm_clflush, _mm_mfence};
+
+    for line in mem.chunks(CACHE_LINE) {
+        // SAFETY: clflush only needs a valid address, and x86_64 always supports it
+        unsafe { _mm_clflush(line.as_ptr()) };
+    }
+    // SAFETY: as above
+    unsafe { _mm_mfence() };
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn flush_cache(_mem: &[u8]) {
+    unreachable!("--flush-batch is rejected up front on other architectures")
+}
+
+/// Measures a single variant at the given step size
+fn run_variant<M: Memory + ?Sized>(
+    args: &TestArgs,
+    mem: &mut M,
+    step_size: usize,
+    variant: Variant,
+    iterations: usize,
+    rng: &mut impl Rng,
+) -> Result<(u64, Duration), Box<dyn Error>> {
+    let total_size = mem.bytes().len();
+    let variant = Variant {
+        store_seed: rng.gen(),
+        ..variant
+    };
+    // Sampling from a distribution set up once avoids the division gen_range does on every call
+    let steps = Uniform::new(0, step_size);
+    Ok(match variant.pattern {
+        Pattern::Random => match (variant.branch.unwrap_or(Branch::Branchless), args.sampling) {
+            (Branch::Branchless, Sampling::Unbiased) => {
+                walk(mem, iterations, variant, |position| {
+                    let step = rng.sample(steps);
+                    // As below; adding the total size first keeps the sum from ever going below
+                    // zero, since the step is less than it
+                    let down = (rng.gen::<bool>() as usize).wrapping_neg();
+                    (position + total_size).wrapping_add((step ^ down).wrapping_sub(down))
+                        % total_size
+                })
+            }
+            (Branch::Branchy, Sampling::Unbiased) => walk(mem, iterations, variant, |position| {
+                let step = rng.sample(steps);
+                // As below
+                let position = if rng.gen() {
+                    black_box(position + step)
+                } else {
+                    black_box(position + total_size - step)
+                };
+                position % total_size
+            }),
+            (Branch::Branchless, Sampling::Modulo) => walk(mem, iterations, variant, |position| {
+                let step: usize = rng.gen();
+                let step = step % step_size;
+                // All ones to move down, or all zeroes to move up; xoring by it and subtracting
+                // it negates the step or leaves it as is
+                let down = (rng.gen::<bool>() as usize).wrapping_neg();
+                position.wrapping_add((step ^ down).wrapping_sub(down)) % total_size
+            }),
+            (Branch::Branchy, Sampling::Modulo) => walk(mem, iterations, variant, |position| {
+                let step: usize = rng.gen();
+                let step = step % step_size;
+                // Hiding the results from the optimizer stops it turning the branch into a
+                // conditional move
+                let position = if rng.gen() {
+                    black_box(position.wrapping_add(step))
+                } else {
+                    black_box(position.wrapping_sub(step))
+                };
+                position % total_size
+            }),
+        },
+        Pattern::Sequential | Pattern::Stride => {
+            let step = if variant.pattern == Pattern::Sequential {
+                variant.element_size
+            } else {
+                step_size
+            };
+            match variant.direction.unwrap_or(Direction::Forward) {
+                Direction::Forward => walk(mem, iterations, variant, |position| {
+                    let position = position + step;
+                    if position >= total_size {
+                        position - total_size
+                    } else {
+                        position
+                    }
+                }),
+                Direction::Backward => walk(mem, iterations, variant, |position| {
+                    if position < step {
+                        position + total_size - step
+                    } else {
+                        position - step
+                    }
+                }),
+            }
+        }
+        Pattern::Zipf => {
+            let zipf = Zipf::new(total_size as u64, args.zipf_exponent)?;
+            walk(mem, iterations, variant, |_| {
+                let rank = rng.sample(zipf) as u128 - 1;
+                ((rank * ZIPF_SCATTER) % total_size as u128) as usize
+            })
+        }
+        Pattern::Gaussian => {
+            let normal = Normal::new(0.0, args.sigma * step_size as f64)?;
+            walk(mem, iterations, variant, |position| {
+                let offset = rng.sample(normal).round() as isize;
+                (position as isize + offset).rem_euclid(total_size as isize) as usize
+            })
+        }
+        Pattern::HotCold => {
+            if args.hot_size >= total_size {
+                return Err(format!(
+                    "--hot-size must be less than the {total_size} byte buffer size"
+                )
+                .into());
+            }
+            let hot = Bernoulli::new(args.hot_probability)?;
+            let cold_size = step_size.min(total_size - args.hot_size);
+            walk(mem, iterations, variant, |_| {
+                if rng.sample(hot) {
+                    rng.gen_range(0..args.hot_size)
+                } else {
+                    args.hot_size + rng.gen_range(0..cold_size)
+                }
+            })
+        }
+        Pattern::Uniform => walk(mem, iterations, variant, |_| rng.gen_range(0..total_size)),
+        Pattern::Tiled | Pattern::RandomTiled => {
+            let blocks = total_size.div_ceil(step_size);
+            let (mut block, mut accesses) = (0, 0);
+            walk(mem, iterations, variant, |_| {
+                if accesses == args.tile_accesses {
+                    accesses = 0;
+                    block = if variant.pattern == Pattern::Tiled {
+                        (block + 1) % blocks
+                    } else {
+                        rng.gen_range(0..blocks)
+                    };
+                }
+                accesses += 1;
+                let start = block * step_size;
+                start + rng.gen_range(0..step_size.min(total_size - start))
+            })
+        }
+        Pattern::Burst => {
+            let pages = (total_size / args.burst_page).max(1);
+            let mut remaining = 0;
+            walk(mem, iterations, variant, |position| {
+                if remaining >= variant.element_size {
+                    remaining -= variant.element_size;
+                    let position = position + variant.element_size;
+                    if position >= total_size {
+                        0
+                    } else {
+                        position
+                    }
+                } else {
+                    remaining = step_size.saturating_sub(variant.element_size);
+                    rng.gen_range(0..pages) * args.burst_page
+                }
+            })
+        }
+        Pattern::Cycle => {
+            let spacing = step_size.next_multiple_of(size_of::<usize>());
+            if spacing > total_size {
+                return Err(format!(
+                    "the cycle pattern needs a buffer of at least {spacing} bytes"
+                )
+                .into());
+            }
+            link_sattolo_cycle(mem.bytes_mut(), spacing, rng);
+            let mut offset = 0;
+            let total_duration = match INDEX_CHECKSUM.get() {
+                Some(mut checksum) => {
+                    let total_duration = measure(iterations, || {
+                        offset = read_offset(mem.bytes(), offset);
+                        checksum = fold_checksum(checksum, offset as u64);
+                    });
+                    INDEX_CHECKSUM.set(Some(checksum));
+                    total_duration
+                }
+                None => measure(iterations, || offset = read_offset(mem.bytes(), offset)),
+            };
+            (offset as u64, total_duration)
+        }
+    })
+}
+
+/// Links the positions `spacing` bytes apart in `mem` into a single random cycle, by running
+/// Sattolo's algorithm over the offsets stored at each of them. Afterwards each position holds
+/// the offset of the next one in the cycle.
+fn link_sattolo_cycle(mem: &mut [u8], spacing: usize, rng: &mut impl Rng) {
+    let nodes = mem.len() / spacing;
+    for node in 0..nodes {
+        write_offset(mem, node * spacing, node * spacing);
+    }
+    for node in (1..nodes).rev() {
+        let other = rng.gen_range(0..node);
+        let (a, b) = (
+            read_offset(mem, node * spacing),
+            read_offset(mem, other * spacing),
+        );
+        write_offset(mem, node * spacing, b);
+        write_offset(mem, other * spacing, a);
+    }
+}
+
+/// Reads the offset stored at `position` in a cycle written by `link_sattolo_cycle`
+fn read_offset(mem: &[u8], position: usize) -> usize {
+    usize::from_ne_bytes(
+        mem[position..position + size_of::<usize>()]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+fn write_offset(mem: &mut [u8], position: usize, offset: usize) {
+    mem[position..position + size_of::<usize>()].copy_from_slice(&offset.to_ne_bytes());
+}
+
+/// Memory a step runs over: a buffer of its own, or a view of one shared between threads that's
+/// only ever read
+trait Memory {
+    fn bytes(&self) -> &[u8];
+
+    /// The bytes to store to, which only the operations that store and the cycle pattern ask for
+    fn bytes_mut(&mut self) -> &mut [u8];
+}
+
+impl Memory for [u8] {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+/// The buffer as seen by one of the threads of --threads
+struct Shared<'a>(&'a [u8]);
+
+impl Memory for Shared<'_> {
+    fn bytes(&self) -> &[u8] {
+        self.0
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        unreachable!("stores and the cycle pattern are rejected up front with --threads")
+    }
+}
+
+/// Runs a single repeat on every thread of the variant at once, all over the same buffer. Returns
+/// the wrapping sum of every thread's sum and the iterations run by all of them, along with the
+/// time those iterations take at the threads' combined rate, and what each thread ran. The
+/// calling thread is the first, and the only one `counters` counts or `measure` records and
+/// prints. With private buffers, each thread after the first walks the next of `private`
+#[allow(clippy::too_many_arguments)]
+fn run_threads(
+    args: &TestArgs,
+    cores: &[usize],
+    mem: &[u8],
+    private: &[Buffer],
+    step_size: usize,
+    variant: Variant,
+    counters: Option<&Counters>,
+    rng: &mut impl Rng,
+) -> Result<(Walked, Vec<ThreadRun>), Box<dyn Error>> {
+    let seeds: Vec<u64> = (1..variant.threads).map(|_| rng.gen()).collect();
+    let checksum = INDEX_CHECKSUM.get().is_some();
+    let barrier = Barrier::new(variant.threads);
+    let (first, others) = thread::scope(|scope| {
+        let handles: Vec<_> = seeds
+            .into_iter()
+            .zip(1..)
+            .map(|(seed, thread)| {
+                let barrier = &barrier;
+                let mem = match variant.buffers {
+                    ThreadBuffers::Shared => mem,
+                    ThreadBuffers::Private => &private[thread - 1][..],
+                };
+                scope.spawn(move || {
+                    quiet();
+                    // Threads start out with the affinity of the one that spawned them, so each
+                    // has to be pinned to a core of its own
+                    let pinned = cores.get(thread).map(|&core| pin_to_core(core));
+                    INDEX_CHECKSUM.set(checksum.then_some(CHECKSUM_START));
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    barrier.wait();
+                    // Errors can't be sent between threads, but their messages can
+                    pinned.transpose().map_err(|err| err.to_string())?;
+                    let result =
+                        run_budgeted(args, &mut Shared(mem), step_size, variant, None, &mut rng)
+                            .map_err(|err| err.to_string());
+                    result.map(|result| (result, INDEX_CHECKSUM.get(), current_core()))
+                })
+            })
+            .collect();
+        barrier.wait();
+        let first = run_budgeted(args, &mut Shared(mem), step_size, variant, counters, rng)
+            .map(|result| (result, current_core()));
+        let others: Vec<_> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        (first, others)
+    });
+    let ((mut sum, mut iterations, duration), core) = first?;
+    let mut rate = iterations as f64 / duration.as_secs_f64();
+    let mut runs = vec![ThreadRun {
+        iterations,
+        duration,
+        core,
+    }];
+    for other in others {
+        let ((other_sum, other_iterations, other_duration), other_checksum, other_core) = other?;
+        runs.push(ThreadRun {
+            iterations: other_iterations,
+            duration: other_duration,
+            core: other_core,
+        });
+        sum = sum.wrapping_add(other_sum);
+        iterations += other_iterations;
+        rate += other_iterations as f64 / other_duration.as_secs_f64();
+        if let (Some(checksum), Some(other_checksum)) = (INDEX_CHECKSUM.get(), other_checksum) {
+            INDEX_CHECKSUM.set(Some(fold_checksum(checksum, other_checksum)));
+        }
+    }
+    let duration = Duration::from_secs_f64(iterations as f64 / rate);
+    Ok(((sum, iterations, duration), runs))
+}
+
+/// Wrapping sum of the elements walked, the iterations run and the time they took
+type Walked = (u64, usize, Duration);
+
+thread_local! {
+    /// Checksum of every position walked on this thread, while a step is being checksummed
+    static INDEX_CHECKSUM: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Checksums start from a nonzero offset, so that visiting only position 0 still changes them
+const CHECKSUM_START: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Folds a value into a checksum. Multiplying after each value makes the checksum depend on their
+/// order, unlike a sum
+fn fold_checksum(checksum: u64, value: u64) -> u64 {
+    (checksum.rotate_left(5) ^ value).wrapping_mul(0x517c_c1b7_2722_0a95)
+}
+
+/// Performs the variant's operation at `iterations` positions in `mem`, starting at position 0
+/// and choosing each following position with `next`. Returns the wrapping sum of all elements
+/// loaded (or, for plain writes, the count of elements written) along with the time taken. With
+/// an index buffer, positions are chosen in untimed batches and only replayed while timing.
+/// Positions are also folded into the checksum, if one is running
+fn walk<M: Memory + ?Sized>(
+    mem: &mut M,
+    iterations: usize,
+    variant: Variant,
+    mut next: impl FnMut(usize) -> usize,
+) -> (u64, Duration) {
+    let Some(mut checksum) = INDEX_CHECKSUM.get() else {
+        return walk_positions(mem, iterations, variant, next);
+    };
+    let result = walk_positions(mem, iterations, variant, |position| {
+        let position = next(position);
+        checksum = fold_checksum(checksum, position as u64);
+        position
+    });
+    INDEX_CHECKSUM.set(Some(checksum));
+    result
+}
+
+/// `walk`, without checksumming
+fn walk_positions<M: Memory + ?Sized>(
+    mem: &mut M,
+    iterations: usize,
+    variant: Variant,
+    mut next: impl FnMut(usize) -> usize,
+) -> (u64, Duration) {
+    let Some(entries) = variant.index_buffer else {
+        return walk_direct(mem, iterations, variant, next);
+    };
+    let mut indices = Vec::with_capacity(entries.min(iterations));
+    let (mut sum, mut total_duration) = (0u64, Duration::ZERO);
+    let mut position = 0;
+    let mut remaining = iterations;
+    while remaining > 0 {
+        indices.clear();
+        for _ in 0..entries.min(remaining) {
+            position = next(position);
+            indices.push(position);
+        }
+        let mut replay = indices.iter();
+        let (batch_sum, batch_duration) =
+            walk_direct(mem, indices.len(), variant, |_| *replay.next().unwrap());
+        sum = sum.wrapping_add(batch_sum);
+        total_duration += batch_duration;
+        remaining -= indices.len();
+    }
+    (sum, total_duration)
+}
+
+/// `walk`, choosing each position with `next` while timing
+fn walk_direct<M: Memory + ?Sized>(
+    mem: &mut M,
+    iterations: usize,
+    variant: Variant,
+    next: impl FnMut(usize) -> usize,
+) -> (u64, Duration) {
+    if variant.overhead {
+        return walk_overhead(iterations, next);
+    }
+    #[cfg(target_arch = "x86_64")]
+    if variant.vector {
+        return match variant.element_size {
+            16 => walk_vectors::<16>(mem.bytes(), iterations, next),
+            32 => walk_vectors::<32>(mem.bytes(), iterations, next),
+            _ => walk_vectors::<64>(mem.bytes(), iterations, next),
+        };
+    }
+    match variant.element_size {
+        1 => walk_elements::<u8>(mem, iterations, variant, next),
+        2 => walk_elements::<u16>(mem, iterations, variant, next),
+        4 => walk_elements::<u32>(mem, iterations, variant, next),
+        8 => walk_elements::<u64>(mem, iterations, variant, next),
+        16 => walk_elements::<[u64; 2]>(mem, iterations, variant, next),
+        32 => walk_elements::<[u64; 4]>(mem, iterations, variant, next),
+        _ => walk_elements::<[u64; 8]>(mem, iterations, variant, next),
+    }
+}
+
+/// `walk` without any memory accesses, running only the harness around them: choosing each
+/// position, and summing the positions instead of the elements there. The mixed operation's
+/// choice of whether to store isn't included
+fn walk_overhead(iterations: usize, mut next: impl FnMut(usize) -> usize) -> (u64, Duration) {
+    let mut sum: u64 = 0;
+    let mut position: usize = 0;
+    let total_duration = measure(iterations, || {
+        position = next(position);
+        sum = black_box(sum.wrapping_add(position as u64));
+    });
+    (sum, total_duration)
+}
+
+/// A value loaded or stored as a single access
+trait Element: Copy {
+    /// Combines the bits of the value into one number, so that none of the load can be skipped
+    fn fold(self) -> u64;
+
+    /// A value to store, derived from `value`
+    fn from_sum(value: u64) -> Self;
+
+    /// The value plus one
+    fn increment(self) -> Self;
+}
+
+macro_rules! impl_element {
+    ($($t:ty),*) => {
+        $(
+            impl Element for $t {
+                fn fold(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_sum(value: u64) -> Self {
+                    value as $t
+                }
+
+                fn increment(self) -> Self {
+                    self.wrapping_add(1)
+                }
+            }
+        )*
+    };
+}
+
+impl_element!(u8, u16, u32, u64);
+
+/// A run of 8-byte words, up to a whole cache line
+impl<const N: usize> Element for [u64; N] {
+    fn fold(self) -> u64 {
+        self.into_iter().fold(0, u64::wrapping_add)
+    }
+
+    fn from_sum(value: u64) -> Self {
+        [value; N]
+    }
+
+    fn increment(self) -> Self {
+        self.map(|word| word.wrapping_add(1))
+    }
+}
+
+/// Loads the element of type `E` containing `position`
+fn load<E: Element>(mem: &[u8], position: usize) -> E {
+    let start = position & !(size_of::<E>() - 1);
+    let bytes = &mem[start..start + size_of::<E>()];
+    // SAFETY: the slice above is exactly the size of an `E`, and every bit pattern is a valid `E`
+    unsafe { bytes.as_ptr().cast::<E>().read_unaligned() }
+}
+
+/// Stores `value` into the element of type `E` containing `position`
+fn store<E: Element>(mem: &mut [u8], position: usize, value: E) {
+    let start = position & !(size_of::<E>() - 1);
+    let bytes = &mut mem[start..start + size_of::<E>()];
+    // SAFETY: as for `load`
+    unsafe { bytes.as_mut_ptr().cast::<E>().write_unaligned(value) }
+}
+
+/// Loads the element of type `E` containing `position` again, a byte at a time with volatile
+/// reads the optimizer must keep, to check the timed loop against in debug builds
+fn load_volatile<E: Element>(mem: &[u8], position: usize) -> E {
+    let start = position & !(size_of::<E>() - 1);
+    let mut bytes = [0u8; 64];
+    for (byte, source) in bytes.iter_mut().zip(&mem[start..start + size_of::<E>()]) {
+        // SAFETY: `source` is a reference, so it's valid and aligned for a read
+        *byte = unsafe { std::ptr::read_volatile(source) };
+    }
+    // SAFETY: as for `load`, and `bytes` is at least as large as any `E`
+    unsafe { bytes.as_ptr().cast::<E>().read_unaligned() }
+}
+
+/// `walk` with elements of type `E`. Loaded values and the running sum pass through `black_box`,
+/// so the optimizer can't elide any of the accesses however well it understands the loop. Debug
+/// builds also check every access: reads are summed a second time with volatile loads, and stores
+/// are read back, so a loop that was miscompiled or partially skipped fails loudly instead of
+/// reporting garbage
+fn walk_elements<E: Element>(
+    mem: &mut (impl Memory + ?Sized),
+    iterations: usize,
+    variant: Variant,
+    mut next: impl FnMut(usize) -> usize,
+) -> (u64, Duration) {
+    let mut sum: u64 = 0;
+    let mut expected: u64 = 0;
+    let mut position: usize = 0;
+    let total_duration = match variant.op {
+        Op::Read => measure(iterations, || {
+            position = next(position);
+            let value = black_box(load::<E>(mem.bytes(), position)).fold();
+            sum = black_box(sum.wrapping_add(value));
+            if cfg!(debug_assertions) {
+                expected = expected.wrapping_add(load_volatile::<E>(mem.bytes(), position).fold());
+            }
+        }),
+        Op::Write => measure(iterations, || {
+            position = next(position);
+            sum = black_box(sum.wrapping_add(1));
+            store(mem.bytes_mut(), position, E::from_sum(sum));
+            if cfg!(debug_assertions) {
+                let stored = load_volatile::<E>(mem.bytes(), position).fold();
+                assert_eq!(
+                    stored,
+                    E::from_sum(sum).fold(),
+                    "store to {position} was lost"
+                );
+            }
+        }),
+        Op::Rmw => measure(iterations, || {
+            position = next(position);
+            let value = black_box(load::<E>(mem.bytes(), position)).increment();
+            store(mem.bytes_mut(), position, value);
+            sum = black_box(sum.wrapping_add(value.fold()));
+            if cfg!(debug_assertions) {
+                let stored = load_volatile::<E>(mem.bytes(), position).fold();
+                assert_eq!(stored, value.fold(), "store to {position} was lost");
+            }
+        }),
+        Op::Mixed => {
+            let mut rng = StdRng::seed_from_u64(variant.store_seed);
+            let store_probability =
+                Bernoulli::new(variant.write_ratio.unwrap_or_default()).unwrap();
+            measure(iterations, || {
+                position = next(position);
+                if rng.sample(store_probability) {
+                    store(mem.bytes_mut(), position, E::from_sum(sum));
+                } else {
+                    let value = black_box(load::<E>(mem.bytes(), position)).fold();
+                    sum = black_box(sum.wrapping_add(value));
+                    if cfg!(debug_assertions) {
+                        expected =
+                            expected.wrapping_add(load_volatile::<E>(mem.bytes(), position).fold());
+                    }
+                }
+            })
+        }
+    };
+    if cfg!(debug_assertions) && matches!(variant.op, Op::Read | Op::Mixed) {
+        assert_eq!(sum, expected, "checksum of the loads doesn't match");
+    }
+    (sum, total_duration)
+}
+
+/// Reads the `WIDTH` byte element containing each of `iterations` positions in `mem` with a single
+/// vector load, choosing positions as in `walk`. The loads are made through inline assembly so the
+/// compiler can neither split nor skip them, which means nothing is summed; returns the number of
+/// loads made along with the time taken.
+#[cfg(target_arch = "x86_64")]
+fn walk_vectors<const WIDTH: usize>(
+    mem: &[u8],
+    iterations: usize,
+    mut next: impl FnMut(usize) -> usize,
+) -> (u64, Duration) {
+    use std::arch::asm;
+
+    let mut count: u64 = 0;
+    let mut position: usize = 0;
+    let total_duration = measure(iterations, || {
+        position = next(position);
+        let start = position & !(WIDTH - 1);
+        let bytes = &mem[start..start + WIDTH];
+        // SAFETY: the load reads exactly the bytes of the slice above, and `check_simd` has made
+        // sure the cpu supports it
+        unsafe {
+            match WIDTH {
+                16 => {
+                    asm!("movdqu xmm0, [{}]", in(reg) bytes.as_ptr(), out("xmm0") _, options(nostack, readonly))
+                }
+                32 => {
+                    asm!("vmovdqu ymm0, [{}]", in(reg) bytes.as_ptr(), out("ymm0") _, options(nostack, readonly))
+                }
+                _ => {
+                    asm!("vmovdqu64 zmm0, [{}]", in(reg) bytes.as_ptr(), out("zmm0") _, options(nostack, readonly))
+                }
+            }
+        }
+        count += 1;
+    });
+    (count, total_duration)
+}
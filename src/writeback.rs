@@ -0,0 +1,133 @@
+use crate::{
+    chase::CACHE_LINE,
+    record::{unix_millis, Mode, Output, Record},
+};
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt,
+    hint::black_box,
+    mem::size_of,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// State the working set is left in before the streaming reads evict it
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Eviction {
+    /// Read every line of the working set, so evicting it needs no writebacks
+    Clean,
+
+    /// Store to every line of the working set, so evicting it writes each line back to memory
+    Dirty,
+}
+
+impl fmt::Display for Eviction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+#[derive(Parser)]
+pub struct WritebackArgs {
+    /// Initial size in bytes of the working set, and of the region streamed through to evict it
+    #[clap(short = 'd', long, default_value_t = 64 * 1024)]
+    min_size: usize,
+
+    /// Maximum size in bytes of the working set
+    #[clap(short, long, default_value_t = 256 * 1024 * 1024)]
+    max_size: usize,
+
+    /// Minimum number of 8 byte words to stream through per state and size. The working set is
+    /// prepared again, untimed, before each pass over the streamed region
+    #[clap(short, long, default_value_t = 1_000_000_000)]
+    iterations: usize,
+
+    /// States to leave the working set in before streaming at each size
+    #[clap(short, long, value_delimiter = ',', default_value = "clean,dirty")]
+    eviction: Vec<Eviction>,
+
+    /// Output file to record results to
+    #[clap(short, long)]
+    out: Option<PathBuf>,
+
+    /// Add results to the end of the output file, rather than overwriting it
+    #[clap(long)]
+    append: bool,
+}
+
+pub fn run_writeback(args: WritebackArgs) -> Result<(), Box<dyn Error>> {
+    if args.min_size < CACHE_LINE || args.max_size < args.min_size {
+        return Err(format!(
+            "--min-size must be at least {CACHE_LINE} bytes and no larger than --max-size"
+        )
+        .into());
+    }
+
+    let mut out = Output::create(args.out, args.append)?;
+
+    println!("Allocating buffers");
+    let len = args.max_size / size_of::<u64>();
+    let mut working_set: Vec<u64> = vec![1; len];
+    let stream: Vec<u64> = vec![2; len];
+
+    let line_elements = CACHE_LINE / size_of::<u64>();
+    let mut size = args.min_size;
+    while size <= args.max_size {
+        let elements = size / size_of::<u64>();
+        let passes = args.iterations.div_ceil(elements);
+        for &eviction in &args.eviction {
+            println!("Testing {eviction} evictions of {size} bytes");
+            let start_time = unix_millis();
+            let mut total_duration = Duration::ZERO;
+            let mut sum: u64 = 0;
+            for pass in 0..passes {
+                let working_set = black_box(&mut working_set[..elements]);
+                match eviction {
+                    Eviction::Clean => {
+                        for line in working_set.iter().step_by(line_elements) {
+                            sum = sum.wrapping_add(*line);
+                        }
+                    }
+                    Eviction::Dirty => {
+                        for line in working_set.iter_mut().step_by(line_elements) {
+                            *line = pass as u64;
+                        }
+                    }
+                }
+
+                let start_instant = Instant::now();
+                // Hide the stream from the optimizer so repeated passes aren't folded into one
+                for word in black_box(&stream[..elements]) {
+                    sum = sum.wrapping_add(*word);
+                }
+                total_duration += Instant::now().duration_since(start_instant);
+            }
+            let total_duration_float = total_duration.as_secs_f32();
+            let streamed = passes * elements;
+            let steps_per_second = (streamed as f32) / total_duration_float;
+            let gigabytes_per_second =
+                (streamed * size_of::<u64>()) as f32 / total_duration_float / 1e9;
+            println!(
+                "Completed testing: took {total_duration_float:.3} secs, with an average streaming bandwidth of {gigabytes_per_second:.2} GB/s. sum: {sum}"
+            );
+            out.write(&Record {
+                start_time,
+                step_size: size as u64,
+                total_duration_millis: total_duration.as_millis(),
+                steps_per_second,
+                mode: Mode::Writeback,
+                eviction: Some(eviction),
+                gigabytes_per_second: Some(gigabytes_per_second),
+                ..Record::default()
+            })?;
+        }
+        size <<= 1;
+    }
+    println!("Finished running tests");
+    out.finish()?;
+
+    Ok(())
+}